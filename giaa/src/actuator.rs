@@ -1,28 +1,125 @@
-use std::collections::HashMap;
+use std::fmt;
 
 use anyhow::Result;
 use common::{Point, point_offset};
-use metadata::{ARTIFACT_INFO, Coordinate, RuleAction};
-use parser::{ExprResult, ExprVar, ExprVarKey, Parser};
-use tracing::info;
+use metadata::{ARTIFACT_INFO, CharacterFile, Coordinate, CoordinateData, RuleAction};
+use parser::{ExprVar, ExprVarKey, Parser, Program, Record, RecordLayout};
+use tracing::{info, warn};
 use window::Window;
 
-use crate::{artifact::Artifact, converter::Converter, rule_expr::RuleExpr};
+use crate::{
+    args::Args, artifact::Artifact, converter::Converter, global::GlobalVar, rule_expr::RuleExpr,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ActuatorResult {
     UnlockAndUnmark,
     OnlyLock,
     LockAndMark,
 }
 
-/// 动作执行器, 依据规则表达式和圣遗物识别信息, 执行动作
-pub struct Actuator<'a> {
-    parser: &'a Parser,
+impl ActuatorResult {
+    /// 圣遗物当前的锁定和标记状态
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    pub fn from_artifact(artifact: &Artifact) -> Self {
+        if artifact.locked {
+            if artifact.marked {
+                Self::LockAndMark
+            } else {
+                Self::OnlyLock
+            }
+        } else {
+            if artifact.marked {
+                unreachable!("不存在未锁定但标记的圣遗物")
+            } else {
+                Self::UnlockAndUnmark
+            }
+        }
+    }
+}
+
+impl fmt::Display for ActuatorResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::UnlockAndUnmark => "未锁定",
+            Self::OnlyLock => "仅锁定",
+            Self::LockAndMark => "标记",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// 命中的规则及命中前后圣遗物的状态
+#[derive(Debug, Clone, Copy)]
+pub struct RuleHit {
+    /// 规则序号, 从 0 开始
+    pub index: usize,
+    /// 是否为未命中时执行的 else_action
+    pub else_branch: bool,
+    pub before: ActuatorResult,
+    pub after: ActuatorResult,
+}
+
+/// 记录动作收集的圣遗物, 按列表名称分组
+#[derive(Debug, Default)]
+pub struct RecordLists {
+    /// 列表名称和圣遗物描述, 按首次记录的顺序排列
+    lists: Vec<(String, Vec<String>)>,
+}
+
+impl RecordLists {
+    /// 向列表中添加圣遗物
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 列表名称
+    /// * `artifact` - 圣遗物描述
+    pub fn add(&mut self, name: &str, artifact: String) {
+        match self.lists.iter_mut().find(|(n, _)| n == name) {
+            Some((_, artifacts)) => artifacts.push(artifact),
+            None => self.lists.push((name.to_string(), vec![artifact])),
+        }
+    }
+
+    /// 输出每个列表收集的圣遗物
+    pub fn report(&self) {
+        for (name, artifacts) in self.lists.iter() {
+            info!("记录列表 '{}': {} 个圣遗物", name, artifacts.len());
+            for artifact in artifacts.iter() {
+                info!("  - {}", artifact);
+            }
+        }
+    }
+}
+
+/// 解释模式下, 比较两侧相差不超过该比例的未命中规则视为接近命中
+const NEAR_MISS_RATIO: f32 = 0.1;
+
+/// 点击按钮所需的窗口和坐标信息
+struct ClickTarget<'a> {
     coordinate: &'a Coordinate,
     converter: &'a Converter<'a>,
     window: &'a dyn Window,
+}
+
+/// 动作执行器, 依据规则表达式和圣遗物识别信息, 执行动作
+pub struct Actuator<'a> {
+    parser: &'a Parser,
+    var_key: ExprVarKey,
+    layout: RecordLayout,
+    /// `已锁定` 和 `已标记` 在记录中的槽位, 规则未用到时为 `None`
+    state_slots: (Option<usize>, Option<usize>),
+    programs: Vec<Program>,
+    /// 规则用到的背包全局变量
+    global_vars: Vec<GlobalVar>,
+    /// 为 `None` 时为离线模式, 只计算动作结果, 不点击按钮
+    target: Option<ClickTarget<'a>>,
     rule_exprs: &'a Vec<RuleExpr>,
+    characters: &'a CharacterFile,
+    args: &'a Args,
 }
 
 impl<'a> Actuator<'a> {
@@ -35,48 +132,128 @@ impl<'a> Actuator<'a> {
     /// * `converter` - 坐标转换器
     /// * `rule_exprs` - 规则与表达式映射列表
     /// * `coordinate` - 坐标数据
+    /// * `characters` - 角色权重
+    /// * `args` - 命令行参数
     pub fn new(
         parser: &'a Parser,
         window: &'a dyn Window,
         converter: &'a Converter,
         rule_exprs: &'a Vec<RuleExpr>,
         coordinate: &'a Coordinate,
+        characters: &'a CharacterFile,
+        args: &'a Args,
     ) -> Result<Self> {
-        Ok(Self {
-            parser: parser,
-            window,
-            converter,
-            rule_exprs,
+        let target = ClickTarget {
             coordinate,
-        })
+            converter,
+            window,
+        };
+        Self::build(parser, rule_exprs, characters, args, Some(target))
     }
 
-    /// 点击坐标
+    /// 构造离线动作执行器, 只计算动作结果, 不点击按钮
     ///
     /// # 参数
     ///
-    /// * `point` - 点击坐标
-    fn click(&self, point: &Point) -> Result<()> {
-        self.window
-            .click(&self.converter.translate_point(point, true)?)?;
-        Ok(())
+    /// * `parser` - 表达式解析器
+    /// * `rule_exprs` - 规则与表达式映射列表
+    /// * `characters` - 角色权重
+    /// * `args` - 命令行参数
+    pub fn offline(
+        parser: &'a Parser,
+        rule_exprs: &'a Vec<RuleExpr>,
+        characters: &'a CharacterFile,
+        args: &'a Args,
+    ) -> Result<Self> {
+        Self::build(parser, rule_exprs, characters, args, None)
+    }
+
+    /// 编译规则表达式, 构造动作执行器
+    ///
+    /// # 参数
+    ///
+    /// * `parser` - 表达式解析器
+    /// * `rule_exprs` - 规则与表达式映射列表
+    /// * `characters` - 角色权重
+    /// * `args` - 命令行参数
+    /// * `target` - 点击按钮所需的窗口和坐标信息
+    fn build(
+        parser: &'a Parser,
+        rule_exprs: &'a Vec<RuleExpr>,
+        characters: &'a CharacterFile,
+        args: &'a Args,
+        target: Option<ClickTarget<'a>>,
+    ) -> Result<Self> {
+        // 所有规则共用同一个记录布局, 每个圣遗物只需生成一次记录
+        let mut var_key = ExprVarKey::default();
+        for rule_expr in rule_exprs.iter() {
+            var_key
+                .boolean_keys
+                .extend(rule_expr.expr_var_key.boolean_keys.clone());
+            var_key
+                .number_keys
+                .extend(rule_expr.expr_var_key.number_keys.clone());
+            var_key
+                .string_keys
+                .extend(rule_expr.expr_var_key.string_keys.clone());
+        }
+        let layout = RecordLayout::new(&var_key);
+        let boolean_slot = |name: &str| layout.boolean_keys.iter().position(|key| key == name);
+        let state_slots = (
+            boolean_slot(&ARTIFACT_INFO.words.locked),
+            boolean_slot(&ARTIFACT_INFO.words.marked),
+        );
+        let mut global_vars: Vec<GlobalVar> = vec![];
+        for global_var in rule_exprs
+            .iter()
+            .flat_map(|rule_expr| &rule_expr.global_vars)
+        {
+            if !global_vars.iter().any(|v| v.name == global_var.name) {
+                global_vars.push(global_var.clone());
+            }
+        }
+        let programs = rule_exprs
+            .iter()
+            .map(|rule_expr| parser.compile(&rule_expr.expr, &layout))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            parser,
+            var_key,
+            layout,
+            state_slots,
+            programs,
+            global_vars,
+            target,
+            rule_exprs,
+            characters,
+            args,
+        })
     }
 
-    /// 识别为 "祝圣之霜定义" 时添加高度偏移
+    /// 点击按钮, 识别为 "祝圣之霜定义" 时添加高度偏移, 离线模式下不点击
     ///
     /// # 参数
     ///
-    /// * `point` - 点击坐标
+    /// * `button` - 从坐标信息中获取按钮坐标
     /// * `artifact` - 圣遗物识别信息
-    fn sanctifying_elixir_offset(&self, point: &Point, artifact: &Artifact) -> Point {
+    fn click(&self, button: fn(&CoordinateData) -> Point, artifact: &Artifact) -> Result<()> {
+        let Some(target) = &self.target else {
+            return Ok(());
+        };
+        let data = &target.coordinate.data;
+        let mut point = button(data);
         if artifact.sanctifying_elixir {
-            return point_offset(
+            point = point_offset(
                 &point,
                 None,
-                Some(self.coordinate.data.artifact_sanctifying_elixir_height as i32),
+                Some(data.artifact_sanctifying_elixir_height as i32),
             );
         }
-        point.clone()
+        target
+            .window
+            .click(&target.converter.translate_point(&point, true)?)?;
+        Ok(())
     }
 
     /// 点击锁定按钮
@@ -85,7 +262,7 @@ impl<'a> Actuator<'a> {
     ///
     /// * `artifact` - 圣遗物识别信息
     fn click_lock(&self, artifact: &Artifact) -> Result<()> {
-        self.click(&self.sanctifying_elixir_offset(&self.coordinate.data.artifact_lock, artifact))
+        self.click(|data| data.artifact_lock, artifact)
     }
 
     /// 点击标记按钮
@@ -94,7 +271,7 @@ impl<'a> Actuator<'a> {
     ///
     /// * `artifact` - 圣遗物识别信息
     fn click_mark(&self, artifact: &Artifact) -> Result<()> {
-        self.click(&self.sanctifying_elixir_offset(&self.coordinate.data.artifact_mark, artifact))
+        self.click(|data| data.artifact_mark, artifact)
     }
 
     /// 处理锁定和标记按钮
@@ -137,60 +314,206 @@ impl<'a> Actuator<'a> {
         Ok(())
     }
 
-    /// 将圣遗物信息转换为表达式变量
+    /// 将圣遗物信息转换为表达式变量, 依赖未识别字段或无法计算的变量记为未知
     ///
     /// # 参数
     ///
     /// * `artifact` - 圣遗物
-    /// * `expr_var_key` - 表达式变量键
-    fn generate_vars(&self, artifact: &Artifact, expr_var_key: &ExprVarKey) -> ExprVar {
-        // 布尔变量
-        let mut boolean_vars = HashMap::new();
-        for name in ARTIFACT_INFO.get_artifact_names() {
-            boolean_vars.insert(name, false);
+    /// * `var_key` - 需要生成的变量
+    /// * `characters` - 角色权重
+    pub fn generate_vars(
+        artifact: &Artifact,
+        var_key: &ExprVarKey,
+        characters: &CharacterFile,
+    ) -> ExprVar {
+        // 强化分析计算量较大, 每个圣遗物只计算一次
+        let analysis = artifact.analyze_rolls();
+        let boolean_maps = artifact.get_boolean_maps(analysis.as_ref());
+        let mut number_maps = artifact.get_number_maps(analysis.as_ref());
+        let mut string_maps = artifact.get_string_maps();
+        let potential_maps = artifact.get_potential_maps(&var_key.number_keys);
+        let (character_numbers, character_strings) = artifact.get_character_maps(characters);
+        number_maps.extend(character_numbers);
+        string_maps.extend(character_strings);
+        let mut expr_var = ExprVar::default();
+
+        for name in var_key.number_keys.iter() {
+            if artifact.is_unknown(name) {
+                expr_var.unknown_vars.insert(name.clone());
+                continue;
+            }
+            // 圣遗物没有的词条值为 0
+            let value = number_maps
+                .get(name)
+                .or_else(|| potential_maps.get(name))
+                .or_else(|| artifact.global_vars.get(name))
+                .copied()
+                .or_else(|| {
+                    let stat = name
+                        .strip_prefix(&format!("{}:", ARTIFACT_INFO.words.main_stat))
+                        .unwrap_or(name);
+                    ARTIFACT_INFO.stats.iter().any(|s| s == stat).then_some(0.0)
+                });
+            match value {
+                Some(value) => {
+                    expr_var.number_vars.insert(name.clone(), value);
+                }
+                None => {
+                    expr_var.unknown_vars.insert(name.clone());
+                }
+            }
         }
-        for slot in ARTIFACT_INFO.slots.iter() {
-            boolean_vars.insert(slot.clone(), false);
+        for name in var_key.boolean_keys.iter() {
+            if artifact.is_unknown(name) {
+                expr_var.unknown_vars.insert(name.clone());
+                continue;
+            }
+            // 圣遗物名称、部位和套装名只有与当前圣遗物一致时为真
+            let value = boolean_maps.get(name).copied().or_else(|| {
+                (ARTIFACT_INFO.slots.iter().any(|slot| slot == name)
+                    || ARTIFACT_INFO.get_artifact_name_by_alias(name).is_some()
+                    || ARTIFACT_INFO.get_artifact_set_name_by_alias(name).is_some())
+                .then_some(false)
+            });
+            match value {
+                Some(value) => {
+                    expr_var.boolean_vars.insert(name.clone(), value);
+                }
+                None => {
+                    expr_var.unknown_vars.insert(name.clone());
+                }
+            }
         }
-        for set_name in ARTIFACT_INFO.get_artifact_set_names() {
-            boolean_vars.insert(set_name, false);
+        for name in var_key.string_keys.iter() {
+            if artifact.is_unknown(name) {
+                expr_var.unknown_vars.insert(name.clone());
+                continue;
+            }
+            match string_maps.get(name) {
+                Some(value) => {
+                    expr_var.string_vars.insert(name.clone(), value.clone());
+                }
+                None => {
+                    expr_var.unknown_vars.insert(name.clone());
+                }
+            }
         }
-        boolean_vars.extend(artifact.get_boolean_maps());
+        expr_var
+    }
+
+    /// 将圣遗物信息转换为表达式记录
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    fn generate_record(&self, artifact: &Artifact) -> Result<Record> {
+        let expr_var = Self::generate_vars(artifact, &self.var_key, self.characters);
+        self.layout.record(
+            |name| expr_var.number_vars.get(name).copied(),
+            |name| expr_var.boolean_vars.get(name).copied(),
+            |name| expr_var.string_vars.get(name).cloned(),
+        )
+    }
 
-        // 数字变量
-        let mut number_vars = HashMap::new();
-        for stat in ARTIFACT_INFO.stats.iter() {
-            number_vars.insert(stat.clone(), 0.0);
-            number_vars.insert(format!("{}:{}", ARTIFACT_INFO.words.main_stat, stat), 0.0);
+    /// 动作只改变锁定和标记状态, 只需更新记录中对应的槽位
+    ///
+    /// # 参数
+    ///
+    /// * `record` - 圣遗物的表达式记录
+    /// * `artifact` - 执行动作后的圣遗物
+    fn update_state(&self, record: &mut Record, artifact: &Artifact) {
+        let (locked_slot, marked_slot) = self.state_slots;
+        if let Some(slot) = locked_slot {
+            record.booleans[slot] = Some(artifact.locked);
         }
-        number_vars.extend(artifact.get_number_maps());
+        if let Some(slot) = marked_slot {
+            record.booleans[slot] = Some(artifact.marked);
+        }
+    }
 
-        // 筛选表达式所需要的变量
-        let boolean_vars = boolean_vars
-            .iter()
-            .filter_map(|(name, value)| {
-                if expr_var_key.boolean_keys.contains(&name) {
-                    Some((name.clone(), *value))
-                } else {
-                    None
-                }
-            })
-            .collect::<HashMap<_, _>>();
-        let number_vars = number_vars
-            .iter()
-            .filter_map(|(name, value)| {
-                if expr_var_key.number_keys.contains(&name) {
-                    Some((name.clone(), *value))
-                } else {
-                    None
+    /// 输出规则的求值过程, 只输出命中、接近命中和结果未知的规则
+    ///
+    /// # 参数
+    ///
+    /// * `rule_expr` - 规则表达式
+    /// * `artifact` - 圣遗物
+    /// * `hit` - 规则结果, `None` 表示未知
+    fn explain(&self, rule_expr: &RuleExpr, artifact: &Artifact, hit: Option<bool>) -> Result<()> {
+        let expr_var = Self::generate_vars(artifact, &self.var_key, self.characters);
+        let explain = self.parser.explain(&rule_expr.expr, &expr_var)?;
+        let description = &rule_expr.rule.description;
+        match hit {
+            Some(true) => info!("规则求值: {}, {}", description, explain),
+            Some(false) if explain.is_near_miss(NEAR_MISS_RATIO) => {
+                info!("规则接近命中: {}, {}", description, explain)
+            }
+            Some(false) => {}
+            None => info!("规则结果未知: {}, {}", description, explain),
+        }
+        Ok(())
+    }
+
+    /// 规则是否用到背包全局变量, 用到时需扫描完整个列表后才能执行规则
+    pub fn has_global_vars(&self) -> bool {
+        !self.global_vars.is_empty()
+    }
+
+    /// 计算背包全局变量, 并写入每个圣遗物
+    ///
+    /// # 参数
+    ///
+    /// * `artifacts` - 背包中的所有圣遗物
+    pub fn fill_global_vars(&self, artifacts: &mut [Artifact]) -> Result<()> {
+        for global_var in self.global_vars.iter() {
+            let values = global_var.compute(artifacts, self.parser, self.characters)?;
+            for (artifact, value) in artifacts.iter_mut().zip(values) {
+                match value {
+                    Some(value) => {
+                        artifact.global_vars.insert(global_var.name.clone(), value);
+                    }
+                    None => {
+                        artifact.global_vars.remove(&global_var.name);
+                    }
                 }
-            })
-            .collect::<HashMap<_, _>>();
+            }
+        }
+        Ok(())
+    }
 
-        ExprVar {
-            boolean_vars,
-            number_vars,
+    /// 输出被支配圣遗物的支配链, 便于在取消锁定前核对
+    ///
+    /// # 参数
+    ///
+    /// * `artifacts` - 背包中的所有圣遗物
+    /// * `label` - 圣遗物序号对应的描述, 例如所在的行列
+    pub fn report_dominance(
+        &self,
+        artifacts: &[Artifact],
+        label: impl Fn(usize) -> String,
+    ) -> Result<()> {
+        for global_var in self.global_vars.iter() {
+            let Some(dominance) = global_var.dominance(artifacts, self.parser, self.characters)?
+            else {
+                continue;
+            };
+            let mut dominated_count = 0;
+            for index in 0..artifacts.len() {
+                let Some(count) = dominance.count(index).filter(|count| *count > 0) else {
+                    continue;
+                };
+                dominated_count += 1;
+                let chain: Vec<String> = dominance.chain(index).into_iter().map(&label).collect();
+                info!(
+                    "{}: {} 被 {} 个圣遗物支配, 支配链: {}",
+                    global_var.name,
+                    label(index),
+                    count,
+                    chain.join(" → ")
+                );
+            }
+            info!("{}: 共 {} 个圣遗物被支配", global_var.name, dominated_count);
         }
+        Ok(())
     }
 
     /// 执行动作, 并返回更新后的圣遗物信息
@@ -199,67 +522,316 @@ impl<'a> Actuator<'a> {
     ///
     /// * `artifact` - 圣遗物
     pub fn exec(&self, artifact: &mut Artifact) -> Result<ActuatorResult> {
+        Ok(self.exec_traced(artifact)?.0)
+    }
+
+    /// 执行动作, 并返回更新后的圣遗物信息和命中的规则
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    pub fn exec_traced(&self, artifact: &mut Artifact) -> Result<(ActuatorResult, Vec<RuleHit>)> {
         // 保留圣遗物原始状态
         let before_artifact = artifact.clone();
+        let (result, hits) = self.evaluate_traced(artifact)?;
+        self.apply(&before_artifact, result)?;
+        Ok((result, hits))
+    }
 
-        // 先计算圣遗物交换状态
-        for rule_expr in self.rule_exprs.iter() {
-            let expr_var = self.generate_vars(&artifact, &rule_expr.expr_var_key);
+    /// 将执行了记录动作的圣遗物加入对应的列表
+    ///
+    /// # 参数
+    ///
+    /// * `hits` - 命中的规则
+    /// * `artifact` - 圣遗物描述
+    /// * `records` - 记录列表
+    pub fn collect_records(&self, hits: &[RuleHit], artifact: &str, records: &mut RecordLists) {
+        for hit in hits {
+            let rule = &self.rule_exprs[hit.index].rule;
+            let action = if hit.else_branch {
+                &rule.else_action
+            } else {
+                &rule.action
+            };
+            if *action == Some(RuleAction::Record) {
+                records.add(rule.record_list_name(), artifact.to_string());
+            }
+        }
+    }
 
-            if let ExprResult::Boolean(result) = self.parser.exec(&rule_expr.expr, &expr_var)? {
-                if !result {
-                    continue;
+    /// 按动作修改圣遗物的锁定和标记状态
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    /// * `action` - 动作
+    fn apply_action(artifact: &mut Artifact, action: &RuleAction) {
+        match action {
+            RuleAction::ClickLock => {
+                artifact.locked = !artifact.locked;
+                if artifact.marked && !artifact.locked {
+                    artifact.marked = false;
                 }
-                info!("规则命中: {}", rule_expr.rule.description);
-                match rule_expr.rule.action {
-                    RuleAction::ClickLock => {
-                        artifact.locked = !artifact.locked;
-                        if artifact.marked && !artifact.locked {
-                            artifact.marked = false;
-                        }
-                    }
-                    RuleAction::ClickMark => {
-                        artifact.marked = !artifact.marked;
-                        if !artifact.locked && artifact.marked {
-                            artifact.locked = true;
-                        }
-                    }
-                    RuleAction::Lock => {
-                        artifact.locked = true;
-                    }
-                    RuleAction::OnlyLock => {
-                        artifact.locked = true;
-                        artifact.marked = false;
-                    }
-                    RuleAction::LockAndMark => {
-                        artifact.locked = true;
-                        artifact.marked = true;
-                    }
-                    RuleAction::UnLockAndMark => {
-                        artifact.locked = false;
-                        artifact.marked = false;
-                    }
+            }
+            RuleAction::ClickMark => {
+                artifact.marked = !artifact.marked;
+                if !artifact.locked && artifact.marked {
+                    artifact.locked = true;
+                }
+            }
+            RuleAction::Lock => {
+                artifact.locked = true;
+            }
+            RuleAction::OnlyLock => {
+                artifact.locked = true;
+                artifact.marked = false;
+            }
+            RuleAction::LockAndMark => {
+                artifact.locked = true;
+                artifact.marked = true;
+            }
+            RuleAction::UnLockAndMark => {
+                artifact.locked = false;
+                artifact.marked = false;
+            }
+            RuleAction::Unmark => {
+                artifact.marked = false;
+            }
+            RuleAction::UnlockUnmarked => {
+                // 标记的圣遗物保持锁定
+                if !artifact.marked {
+                    artifact.locked = false;
                 }
             }
+            RuleAction::Mark => {
+                artifact.locked = true;
+                artifact.marked = true;
+            }
+            RuleAction::Record => {}
         }
+    }
 
-        // 更改状态
-        let result = if artifact.locked {
-            if artifact.marked {
-                self.handle_lock_and_mark(&before_artifact)?;
-                ActuatorResult::LockAndMark
-            } else {
-                self.handle_only_lock(&before_artifact)?;
-                ActuatorResult::OnlyLock
+    /// 依次执行规则, 计算圣遗物的目标锁定和标记状态, 并返回命中的规则, 不点击按钮
+    ///
+    /// 规则未命中时执行 else_action 并跳过其子规则, 命中设置了 stop 的规则后不再执行后续规则
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物, 执行后为目标状态
+    pub fn evaluate_traced(
+        &self,
+        artifact: &mut Artifact,
+    ) -> Result<(ActuatorResult, Vec<RuleHit>)> {
+        let mut record = self.generate_record(artifact)?;
+        let mut hits = vec![];
+        // 命中设置了 stop 的规则后, 执行完其子规则即结束
+        let mut stop_at = self.rule_exprs.len();
+        let mut index = 0;
+        while index < stop_at {
+            let rule_expr = &self.rule_exprs[index];
+            let rule = &rule_expr.rule;
+            let hit = self.programs[index].eval(&record)?;
+            if self.args.explain {
+                self.explain(rule_expr, artifact, hit)?;
             }
-        } else {
-            if artifact.marked {
-                unreachable!("不存在未锁定但标记的圣遗物")
+            let Some(hit) = hit else {
+                // 结果未知时不执行动作和子规则, 避免误操作
+                warn!("规则结果未知, 已跳过: {}", rule.description);
+                index += rule_expr.size;
+                continue;
+            };
+            let action = if hit {
+                info!("规则命中: {}", rule.description);
+                &rule.action
             } else {
-                self.handle_un_lock_and_mark(&before_artifact)?;
-                ActuatorResult::UnlockAndUnmark
+                &rule.else_action
+            };
+            if hit || action.is_some() {
+                let before = ActuatorResult::from_artifact(artifact);
+                if let Some(action) = action {
+                    if !hit {
+                        info!("规则未命中, 执行 else_action: {}", rule.description);
+                    }
+                    if *action == RuleAction::Record {
+                        info!("记录到列表 '{}'", rule.record_list_name());
+                    }
+                    Self::apply_action(artifact, action);
+                    // 动作会影响后续规则的判断, 需更新记录中的状态
+                    self.update_state(&mut record, artifact);
+                }
+                hits.push(RuleHit {
+                    index,
+                    else_branch: !hit,
+                    before,
+                    after: ActuatorResult::from_artifact(artifact),
+                });
+            }
+            if !hit {
+                index += rule_expr.size;
+                continue;
             }
+            if rule.stop {
+                info!("规则设置了 stop, 不再执行后续规则: {}", rule.description);
+                stop_at = stop_at.min(index + rule_expr.size);
+            }
+            index += 1;
+        }
+        Ok((ActuatorResult::from_artifact(artifact), hits))
+    }
+
+    /// 点击按钮, 将圣遗物从当前状态改为目标状态
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 当前状态的圣遗物
+    /// * `result` - 目标状态
+    pub fn apply(&self, artifact: &Artifact, result: ActuatorResult) -> Result<()> {
+        match result {
+            ActuatorResult::LockAndMark => self.handle_lock_and_mark(artifact),
+            ActuatorResult::OnlyLock => self.handle_only_lock(artifact),
+            ActuatorResult::UnlockAndUnmark => self.handle_un_lock_and_mark(artifact),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metadata::Rule;
+
+    use super::*;
+    use crate::create_parser;
+
+    #[test]
+    fn test_evaluate_nested_rules() {
+        let yaml = "
+- description: 5星分组
+  expression: 星级 == 5
+  stop: true
+  rules:
+    - description: 双爆
+      expression: 暴击率 > 0
+      action: 锁定
+      else_action: 取消锁定和标记
+    - description: 满级
+      expression: 等级 == 20
+      action: 锁定和标记
+- description: 非5星
+  expression: 星级 >= 1
+  action: 仅锁定
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        let characters = CharacterFile::default();
+        let mut parser = create_parser(&characters).unwrap();
+        let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser).unwrap();
+        assert_eq!(rule_exprs.len(), 4);
+        assert_eq!(rule_exprs[0].size, 3);
+        assert_eq!(rule_exprs[2].parent, Some(0));
+        let args = <Args as clap::Parser>::parse_from(["giaa"]);
+        let actuator = Actuator::offline(&parser, &rule_exprs, &characters, &args).unwrap();
+        let evaluate = |yaml: &str| {
+            let value = serde_yaml::from_str(yaml).unwrap();
+            let mut artifact = Artifact::from_yaml_value(&value).unwrap();
+            let (result, hits) = actuator.evaluate_traced(&mut artifact).unwrap();
+            let hits: Vec<(usize, bool)> = hits
+                .iter()
+                .map(|hit| (hit.index, hit.else_branch))
+                .collect();
+            (result, hits)
+        };
+
+        assert_eq!(
+            evaluate("{ stars: 5, level: 20, sub_stats: [{ name: 暴击率, value: 3 }] }"),
+            (
+                ActuatorResult::LockAndMark,
+                vec![(0, false), (1, false), (2, false)]
+            )
+        );
+        assert_eq!(
+            evaluate("{ stars: 5, level: 4, locked: true, sub_stats: [] }"),
+            (ActuatorResult::UnlockAndUnmark, vec![(0, false), (1, true)])
+        );
+        assert_eq!(
+            evaluate("{ stars: 4, level: 20, sub_stats: [{ name: 暴击率, value: 3 }] }"),
+            (ActuatorResult::OnlyLock, vec![(3, false)])
+        );
+    }
+
+    #[test]
+    fn test_evaluate_state_update() {
+        let yaml = "
+- description: 锁定5星
+  expression: 星级 == 5
+  action: 锁定
+- description: 标记已锁定的满级圣遗物
+  expression: 已锁定 && !已标记 && 等级 == 20
+  action: 锁定和标记
+- description: 解锁未锁定的
+  expression: '!已锁定'
+  action: 取消锁定和标记
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        let characters = CharacterFile::default();
+        let mut parser = create_parser(&characters).unwrap();
+        let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser).unwrap();
+        let args = <Args as clap::Parser>::parse_from(["giaa"]);
+        let actuator = Actuator::offline(&parser, &rule_exprs, &characters, &args).unwrap();
+        let evaluate = |yaml: &str| {
+            let value = serde_yaml::from_str(yaml).unwrap();
+            let mut artifact = Artifact::from_yaml_value(&value).unwrap();
+            let (result, hits) = actuator.evaluate_traced(&mut artifact).unwrap();
+            let hits: Vec<usize> = hits.iter().map(|hit| hit.index).collect();
+            (result, hits)
         };
-        Ok(result)
+
+        // 后续规则读取到前面规则执行动作后的状态
+        assert_eq!(
+            evaluate("{ stars: 5, level: 20 }"),
+            (ActuatorResult::LockAndMark, vec![0, 1])
+        );
+        assert_eq!(
+            evaluate("{ stars: 4, level: 20 }"),
+            (ActuatorResult::UnlockAndUnmark, vec![2])
+        );
+    }
+
+    #[test]
+    fn test_apply_action() {
+        let apply = |locked: bool, marked: bool, action: RuleAction| {
+            let mut artifact = Artifact {
+                locked,
+                marked,
+                ..Default::default()
+            };
+            Actuator::apply_action(&mut artifact, &action);
+            ActuatorResult::from_artifact(&artifact)
+        };
+        use ActuatorResult::*;
+        assert_eq!(apply(true, true, RuleAction::Unmark), OnlyLock);
+        assert_eq!(apply(false, false, RuleAction::Unmark), UnlockAndUnmark);
+        assert_eq!(
+            apply(true, false, RuleAction::UnlockUnmarked),
+            UnlockAndUnmark
+        );
+        assert_eq!(apply(true, true, RuleAction::UnlockUnmarked), LockAndMark);
+        assert_eq!(apply(false, false, RuleAction::Mark), LockAndMark);
+        assert_eq!(apply(true, false, RuleAction::Record), OnlyLock);
+    }
+
+    #[test]
+    fn test_record_lists() {
+        let mut records = RecordLists::default();
+        records.add("高分", String::from("A"));
+        records.add("清理", String::from("B"));
+        records.add("高分", String::from("C"));
+        assert_eq!(
+            records.lists,
+            vec![
+                (
+                    String::from("高分"),
+                    vec![String::from("A"), String::from("C")]
+                ),
+                (String::from("清理"), vec![String::from("B")]),
+            ]
+        );
     }
 }
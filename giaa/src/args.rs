@@ -1,10 +1,16 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use tracing::Level;
 
+use crate::export::{ExportFormat, ExportTarget};
+
 /// 欢迎使用 GIAA (Genshin Impact Artifact Assistant) 原神圣遗物助手
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    /// 子命令, 未指定时扫描游戏中的圣遗物
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// 原神窗口名称
     #[arg(short, long, default_values = ["原神", "Genshin Impact"])]
     pub window_titles: Vec<String>,
@@ -13,9 +19,18 @@ pub struct Args {
     #[arg(long, default_value_t = false)]
     pub list_window_titles: bool,
 
-    /// 规则文件路径
-    #[arg(short, long, default_value = "rules.yaml")]
-    pub rules_file: String,
+    /// 规则文件路径, 可多次指定, 按顺序合并. 角色权重文件从第一个规则文件所在目录加载
+    #[arg(
+        short,
+        long = "rules-file",
+        default_value = "rules.yaml",
+        global = true
+    )]
+    pub rules_files: Vec<String>,
+
+    /// 启用的规则配置 (profiles) 名称, 可多次指定, 未指定时执行所有规则
+    #[arg(long = "profile", global = true)]
+    pub profiles: Vec<String>,
 
     /// 日志等级 (trace, debug, info, warn, error)
     #[arg(short, long, default_value = "info")]
@@ -40,6 +55,58 @@ pub struct Args {
     /// 启用识别严格模式 (严格模式下: 识别圣遗物需全部属性正确才会执行动作)
     #[arg(long, default_value_t = false)]
     pub strict_mode: bool,
+
+    /// 仅扫描识别圣遗物, 不加载规则文件, 不执行任何动作
+    #[arg(long, default_value_t = false)]
+    pub scan_only: bool,
+
+    /// 将识别到的圣遗物导出到文件, 格式为 <格式>:<路径>, 可多次指定.
+    /// 支持的格式: good (GOOD v2), mona (莫娜占卜铺), csv (副词条为固定列), jsonl (JSON Lines)
+    #[arg(long = "export", value_name = "FORMAT:PATH")]
+    pub exports: Vec<ExportTarget>,
+
+    /// 输出命中、接近命中 (比较两侧相差 10% 以内) 和结果未知的规则的求值过程
+    #[arg(long, default_value_t = false, global = true)]
+    pub explain: bool,
+}
+
+/// 子命令
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 离线测试表达式或规则文件, 无需启动游戏
+    Eval(EvalArgs),
+    /// 离线模拟执行规则文件, 从导出的背包文件读取圣遗物, 不点击按钮
+    Simulate(SimulateArgs),
+    /// 执行规则文件中的测试用例 (examples), 有用例未通过时以非零状态码退出
+    TestRules,
+    /// 输出合并引用文件并按配置筛选后的规则列表, 序号与其他命令输出的规则序号一致
+    ListRules,
+    /// 静态检查规则文件, 报告恒为真或恒为假、总会被覆盖和未限制星级就取消锁定的规则, 存在错误时以非零状态码退出
+    CheckRules,
+}
+
+/// 离线测试参数
+#[derive(clap::Args, Debug)]
+pub struct EvalArgs {
+    /// 圣遗物数据文件路径 (YAML 或 JSON, 字段与识别出的圣遗物信息一致)
+    #[arg(short, long)]
+    pub artifact: String,
+
+    /// 要测试的表达式, 未指定时执行规则文件中的所有规则
+    #[arg(short, long)]
+    pub expression: Option<String>,
+}
+
+/// 离线模拟参数
+#[derive(clap::Args, Debug)]
+pub struct SimulateArgs {
+    /// 背包文件路径, 可以是 giaa 或其他工具导出的文件
+    #[arg(short, long)]
+    pub inventory: String,
+
+    /// 背包文件格式 (good, mona, csv, jsonl), 未指定时根据扩展名和内容推断
+    #[arg(short, long)]
+    pub format: Option<ExportFormat>,
 }
 
 impl Args {
@@ -47,4 +114,14 @@ impl Args {
     pub fn new() -> Self {
         Self::parse()
     }
+
+    /// 第一个规则文件, 角色权重文件从其所在目录加载
+    pub fn main_rules_file(&self) -> &str {
+        &self.rules_files[0]
+    }
+
+    /// 所有规则文件, 用于日志提示
+    pub fn rules_files_display(&self) -> String {
+        self.rules_files.join(", ")
+    }
 }
@@ -1,17 +1,55 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+};
 
-use metadata::ARTIFACT_INFO;
+use anyhow::{Context, Result, bail};
+use metadata::{
+    ARTIFACT_INFO, CRIT_SCORE_WEIGHTS, CharacterFile, RollAnalysis, STAT_TABLE, ScoreDistribution,
+};
+use parser::split_param_var;
+use serde::{Deserialize, Serialize};
 
 /// 圣遗物副词条
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArtifactSubStat {
     pub name: String,
     pub value: f32,
+    #[serde(default)]
     pub unactivated: bool,
 }
 
+/// 可能识别失败的圣遗物字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactField {
+    Name,
+    Slot,
+    MainStat,
+    MainStatValue,
+    Level,
+    SubStats,
+    SetName,
+}
+
+impl fmt::Display for ArtifactField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ArtifactField::Name => "名称",
+            ArtifactField::Slot => "部位",
+            ArtifactField::MainStat => "主词条",
+            ArtifactField::MainStatValue => "主词条值",
+            ArtifactField::Level => "等级",
+            ArtifactField::SubStats => "副词条",
+            ArtifactField::SetName => "套装名称",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// 圣遗物识别信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Artifact {
     pub name: String,
     pub slot: String,
@@ -25,13 +63,36 @@ pub struct Artifact {
     pub sub_stats: Vec<ArtifactSubStat>,
     pub set_name: String,
     pub equipped: bool,
+    /// 未能识别的字段, 依赖这些字段的变量值未知
+    pub unknown_fields: HashSet<ArtifactField>,
+    /// 等级与主词条值不符, 识别结果有误
+    pub misidentified: bool,
+    /// 背包全局变量的值, 扫描完整个列表后计算, 没有值的变量未知
+    #[serde(skip)]
+    pub global_vars: HashMap<String, f32>,
 }
 
 impl fmt::Display for Artifact {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unknown_fields = if self.unknown_fields.is_empty() {
+            String::new()
+        } else {
+            let mut fields: Vec<String> = self
+                .unknown_fields
+                .iter()
+                .map(|field| field.to_string())
+                .collect();
+            fields.sort();
+            format!(", 未识别: [{}]", fields.join(", "))
+        };
+        let misidentified = if self.misidentified {
+            ", 识别异常: 等级与主词条值不符"
+        } else {
+            ""
+        };
         write!(
             f,
-            "圣遗物 {{ 名称: {}, 部位: {}, 主词条: {}, 主词条值: {}, 星级: {}, 是否祝圣之霜定义: {}, 等级: {}, 是否标记: {}, 是否锁定: {}, 副词条: [{}], 套装名称: {}, 是否装备: {} }}",
+            "圣遗物 {{ 名称: {}, 部位: {}, 主词条: {}, 主词条值: {}, 星级: {}, 是否祝圣之霜定义: {}, 等级: {}, 是否标记: {}, 是否锁定: {}, 副词条: [{}], 套装名称: {}, 是否装备: {}{}{} }}",
             self.name,
             self.slot,
             self.main_stat,
@@ -54,18 +115,299 @@ impl fmt::Display for Artifact {
                 .collect::<Vec<String>>()
                 .join(", "),
             self.set_name,
-            self.equipped
+            self.equipped,
+            unknown_fields,
+            misidentified
         )
     }
 }
 
 impl Artifact {
+    /// 从 YAML 或 JSON 文件加载圣遗物信息, 名称和套装名支持别名
+    ///
+    /// # 参数
+    ///
+    /// * `artifact_file` - 圣遗物文件路径
+    pub fn load(artifact_file: &str) -> Result<Self> {
+        let data = fs::read(artifact_file)
+            .with_context(|| format!("读取圣遗物文件 {} 失败", artifact_file))?;
+        // JSON 是 YAML 的子集, 统一按 YAML 解析
+        let mut artifact: Self = serde_yaml::from_slice(&data)
+            .with_context(|| format!("解析圣遗物文件 {} 失败", artifact_file))?;
+        artifact.normalize()?;
+        Ok(artifact)
+    }
+
+    /// 从 YAML 值构造圣遗物信息, 例如规则测试用例中的圣遗物, 名称和套装名支持别名
+    ///
+    /// # 参数
+    ///
+    /// * `value` - YAML 值
+    pub fn from_yaml_value(value: &serde_yaml::Value) -> Result<Self> {
+        let mut artifact: Self =
+            serde_yaml::from_value(value.clone()).context("解析圣遗物信息失败")?;
+        artifact.normalize()?;
+        Ok(artifact)
+    }
+
+    /// 将名称和套装名的别名替换为正式名称, 并检查部位、词条和锁定状态是否有效
+    pub fn normalize(&mut self) -> Result<()> {
+        if !self.name.is_empty() {
+            let Some(name) = ARTIFACT_INFO.get_artifact_name_by_alias(&self.name) else {
+                bail!("圣遗物名称 '{}' 不存在", self.name);
+            };
+            self.name = name;
+        }
+        if !self.set_name.is_empty() {
+            let Some(set_name) = ARTIFACT_INFO.get_artifact_set_name_by_alias(&self.set_name)
+            else {
+                bail!("圣遗物套装 '{}' 不存在", self.set_name);
+            };
+            self.set_name = set_name;
+        }
+        if !self.slot.is_empty() && !ARTIFACT_INFO.slots.contains(&self.slot) {
+            bail!("圣遗物部位 '{}' 不存在", self.slot);
+        }
+        let stats = self
+            .sub_stats
+            .iter()
+            .map(|sub_stat| &sub_stat.name)
+            .chain([&self.main_stat].into_iter().filter(|s| !s.is_empty()));
+        for stat in stats {
+            if !ARTIFACT_INFO.stats.contains(stat) {
+                bail!("圣遗物词条 '{}' 不存在", stat);
+            }
+        }
+        if self.marked && !self.locked {
+            bail!("不存在未锁定但标记的圣遗物");
+        }
+        Ok(())
+    }
+
+    /// 判断两次识别的是否为同一个圣遗物, 比较识别到的属性和锁定、标记状态
+    ///
+    /// # 参数
+    ///
+    /// * `other` - 另一次识别的圣遗物
+    pub fn same_as(&self, other: &Artifact) -> bool {
+        self.name == other.name
+            && self.slot == other.slot
+            && self.set_name == other.set_name
+            && self.main_stat == other.main_stat
+            && self.main_stat_value == other.main_stat_value
+            && self.stars == other.stars
+            && self.level == other.level
+            && self.locked == other.locked
+            && self.marked == other.marked
+            && self.sub_stats.len() == other.sub_stats.len()
+            && self
+                .sub_stats
+                .iter()
+                .zip(other.sub_stats.iter())
+                .all(|(a, b)| {
+                    a.name == b.name && a.value == b.value && a.unactivated == b.unactivated
+                })
+    }
+
+    /// 判断变量值是否未知, 即变量依赖的字段未能识别
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称
+    pub fn is_unknown(&self, name: &str) -> bool {
+        if self.unknown_fields.is_empty() {
+            return false;
+        }
+        let words = &ARTIFACT_INFO.words;
+        let unknown = |field| self.unknown_fields.contains(&field);
+
+        if let Some(stat) = name.strip_prefix(&format!("{}:", words.main_stat)) {
+            return ARTIFACT_INFO.stats.iter().any(|s| s == stat)
+                && (unknown(ArtifactField::MainStat) || unknown(ArtifactField::MainStatValue));
+        }
+        if *name == words.name || ARTIFACT_INFO.get_artifact_name_by_alias(name).is_some() {
+            return unknown(ArtifactField::Name);
+        }
+        if *name == words.slot || ARTIFACT_INFO.slots.iter().any(|slot| slot == name) {
+            return unknown(ArtifactField::Slot);
+        }
+        if *name == words.set_name || ARTIFACT_INFO.get_artifact_set_name_by_alias(name).is_some() {
+            return unknown(ArtifactField::SetName);
+        }
+        if *name == words.main_stat_name {
+            return unknown(ArtifactField::MainStat);
+        }
+        if *name == words.level {
+            return unknown(ArtifactField::Level);
+        }
+        if *name == words.sub_stats_count || ARTIFACT_INFO.stats.iter().any(|s| s == name) {
+            return unknown(ArtifactField::SubStats);
+        }
+        if Self::is_roll_var(name) {
+            return unknown(ArtifactField::SubStats) || unknown(ArtifactField::Level);
+        }
+        if Self::is_character_var(name) {
+            return unknown(ArtifactField::SubStats)
+                || unknown(ArtifactField::Slot)
+                || unknown(ArtifactField::MainStat)
+                || unknown(ArtifactField::SetName);
+        }
+        if Self::is_potential_var(name) {
+            return unknown(ArtifactField::SubStats)
+                || unknown(ArtifactField::Level)
+                || unknown(ArtifactField::MainStat);
+        }
+        false
+    }
+
+    /// 判断变量是否为角色评分变量, 这些变量依赖部位、主词条、套装和副词条
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称
+    pub fn is_character_var(name: &str) -> bool {
+        let words = &ARTIFACT_INFO.words;
+        *name == words.best_character_score
+            || *name == words.best_character
+            || name.starts_with(&format!("{}:", words.character_score))
+    }
+
+    /// 获取角色评分变量的值, 分别为数字和字符串变量
+    ///
+    /// 评分最高的角色有多个时取角色列表中靠前的角色, 所有角色评分都为 0 时最适角色为 "无"
+    ///
+    /// # 参数
+    ///
+    /// * `characters` - 角色权重
+    pub fn get_character_maps(
+        &self,
+        characters: &CharacterFile,
+    ) -> (HashMap<String, f32>, HashMap<String, String>) {
+        let words = &ARTIFACT_INFO.words;
+        let sub_stats: Vec<(&str, f32, bool)> = self
+            .sub_stats
+            .iter()
+            .map(|sub_stat| (sub_stat.name.as_str(), sub_stat.value, sub_stat.unactivated))
+            .collect();
+        let mut numbers = HashMap::new();
+        let mut best: Option<(&str, f64)> = None;
+        for (character, score) in
+            characters.scores(&self.slot, &self.main_stat, &self.set_name, &sub_stats)
+        {
+            numbers.insert(
+                ARTIFACT_INFO.get_character_score_key(character),
+                score as f32,
+            );
+            if score > 0.0 && best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((character, score));
+            }
+        }
+        let (best_character, best_score) = best.unwrap_or((&words.no_character, 0.0));
+        numbers.insert(words.best_character_score.clone(), best_score as f32);
+        let strings = HashMap::from([(words.best_character.clone(), best_character.to_string())]);
+        (numbers, strings)
+    }
+
+    /// 判断变量是否为满级评分估计变量, 这些变量依赖星级、等级、主词条和副词条
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称, 带参数的变量包含参数
+    pub fn is_potential_var(name: &str) -> bool {
+        let words = &ARTIFACT_INFO.words;
+        *name == words.expected_crit_score
+            || split_param_var(name).is_some_and(|(name, _)| name == words.crit_score_probability)
+    }
+
+    /// 计算强化到满级时双爆分的分布, 无法计算时返回 `None`
+    pub fn crit_score_distribution(&self) -> Option<ScoreDistribution> {
+        let sub_stats: Vec<(&str, f32, bool)> = self
+            .sub_stats
+            .iter()
+            .map(|sub_stat| (sub_stat.name.as_str(), sub_stat.value, sub_stat.unactivated))
+            .collect();
+        let weights = CRIT_SCORE_WEIGHTS
+            .iter()
+            .map(|(name, weight)| (name.to_string(), *weight))
+            .collect();
+        STAT_TABLE.score_distribution(
+            self.stars as u32,
+            self.level as u32,
+            &self.main_stat,
+            &sub_stats,
+            &weights,
+        )
+    }
+
+    /// 获取满级评分估计变量的值
+    ///
+    /// 计算分布的开销较大, 只在用到这些变量时计算, 无法计算时不提供变量值
+    ///
+    /// # 参数
+    ///
+    /// * `names` - 用到的数字变量名称
+    pub fn get_potential_maps(&self, names: &[String]) -> HashMap<String, f32> {
+        let mut result = HashMap::new();
+        if !names.iter().any(|name| Self::is_potential_var(name)) {
+            return result;
+        }
+        let Some(distribution) = self.crit_score_distribution() else {
+            return result;
+        };
+        let words = &ARTIFACT_INFO.words;
+        for name in names {
+            if *name == words.expected_crit_score {
+                result.insert(name.clone(), distribution.expected() as f32);
+            } else if let Some((key, target)) = split_param_var(name)
+                && key == words.crit_score_probability
+                && let Ok(target) = target.parse::<f64>()
+            {
+                result.insert(
+                    name.clone(),
+                    distribution.probability_at_least(target) as f32,
+                );
+            }
+        }
+        result
+    }
+
+    /// 判断变量是否为副词条强化分析变量, 这些变量依赖星级、等级和副词条
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称
+    pub fn is_roll_var(name: &str) -> bool {
+        let words = &ARTIFACT_INFO.words;
+        [
+            &words.effective_rolls,
+            &words.upgrades,
+            &words.initial_sub_stats,
+            &words.ambiguous_rolls,
+        ]
+        .iter()
+        .any(|word| *word == name)
+            || STAT_TABLE
+                .sub_stats
+                .iter()
+                .any(|sub_stat| ARTIFACT_INFO.get_roll_count_key(&sub_stat.name) == name)
+    }
+
+    /// 分析副词条的强化次数和档位, 无法分析时返回 `None`
+    pub fn analyze_rolls(&self) -> Option<RollAnalysis> {
+        let sub_stats: Vec<(&str, f32, bool)> = self
+            .sub_stats
+            .iter()
+            .map(|sub_stat| (sub_stat.name.as_str(), sub_stat.value, sub_stat.unactivated))
+            .collect();
+        STAT_TABLE.analyze_rolls(self.stars as u32, self.level as u32, &sub_stats)
+    }
+
     /// 获取圣遗物布尔类型的数据
     ///
     /// # 参数
     ///
-    /// * `words` - 圣遗物词条信息名称
-    pub fn get_boolean_maps(&self) -> HashMap<String, bool> {
+    /// * `analysis` - 副词条强化分析结果, 由 `analyze_rolls` 计算
+    pub fn get_boolean_maps(&self, analysis: Option<&RollAnalysis>) -> HashMap<String, bool> {
         let mut result = HashMap::new();
         result.insert(self.name.clone(), true);
         result.insert(self.slot.clone(), true);
@@ -77,15 +419,34 @@ impl Artifact {
         result.insert(ARTIFACT_INFO.words.equipped.clone(), self.equipped);
         result.insert(ARTIFACT_INFO.words.marked.clone(), self.marked);
         result.insert(ARTIFACT_INFO.words.locked.clone(), self.locked);
+        if let Some(analysis) = analysis {
+            result.insert(
+                ARTIFACT_INFO.words.ambiguous_rolls.clone(),
+                analysis.ambiguous,
+            );
+        }
         result
     }
 
+    /// 获取圣遗物字符串类型的数据
+    pub fn get_string_maps(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (ARTIFACT_INFO.words.name.clone(), self.name.clone()),
+            (ARTIFACT_INFO.words.slot.clone(), self.slot.clone()),
+            (ARTIFACT_INFO.words.set_name.clone(), self.set_name.clone()),
+            (
+                ARTIFACT_INFO.words.main_stat_name.clone(),
+                self.main_stat.clone(),
+            ),
+        ])
+    }
+
     /// 获取圣遗物数值类型的数据
     ///
     /// # 参数
     ///
-    /// * `words` - 圣遗物词条信息名称
-    pub fn get_number_maps(&self) -> HashMap<String, f32> {
+    /// * `analysis` - 副词条强化分析结果, 由 `analyze_rolls` 计算
+    pub fn get_number_maps(&self, analysis: Option<&RollAnalysis>) -> HashMap<String, f32> {
         let mut result = HashMap::new();
         result.insert(ARTIFACT_INFO.words.star.clone(), self.stars);
         result.insert(ARTIFACT_INFO.words.level.clone(), self.level);
@@ -104,10 +465,69 @@ impl Artifact {
                 .count() as f32,
         );
 
+        // 无法分析强化次数时不提供相关变量, 变量值为未知
+        if let Some(analysis) = analysis {
+            let words = &ARTIFACT_INFO.words;
+            for sub_stat in STAT_TABLE.sub_stats.iter() {
+                result.insert(ARTIFACT_INFO.get_roll_count_key(&sub_stat.name), 0.0);
+            }
+            let mut effective_rolls = 0;
+            // 待激活的副词条尚未生效, 不计入强化次数
+            let active = analysis
+                .sub_stats
+                .iter()
+                .zip(self.sub_stats.iter())
+                .filter(|(_, sub_stat)| !sub_stat.unactivated);
+            for (sub_stat, _) in active {
+                result.insert(
+                    ARTIFACT_INFO.get_roll_count_key(&sub_stat.name),
+                    sub_stat.rolls as f32,
+                );
+                if STAT_TABLE.effective_stats.contains(&sub_stat.name) {
+                    effective_rolls += sub_stat.rolls;
+                }
+            }
+            result.insert(words.effective_rolls.clone(), effective_rolls as f32);
+            result.insert(words.upgrades.clone(), analysis.upgrades as f32);
+            result.insert(
+                words.initial_sub_stats.clone(),
+                analysis.initial_sub_stats as f32,
+            );
+        }
+
         result
     }
 }
 
+#[cfg(test)]
+impl Artifact {
+    /// 测试用的圣遗物: 0 级 5 星攻击力百分比时之沙, 3 条副词条和 1 条待激活副词条, 已锁定未标记
+    pub fn sample() -> Self {
+        let sub_stat = |name: &str, value: f32, unactivated: bool| ArtifactSubStat {
+            name: name.to_string(),
+            value,
+            unactivated,
+        };
+        Self {
+            name: String::from("角斗士的希冀"),
+            slot: String::from("时之沙"),
+            main_stat: String::from("攻击力百分比"),
+            main_stat_value: 7.0,
+            stars: 5.0,
+            level: 0.0,
+            locked: true,
+            sub_stats: vec![
+                sub_stat("暴击率", 3.9, false),
+                sub_stat("暴击伤害", 7.8, false),
+                sub_stat("攻击力", 19.0, false),
+                sub_stat("生命值", 299.0, true),
+            ],
+            set_name: String::from("角斗士的终幕礼"),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug)]
 // 圣遗物升级材料-祝圣精华/油膏
 pub struct ArtifactEnhancementMaterial {
@@ -0,0 +1,129 @@
+/// 帕累托支配分析
+///
+/// 同组中另一个圣遗物的所有属性都大于等于当前圣遗物, 且至少一项更高时, 当前圣遗物被其支配.
+/// 所有属性都相同的圣遗物中, 列表中靠前的支配靠后的, 保证重复的圣遗物至少保留一个
+#[derive(Debug, Clone)]
+pub struct Dominance {
+    /// 每个圣遗物的支配者序号, 不参与分析的圣遗物为 `None`
+    dominators: Vec<Option<Vec<usize>>>,
+}
+
+/// 判断 `a` 是否支配 `b`
+///
+/// # 参数
+///
+/// * `a` - 序号和属性值
+/// * `b` - 序号和属性值
+fn dominates(a: (usize, &[f64]), b: (usize, &[f64])) -> bool {
+    let (a_index, a) = a;
+    let (b_index, b) = b;
+    if a.iter().zip(b).any(|(x, y)| x < y) {
+        return false;
+    }
+    a.iter().zip(b).any(|(x, y)| x > y) || a_index < b_index
+}
+
+impl Dominance {
+    /// 分析列表中每个圣遗物被同组哪些圣遗物支配
+    ///
+    /// # 参数
+    ///
+    /// * `items` - 每个圣遗物的分组和属性值, 为 `None` 时不参与分析
+    pub fn analyze(items: &[Option<(Vec<String>, Vec<f64>)>]) -> Self {
+        let dominators = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let (group, values) = item.as_ref()?;
+                let result = items
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, other)| {
+                        other.as_ref().is_some_and(|(other_group, other_values)| {
+                            other_group == group && dominates((*j, other_values), (i, values))
+                        })
+                    })
+                    .map(|(j, _)| j)
+                    .collect();
+                Some(result)
+            })
+            .collect();
+        Self { dominators }
+    }
+
+    /// 支配圣遗物的数量, 不参与分析时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `index` - 圣遗物序号
+    pub fn count(&self, index: usize) -> Option<usize> {
+        Some(self.dominators.get(index)?.as_ref()?.len())
+    }
+
+    /// 支配链, 从当前圣遗物开始, 每次选择被支配最少的支配者, 直到未被支配的圣遗物
+    ///
+    /// # 参数
+    ///
+    /// * `index` - 圣遗物序号
+    pub fn chain(&self, index: usize) -> Vec<usize> {
+        let mut result = vec![index];
+        let mut current = index;
+        // 支配关系没有环, 支配者被支配的数量严格更少
+        while let Some(Some(dominators)) = self.dominators.get(current) {
+            let next = dominators
+                .iter()
+                .min_by_key(|j| (self.count(**j).unwrap_or(0), **j));
+            let Some(next) = next else {
+                break;
+            };
+            current = *next;
+            result.push(current);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(group: &str, values: &[f64]) -> Option<(Vec<String>, Vec<f64>)> {
+        Some((vec![group.to_string()], values.to_vec()))
+    }
+
+    #[test]
+    fn test_analyze() {
+        let dominance = Dominance::analyze(&[
+            item("a", &[10.0, 20.0]),
+            item("a", &[10.0, 10.0]),
+            item("a", &[5.0, 5.0]),
+            item("a", &[20.0, 5.0]),
+            item("b", &[1.0, 1.0]),
+            None,
+        ]);
+        assert_eq!(dominance.count(0), Some(0));
+        assert_eq!(dominance.count(1), Some(1));
+        assert_eq!(dominance.count(2), Some(3));
+        assert_eq!(dominance.count(3), Some(0));
+        assert_eq!(dominance.count(4), Some(0));
+        assert_eq!(dominance.count(5), None);
+
+        assert_eq!(dominance.chain(2), vec![2, 0]);
+        assert_eq!(dominance.chain(1), vec![1, 0]);
+        assert_eq!(dominance.chain(0), vec![0]);
+    }
+
+    #[test]
+    fn test_analyze_duplicates() {
+        // 重复的圣遗物只保留列表中靠前的一个
+        let dominance = Dominance::analyze(&[
+            item("a", &[10.0, 20.0]),
+            item("a", &[10.0, 20.0]),
+            item("a", &[10.0, 20.0]),
+        ]);
+        assert_eq!(dominance.count(0), Some(0));
+        assert_eq!(dominance.count(1), Some(1));
+        assert_eq!(dominance.count(2), Some(2));
+        assert_eq!(dominance.chain(2), vec![2, 0]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::{path::Path, slice};
+
+use anyhow::{Result, anyhow};
+use metadata::{CharacterFile, Rule};
+use tracing::info;
+
+use crate::{
+    actuator::Actuator,
+    args::{Args, EvalArgs},
+    artifact::Artifact,
+    create_parser,
+    global::GlobalVar,
+    load_rule_file,
+    rule_expr::RuleExpr,
+};
+
+/// 离线测试表达式或规则文件, 不涉及窗口、OCR 和点击
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+/// * `eval_args` - 离线测试参数
+pub fn eval(args: &Args, eval_args: &EvalArgs) -> Result<()> {
+    let mut artifact = Artifact::load(&eval_args.artifact)?;
+    info!("{}", artifact);
+    match artifact.analyze_rolls() {
+        Some(analysis) => {
+            let sub_stats: Vec<String> = analysis
+                .sub_stats
+                .iter()
+                .map(|s| format!("{}: {}次 (档位 {:?})", s.name, s.rolls, s.tiers))
+                .collect();
+            info!(
+                "副词条强化分析: [{}], 初始词条数: {}, 总强化次数: {}, 次数不唯一: {}",
+                sub_stats.join(", "),
+                analysis.initial_sub_stats,
+                analysis.upgrades,
+                analysis.ambiguous
+            );
+        }
+        None => info!("副词条强化分析: 无法分析, 星级、等级与副词条数值不符"),
+    }
+
+    let characters = CharacterFile::load_beside(args.main_rules_file())?;
+    let mut parser = create_parser(&characters)?;
+
+    if let Some(expression) = &eval_args.expression {
+        // 规则文件存在时可以引用其中的定义
+        if args
+            .rules_files
+            .iter()
+            .all(|rules_file| Path::new(rules_file).exists())
+        {
+            RuleExpr::from_rules(&Rule::load_all(&args.rules_files)?, &mut parser)?;
+        }
+        let expr = parser
+            .parse(expression)
+            .map_err(|e| anyhow!("解析表达式失败: \n{}\n错误原因: {}", expression, e))?;
+        let var_key = expr.get_var_keys();
+        RuleExpr::check_param_vars(&var_key)?;
+        // 背包全局变量按只有当前圣遗物的背包计算
+        for global_var in GlobalVar::from_var_key(&var_key, &parser)? {
+            let values = global_var.compute(slice::from_ref(&artifact), &parser, &characters)?;
+            if let Some(Some(value)) = values.first() {
+                artifact.global_vars.insert(global_var.name, *value);
+            }
+        }
+        let expr_var = Actuator::generate_vars(&artifact, &var_key, &characters);
+        let explain = parser.explain(&expr, &expr_var)?;
+        info!("求值过程: {}", explain);
+        if let Some(result) = &explain.result {
+            info!("表达式结果: {}", result);
+        }
+        return Ok(());
+    }
+
+    let rule_file = load_rule_file(args)?;
+    let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser)?;
+    let actuator = Actuator::offline(&parser, &rule_exprs, &characters, args)?;
+    if actuator.has_global_vars() {
+        info!("背包全局变量按只有当前圣遗物的背包计算");
+        actuator.fill_global_vars(slice::from_mut(&mut artifact))?;
+    }
+    let result = actuator.exec(&mut artifact)?;
+    info!(
+        "执行动作结果: {}, 是否锁定: {}, 是否标记: {}",
+        result, artifact.locked, artifact.marked
+    );
+    Ok(())
+}
@@ -0,0 +1,531 @@
+use std::{collections::BTreeMap, fmt, fs, str::FromStr};
+
+use anyhow::{Context, Result, anyhow, bail};
+use metadata::{
+    GOOD_FORMAT, GOOD_KEY_TABLE, GOOD_VERSION, KeyTable, MONA_KEY_TABLE, MONA_VERSION, STAT_TABLE,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::artifact::{Artifact, ArtifactSubStat};
+
+/// 导出数据的来源
+const EXPORT_SOURCE: &str = "giaa";
+
+/// CSV 格式中副词条之前的固定列
+pub const CSV_HEADERS: [&str; 10] = [
+    "名称",
+    "套装",
+    "部位",
+    "主词条",
+    "主词条值",
+    "星级",
+    "等级",
+    "已锁定",
+    "已标记",
+    "已装备",
+];
+
+/// CSV 格式中待激活副词条名称所在的列, 位于所有副词条列之后
+pub const CSV_UNACTIVATED_HEADER: &str = "待激活副词条";
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// GOOD (Genshin Open Object Description) v2 格式
+    Good,
+    /// mona (莫娜占卜铺) 格式
+    Mona,
+    /// 每个圣遗物一行, 副词条为固定列的 CSV 格式
+    Csv,
+    /// 每个圣遗物一行 JSON 的 JSON Lines 格式, 字段与识别出的圣遗物信息一致
+    JsonLines,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "good" => Ok(Self::Good),
+            "mona" => Ok(Self::Mona),
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => bail!("不支持的导出格式 '{}', 可选: good, mona, csv, jsonl", s),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Good => "good",
+            Self::Mona => "mona",
+            Self::Csv => "csv",
+            Self::JsonLines => "jsonl",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ExportFormat {
+    /// 获取格式对应的导出器
+    fn exporter(&self) -> Box<dyn Exporter> {
+        match self {
+            Self::Good => Box::new(GoodExporter),
+            Self::Mona => Box::new(MonaExporter),
+            Self::Csv => Box::new(CsvExporter),
+            Self::JsonLines => Box::new(JsonLinesExporter),
+        }
+    }
+}
+
+/// 导出目标, 命令行中的格式为 `<格式>:<路径>`, 例如 `good:good.json`
+#[derive(Debug, Clone)]
+pub struct ExportTarget {
+    pub format: ExportFormat,
+    pub path: String,
+}
+
+impl FromStr for ExportTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (format, path) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("导出目标 '{}' 格式错误, 应为 <格式>:<路径>", s))?;
+        if path.is_empty() {
+            bail!("导出目标 '{}' 缺少文件路径", s);
+        }
+        Ok(Self {
+            format: format.parse()?,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// 圣遗物导出器
+trait Exporter {
+    /// 是否导出属性不完整的圣遗物, 格式能完整保留识别信息时为 `true`
+    fn allow_incomplete(&self) -> bool {
+        false
+    }
+
+    /// 转换圣遗物列表为文件内容
+    ///
+    /// # 参数
+    ///
+    /// * `artifacts` - 圣遗物列表
+    fn export(&self, artifacts: &[&Artifact]) -> Result<String>;
+}
+
+/// 在键名对照表中查找名称对应的键名
+///
+/// # 参数
+///
+/// * `key` - 查找结果
+/// * `name` - 名称
+fn lookup(key: Option<&str>, name: &str) -> Result<String> {
+    key.map(str::to_string)
+        .ok_or_else(|| anyhow!("'{}' 没有对应的键名, 请检查键名对照表", name))
+}
+
+/// 判断属性数值是否为百分比, GOOD 键名以 `_` 结尾的属性为百分比属性
+///
+/// # 参数
+///
+/// * `stat` - 属性名称
+pub fn is_percent_stat(stat: &str) -> bool {
+    GOOD_KEY_TABLE
+        .stat_key(stat)
+        .is_some_and(|key| key.ends_with('_'))
+}
+
+/// GOOD 格式的副词条
+#[derive(Debug, Serialize)]
+struct GoodSubStat {
+    key: String,
+    value: f32,
+}
+
+/// GOOD 格式的圣遗物
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GoodArtifact {
+    set_key: String,
+    slot_key: String,
+    level: u32,
+    rarity: u32,
+    main_stat_key: String,
+    location: String,
+    lock: bool,
+    substats: Vec<GoodSubStat>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unactivated_substats: Vec<GoodSubStat>,
+}
+
+/// GOOD 格式文件
+#[derive(Debug, Serialize)]
+struct GoodFile {
+    format: &'static str,
+    version: u32,
+    source: &'static str,
+    artifacts: Vec<GoodArtifact>,
+}
+
+/// GOOD 格式导出器
+struct GoodExporter;
+
+impl GoodExporter {
+    /// 转换副词条为 GOOD 格式
+    ///
+    /// # 参数
+    ///
+    /// * `sub_stat` - 副词条
+    fn sub_stat(sub_stat: &ArtifactSubStat) -> Result<GoodSubStat> {
+        Ok(GoodSubStat {
+            key: lookup(GOOD_KEY_TABLE.stat_key(&sub_stat.name), &sub_stat.name)?,
+            value: sub_stat.value,
+        })
+    }
+
+    /// 转换圣遗物为 GOOD 格式
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    fn artifact(artifact: &Artifact) -> Result<GoodArtifact> {
+        let table: &KeyTable = &GOOD_KEY_TABLE;
+        let mut substats = vec![];
+        let mut unactivated_substats = vec![];
+        for sub_stat in artifact.sub_stats.iter() {
+            let good_sub_stat = Self::sub_stat(sub_stat)?;
+            if sub_stat.unactivated {
+                unactivated_substats.push(good_sub_stat);
+            } else {
+                substats.push(good_sub_stat);
+            }
+        }
+        Ok(GoodArtifact {
+            set_key: lookup(table.set_key(&artifact.set_name), &artifact.set_name)?,
+            slot_key: lookup(table.slot_key(&artifact.slot), &artifact.slot)?,
+            level: artifact.level as u32,
+            rarity: artifact.stars as u32,
+            main_stat_key: lookup(table.stat_key(&artifact.main_stat), &artifact.main_stat)?,
+            // 只识别是否已装备, 无法识别装备的角色
+            location: String::new(),
+            lock: artifact.locked,
+            substats,
+            unactivated_substats,
+        })
+    }
+}
+
+impl Exporter for GoodExporter {
+    fn export(&self, artifacts: &[&Artifact]) -> Result<String> {
+        let good_file = GoodFile {
+            format: GOOD_FORMAT,
+            version: GOOD_VERSION,
+            source: EXPORT_SOURCE,
+            artifacts: artifacts
+                .iter()
+                .map(|artifact| Self::artifact(artifact))
+                .collect::<Result<_>>()?,
+        };
+        Ok(serde_json::to_string_pretty(&good_file)?)
+    }
+}
+
+/// mona 格式的词条
+#[derive(Debug, Serialize)]
+struct MonaTag {
+    name: String,
+    value: f64,
+}
+
+/// mona 格式的圣遗物
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MonaArtifact {
+    set_name: String,
+    position: String,
+    main_tag: MonaTag,
+    normal_tags: Vec<MonaTag>,
+    omit: bool,
+    level: u32,
+    star: u32,
+}
+
+/// mona 格式文件, 圣遗物按部位分组
+#[derive(Debug, Serialize)]
+struct MonaFile {
+    version: &'static str,
+    #[serde(flatten)]
+    positions: BTreeMap<String, Vec<MonaArtifact>>,
+}
+
+/// mona 格式导出器
+struct MonaExporter;
+
+impl MonaExporter {
+    /// 转换词条为 mona 格式, 百分比数值转换为小数
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    /// * `value` - 属性数值
+    fn tag(name: &str, value: f32) -> Result<MonaTag> {
+        let mut value = value as f64;
+        if is_percent_stat(name) {
+            // 保留 4 位小数, 避免浮点误差
+            value = (value / 100.0 * 1e4).round() / 1e4;
+        }
+        Ok(MonaTag {
+            name: lookup(MONA_KEY_TABLE.stat_key(name), name)?,
+            value,
+        })
+    }
+
+    /// 转换圣遗物为 mona 格式, 待激活的副词条不导出
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    fn artifact(artifact: &Artifact) -> Result<MonaArtifact> {
+        let table: &KeyTable = &MONA_KEY_TABLE;
+        Ok(MonaArtifact {
+            set_name: lookup(table.set_key(&artifact.set_name), &artifact.set_name)?,
+            position: lookup(table.slot_key(&artifact.slot), &artifact.slot)?,
+            main_tag: Self::tag(&artifact.main_stat, artifact.main_stat_value)?,
+            normal_tags: artifact
+                .sub_stats
+                .iter()
+                .filter(|sub_stat| !sub_stat.unactivated)
+                .map(|sub_stat| Self::tag(&sub_stat.name, sub_stat.value))
+                .collect::<Result<_>>()?,
+            omit: false,
+            level: artifact.level as u32,
+            star: artifact.stars as u32,
+        })
+    }
+}
+
+impl Exporter for MonaExporter {
+    fn export(&self, artifacts: &[&Artifact]) -> Result<String> {
+        let mut positions: BTreeMap<String, Vec<MonaArtifact>> = MONA_KEY_TABLE
+            .slots
+            .values()
+            .map(|position| (position.clone(), vec![]))
+            .collect();
+        for artifact in artifacts {
+            let mona_artifact = Self::artifact(artifact)?;
+            positions
+                .entry(mona_artifact.position.clone())
+                .or_default()
+                .push(mona_artifact);
+        }
+        let mona_file = MonaFile {
+            version: MONA_VERSION,
+            positions,
+        };
+        Ok(serde_json::to_string_pretty(&mona_file)?)
+    }
+}
+
+/// CSV 格式导出器
+struct CsvExporter;
+
+impl Exporter for CsvExporter {
+    fn export(&self, artifacts: &[&Artifact]) -> Result<String> {
+        let sub_stat_names: Vec<&str> = STAT_TABLE
+            .sub_stats
+            .iter()
+            .map(|sub_stat| sub_stat.name.as_str())
+            .collect();
+        let mut writer = csv::Writer::from_writer(vec![]);
+        let mut headers = CSV_HEADERS.to_vec();
+        headers.extend(sub_stat_names.iter());
+        headers.push(CSV_UNACTIVATED_HEADER);
+        writer.write_record(&headers)?;
+
+        for artifact in artifacts {
+            let mut record = vec![
+                artifact.name.clone(),
+                artifact.set_name.clone(),
+                artifact.slot.clone(),
+                artifact.main_stat.clone(),
+                artifact.main_stat_value.to_string(),
+                artifact.stars.to_string(),
+                artifact.level.to_string(),
+                artifact.locked.to_string(),
+                artifact.marked.to_string(),
+                artifact.equipped.to_string(),
+            ];
+            for name in sub_stat_names.iter() {
+                let value = artifact
+                    .sub_stats
+                    .iter()
+                    .find(|sub_stat| sub_stat.name == *name)
+                    .map(|sub_stat| sub_stat.value.to_string());
+                record.push(value.unwrap_or_default());
+            }
+            let unactivated = artifact
+                .sub_stats
+                .iter()
+                .find(|sub_stat| sub_stat.unactivated)
+                .map(|sub_stat| sub_stat.name.clone());
+            record.push(unactivated.unwrap_or_default());
+            writer.write_record(&record)?;
+        }
+        let data = writer.into_inner().context("写入 CSV 数据失败")?;
+        Ok(String::from_utf8(data)?)
+    }
+}
+
+/// JSON Lines 格式导出器
+struct JsonLinesExporter;
+
+impl Exporter for JsonLinesExporter {
+    fn allow_incomplete(&self) -> bool {
+        // 圣遗物信息中包含未识别字段和识别异常标记
+        true
+    }
+
+    fn export(&self, artifacts: &[&Artifact]) -> Result<String> {
+        let mut result = String::new();
+        for artifact in artifacts {
+            result.push_str(&serde_json::to_string(artifact)?);
+            result.push('\n');
+        }
+        Ok(result)
+    }
+}
+
+/// 转换圣遗物列表为导出格式的文件内容, 返回内容和导出的圣遗物数量
+///
+/// # 参数
+///
+/// * `format` - 导出格式
+/// * `artifacts` - 圣遗物列表
+pub fn export_content(format: ExportFormat, artifacts: &[Artifact]) -> Result<(String, usize)> {
+    let exporter = format.exporter();
+    let mut exported = vec![];
+    for artifact in artifacts {
+        let complete = artifact.unknown_fields.is_empty() && !artifact.misidentified;
+        if complete || exporter.allow_incomplete() {
+            exported.push(artifact);
+        } else {
+            warn!("圣遗物属性不完整, 未导出为 {} 格式: {}", format, artifact);
+        }
+    }
+    Ok((exporter.export(&exported)?, exported.len()))
+}
+
+/// 导出圣遗物列表到文件
+///
+/// # 参数
+///
+/// * `target` - 导出目标
+/// * `artifacts` - 圣遗物列表
+pub fn export(target: &ExportTarget, artifacts: &[Artifact]) -> Result<()> {
+    let (content, count) = export_content(target.format, artifacts)?;
+    fs::write(&target.path, content)
+        .with_context(|| format!("写入导出文件 {} 失败", target.path))?;
+    info!(
+        "已导出 {} 个圣遗物到 {} ({} 格式)",
+        count, target.path, target.format
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::artifact::ArtifactField;
+
+    use super::*;
+
+    fn incomplete() -> Artifact {
+        Artifact {
+            unknown_fields: HashSet::from([ArtifactField::SubStats]),
+            ..Artifact::sample()
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let target: ExportTarget = "good:C:\\giaa\\good.json".parse().unwrap();
+        assert_eq!(target.format, ExportFormat::Good);
+        assert_eq!(target.path, "C:\\giaa\\good.json");
+        assert_eq!(
+            "JSONL:a.jsonl".parse::<ExportTarget>().unwrap().format,
+            ExportFormat::JsonLines
+        );
+        assert!("good.json".parse::<ExportTarget>().is_err());
+        assert!("xml:a.xml".parse::<ExportTarget>().is_err());
+        assert!("csv:".parse::<ExportTarget>().is_err());
+    }
+
+    #[test]
+    fn test_export_good() {
+        let (content, count) =
+            export_content(ExportFormat::Good, &[Artifact::sample(), incomplete()]).unwrap();
+        assert_eq!(count, 1);
+        let value: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(value["format"], "GOOD");
+        assert_eq!(value["version"], 2);
+
+        let artifacts = value["artifacts"].as_array().unwrap();
+        assert_eq!(artifacts.len(), 1);
+        let good = &artifacts[0];
+        assert_eq!(good["setKey"], "GladiatorsFinale");
+        assert_eq!(good["slotKey"], "sands");
+        assert_eq!(good["mainStatKey"], "atk_");
+        assert_eq!(good["rarity"], 5);
+        assert_eq!(good["level"], 0);
+        assert_eq!(good["lock"], true);
+        assert_eq!(good["substats"].as_array().unwrap().len(), 3);
+        assert_eq!(good["substats"][1]["key"], "critDMG_");
+        assert_eq!(good["unactivatedSubstats"][0]["key"], "hp");
+    }
+
+    #[test]
+    fn test_export_mona() {
+        let (content, _) = export_content(ExportFormat::Mona, &[Artifact::sample()]).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(value["version"], "1");
+        assert_eq!(value["flower"].as_array().unwrap().len(), 0);
+
+        let mona = &value["sand"][0];
+        assert_eq!(mona["setName"], "gladiatorFinale");
+        assert_eq!(mona["mainTag"]["name"], "attackPercentage");
+        assert_eq!(mona["mainTag"]["value"], 0.07);
+        assert_eq!(mona["normalTags"].as_array().unwrap().len(), 3);
+        assert_eq!(mona["normalTags"][0]["value"], 0.039);
+        assert_eq!(mona["normalTags"][2]["value"], 19.0);
+    }
+
+    #[test]
+    fn test_export_csv_and_json_lines() {
+        let (content, count) = export_content(ExportFormat::Csv, &[Artifact::sample()]).unwrap();
+        assert_eq!(count, 1);
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines[0],
+            "名称,套装,部位,主词条,主词条值,星级,等级,已锁定,已标记,已装备,生命值,攻击力,防御力,生命值百分比,攻击力百分比,防御力百分比,元素精通,元素充能效率,暴击率,暴击伤害,待激活副词条"
+        );
+        assert_eq!(
+            lines[1],
+            "角斗士的希冀,角斗士的终幕礼,时之沙,攻击力百分比,7,5,0,true,false,false,299,19,,,,,,,3.9,7.8,生命值"
+        );
+
+        // JSON Lines 保留不完整的圣遗物
+        let (content, count) =
+            export_content(ExportFormat::JsonLines, &[Artifact::sample(), incomplete()]).unwrap();
+        assert_eq!(count, 2);
+        let lines: Vec<&str> = content.lines().collect();
+        let value: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(value["unknown_fields"][0], "sub_stats");
+    }
+}
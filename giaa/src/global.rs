@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, anyhow, bail};
+use metadata::{ARTIFACT_INFO, CharacterFile};
+use parser::{Expr, ExprResult, ExprType, ExprVarKey, Parser, split_param_args, split_param_var};
+
+use crate::{actuator::Actuator, artifact::Artifact, dominance::Dominance};
+
+/// 背包全局变量的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GlobalKind {
+    /// 同组中评分的排名, 评分最高为 1, 评分相同时排名相同
+    Rank,
+    /// 同组中评分小于等于自身的圣遗物占比, 范围 0 到 100
+    Percentile,
+    /// 同组圣遗物的数量
+    GroupCount,
+    /// 同套装、同部位、同主词条的圣遗物中, 所有属性都不低于自身的圣遗物数量
+    Dominated,
+}
+
+impl GlobalKind {
+    /// 根据变量名称获取种类
+    ///
+    /// # 参数
+    ///
+    /// * `key` - 不含参数的变量名称
+    fn from_key(key: &str) -> Option<Self> {
+        let words = &ARTIFACT_INFO.words;
+        if *key == words.rank {
+            Some(Self::Rank)
+        } else if *key == words.percentile {
+            Some(Self::Percentile)
+        } else if *key == words.group_count {
+            Some(Self::GroupCount)
+        } else if *key == words.dominated_count {
+            Some(Self::Dominated)
+        } else {
+            None
+        }
+    }
+}
+
+/// 背包全局变量, 例如 `排名(双爆分, 部位, 主词条)`
+///
+/// 变量值依赖背包中的所有圣遗物, 只有扫描完整个列表后才能计算.
+/// 排名和百分位的第一个参数为评分表达式, 其余参数为分组表达式, 分组表达式的值都相同的圣遗物为同一组.
+/// 被支配数的参数都为参与比较的属性表达式, 按套装、部位和主词条分组
+#[derive(Debug, Clone)]
+pub struct GlobalVar {
+    /// 变量名称, 包含参数
+    pub name: String,
+    kind: GlobalKind,
+    scores: Vec<Expr>,
+    groups: Vec<Expr>,
+    /// 评分和分组表达式用到的变量
+    pub var_key: ExprVarKey,
+}
+
+impl GlobalVar {
+    /// 判断变量是否为背包全局变量
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称, 包含参数
+    pub fn is_global_var(name: &str) -> bool {
+        split_param_var(name).is_some_and(|(key, _)| GlobalKind::from_key(key).is_some())
+    }
+
+    /// 解析背包全局变量的参数, 不是背包全局变量时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称, 包含参数
+    /// * `parser` - 表达式解析器
+    pub fn parse(name: &str, parser: &Parser) -> Result<Option<Self>> {
+        let Some((key, params)) = split_param_var(name) else {
+            return Ok(None);
+        };
+        let Some(kind) = GlobalKind::from_key(key) else {
+            return Ok(None);
+        };
+
+        let mut scores = vec![];
+        let mut groups = vec![];
+        let mut var_key = ExprVarKey::default();
+        if kind == GlobalKind::Dominated {
+            let words = &ARTIFACT_INFO.words;
+            for key in [&words.set_name, &words.slot, &words.main_stat_name] {
+                groups.push(Expr::StringVariable(key.clone()));
+                var_key.string_keys.push(key.clone());
+            }
+        }
+        for (i, param) in split_param_args(params).into_iter().enumerate() {
+            let is_score = match kind {
+                GlobalKind::Rank | GlobalKind::Percentile => i == 0,
+                GlobalKind::GroupCount => false,
+                GlobalKind::Dominated => true,
+            };
+            let context = if is_score {
+                ExprType::Number
+            } else {
+                ExprType::Boolean
+            };
+            let (expr, expr_type) = parser
+                .parse_typed(param, context)
+                .map_err(|e| anyhow!("变量 '{}' 的参数 '{}' 错误: {}", name, param, e))?;
+            let keys = expr.get_var_keys();
+            if keys.number_keys.iter().any(|key| Self::is_global_var(key)) {
+                bail!("变量 '{}' 的参数不能引用其他背包全局变量", name);
+            }
+            var_key.boolean_keys.extend(keys.boolean_keys);
+            var_key.number_keys.extend(keys.number_keys);
+            var_key.string_keys.extend(keys.string_keys);
+
+            if is_score {
+                if expr_type != ExprType::Number {
+                    bail!(
+                        "变量 '{}' 的第 {} 个参数应为数字类型的评分, 实际为{}类型",
+                        name,
+                        i + 1,
+                        expr_type
+                    );
+                }
+                scores.push(expr);
+            } else {
+                groups.push(expr);
+            }
+        }
+
+        Ok(Some(Self {
+            name: name.to_string(),
+            kind,
+            scores,
+            groups,
+            var_key,
+        }))
+    }
+
+    /// 解析变量中的所有背包全局变量
+    ///
+    /// # 参数
+    ///
+    /// * `var_key` - 表达式变量键
+    /// * `parser` - 表达式解析器
+    pub fn from_var_key(var_key: &ExprVarKey, parser: &Parser) -> Result<Vec<Self>> {
+        let mut result = vec![];
+        for name in var_key.number_keys.iter() {
+            if let Some(global_var) = Self::parse(name, parser)? {
+                result.push(global_var);
+            }
+        }
+        Ok(result)
+    }
+
+    /// 计算圣遗物的分组和评分, 依赖的变量未知时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `artifact` - 圣遗物
+    /// * `parser` - 表达式解析器
+    /// * `characters` - 角色权重
+    fn group_and_scores(
+        &self,
+        artifact: &Artifact,
+        parser: &Parser,
+        characters: &CharacterFile,
+    ) -> Result<Option<(Vec<String>, Vec<f64>)>> {
+        let expr_var = Actuator::generate_vars(artifact, &self.var_key, characters);
+        let mut scores = vec![];
+        for expr in self.scores.iter() {
+            match parser.exec(expr, &expr_var)?.as_f64() {
+                Some(score) => scores.push(score),
+                None => return Ok(None),
+            }
+        }
+        let mut group = vec![];
+        for expr in self.groups.iter() {
+            match parser.exec(expr, &expr_var)? {
+                ExprResult::Unknown => return Ok(None),
+                value => group.push(value.to_string()),
+            }
+        }
+        Ok(Some((group, scores)))
+    }
+
+    /// 被支配数变量的支配分析, 其他变量返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `artifacts` - 背包中的所有圣遗物
+    /// * `parser` - 表达式解析器
+    /// * `characters` - 角色权重
+    pub fn dominance(
+        &self,
+        artifacts: &[Artifact],
+        parser: &Parser,
+        characters: &CharacterFile,
+    ) -> Result<Option<Dominance>> {
+        if self.kind != GlobalKind::Dominated {
+            return Ok(None);
+        }
+        let items = artifacts
+            .iter()
+            .map(|artifact| self.group_and_scores(artifact, parser, characters))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Some(Dominance::analyze(&items)))
+    }
+
+    /// 计算每个圣遗物的变量值, 顺序与圣遗物列表一致
+    ///
+    /// 评分或分组未知的圣遗物不参与计算, 其变量值为 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `artifacts` - 背包中的所有圣遗物
+    /// * `parser` - 表达式解析器
+    /// * `characters` - 角色权重
+    pub fn compute(
+        &self,
+        artifacts: &[Artifact],
+        parser: &Parser,
+        characters: &CharacterFile,
+    ) -> Result<Vec<Option<f32>>> {
+        if let Some(dominance) = self.dominance(artifacts, parser, characters)? {
+            return Ok((0..artifacts.len())
+                .map(|index| dominance.count(index).map(|count| count as f32))
+                .collect());
+        }
+
+        let items = artifacts
+            .iter()
+            .map(|artifact| self.group_and_scores(artifact, parser, characters))
+            .collect::<Result<Vec<_>>>()?;
+        let mut groups: HashMap<&[String], Vec<f64>> = HashMap::new();
+        for (group, scores) in items.iter().flatten() {
+            groups
+                .entry(group.as_slice())
+                .or_default()
+                .push(scores.first().copied().unwrap_or(0.0));
+        }
+
+        Ok(items
+            .iter()
+            .map(|item| {
+                let (group, scores) = item.as_ref()?;
+                let score = scores.first().copied().unwrap_or(0.0);
+                let others = &groups[group.as_slice()];
+                let value = match self.kind {
+                    GlobalKind::Rank => {
+                        1.0 + others.iter().filter(|other| **other > score).count() as f32
+                    }
+                    GlobalKind::Percentile => {
+                        let count = others.iter().filter(|other| **other <= score).count();
+                        count as f32 / others.len() as f32 * 100.0
+                    }
+                    GlobalKind::GroupCount => others.len() as f32,
+                    GlobalKind::Dominated => unreachable!("被支配数由支配分析计算"),
+                };
+                Some(value)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::artifact::ArtifactSubStat;
+
+    use super::*;
+
+    fn artifact(slot: &str, crit_rate: f32) -> Artifact {
+        Artifact {
+            slot: slot.to_string(),
+            set_name: String::from("角斗士的终幕礼"),
+            main_stat: String::from("生命值"),
+            stars: 5.0,
+            sub_stats: vec![ArtifactSubStat {
+                name: String::from("暴击率"),
+                value: crit_rate,
+                unactivated: false,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn parser() -> Parser {
+        let var_key = ExprVarKey::new(
+            ARTIFACT_INFO.get_boolean_keys(),
+            ARTIFACT_INFO.get_number_keys(),
+        );
+        let mut parser = Parser::new(3, var_key).unwrap();
+        parser.set_string_vars(ARTIFACT_INFO.get_string_keys());
+        parser.set_param_number_vars(ARTIFACT_INFO.get_param_number_keys());
+        parser
+    }
+
+    #[test]
+    fn test_compute() {
+        let parser = parser();
+        let characters = CharacterFile::default();
+        let artifacts = vec![
+            artifact("生之花", 3.9),
+            artifact("生之花", 7.8),
+            artifact("死之羽", 3.9),
+            artifact("生之花", 7.8),
+        ];
+        let compute = |name: &str| {
+            GlobalVar::parse(name, &parser)
+                .unwrap()
+                .unwrap()
+                .compute(&artifacts, &parser, &characters)
+                .unwrap()
+        };
+
+        assert_eq!(
+            compute("排名(暴击率 * 2, 部位)"),
+            vec![Some(3.0), Some(1.0), Some(1.0), Some(1.0)]
+        );
+        // 单独的副词条名称作为评分时为副词条数值
+        assert_eq!(
+            compute("百分位(暴击率)"),
+            vec![Some(50.0), Some(100.0), Some(50.0), Some(100.0)]
+        );
+        assert_eq!(
+            compute("同组数量(部位)"),
+            vec![Some(3.0), Some(3.0), Some(1.0), Some(3.0)]
+        );
+        // 属性相同时列表中靠前的支配靠后的
+        assert_eq!(
+            compute("被支配数(暴击率)"),
+            vec![Some(2.0), Some(0.0), Some(0.0), Some(1.0)]
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let parser = parser();
+        assert!(
+            GlobalVar::parse("双爆分达标概率(30)", &parser)
+                .unwrap()
+                .is_none()
+        );
+
+        let err = GlobalVar::parse("排名(部位)", &parser).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "变量 '排名(部位)' 的第 1 个参数应为数字类型的评分, 实际为字符串类型"
+        );
+
+        let err = GlobalVar::parse("排名(排名(暴击率), 部位)", &parser).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "变量 '排名(排名(暴击率), 部位)' 的参数不能引用其他背包全局变量"
+        );
+    }
+}
@@ -1,15 +1,18 @@
-use std::{cell::RefCell, collections::HashSet};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 
 use anyhow::{Result, bail};
 use common::{Point, Region, point_offset, region_offset, remove_special_char, str_to_number};
 use image::{Pixel, Rgb, RgbaImage};
-use metadata::{ARTIFACT_INFO, CoordinateData};
+use metadata::{ARTIFACT_INFO, CoordinateData, STAT_TABLE, ValueCheck};
 use ocr::{Ocr, OcrResult};
-use parser::Expr;
+use tracing::{info, warn};
 
 use crate::{
     args::Args,
-    artifact::{Artifact, ArtifactEnhancementMaterial, ArtifactSubStat},
+    artifact::{Artifact, ArtifactEnhancementMaterial, ArtifactField, ArtifactSubStat},
     color::{average_color_diff, color_distance},
     converter::Converter,
     rule_expr::RuleExpr,
@@ -34,7 +37,7 @@ pub struct ArtifactIdentify {
 
 #[derive(Debug)]
 pub enum IdentifyResult {
-    Artifact(Artifact),                                       // 圣遗物
+    Artifact(Box<Artifact>),                                  // 圣遗物
     ArtifactEnhancementMaterial(ArtifactEnhancementMaterial), // 圣遗物增强材料-祝圣精华/油膏
 }
 
@@ -55,6 +58,22 @@ impl ArtifactIdentify {
         }
     }
 
+    /// 识别所有字段, 用于仅扫描和导出
+    pub fn all() -> Self {
+        Self {
+            name: true,
+            slot: true,
+            main_stat: true,
+            main_stat_value: true,
+            stars: true,
+            sub_stats: true,
+            sub_stats_count: true,
+            set_name: true,
+            equipped: true,
+            level: true,
+        }
+    }
+
     /// 通过规则表达式和圣遗物信息, 确定识别哪些字段
     ///
     /// # 参数
@@ -65,9 +84,26 @@ impl ArtifactIdentify {
         let mut di = Self::default();
 
         for rule_expr in rule_exprs {
-            let var_keys = Expr::get_var_keys(&rule_expr.expr);
-            all_keys.extend(var_keys.boolean_keys);
-            all_keys.extend(var_keys.number_keys);
+            let var_keys = &rule_expr.expr_var_key;
+            all_keys.extend(var_keys.boolean_keys.iter().cloned());
+            all_keys.extend(var_keys.number_keys.iter().cloned());
+            all_keys.extend(var_keys.string_keys.iter().cloned());
+        }
+
+        if all_keys.contains(&ARTIFACT_INFO.words.name) {
+            di.name = true;
+        }
+
+        if all_keys.contains(&ARTIFACT_INFO.words.slot) {
+            di.slot = true;
+        }
+
+        if all_keys.contains(&ARTIFACT_INFO.words.set_name) {
+            di.set_name = true;
+        }
+
+        if all_keys.contains(&ARTIFACT_INFO.words.main_stat_name) {
+            di.main_stat = true;
         }
 
         for name in ARTIFACT_INFO.get_artifact_names() {
@@ -113,11 +149,51 @@ impl ArtifactIdentify {
             }
         }
 
+        // 副词条强化分析依赖星级、等级和副词条
+        if all_keys.iter().any(|key| Artifact::is_roll_var(key)) {
+            di.stars = true;
+            di.level = true;
+            di.sub_stats = true;
+        }
+
+        // 角色评分依赖部位、主词条、套装和副词条
+        if all_keys.iter().any(|key| Artifact::is_character_var(key)) {
+            di.slot = true;
+            di.main_stat = true;
+            di.set_name = true;
+            di.sub_stats = true;
+        }
+
+        // 满级评分估计依赖星级、等级、主词条和副词条
+        if all_keys.iter().any(|key| Artifact::is_potential_var(key)) {
+            di.stars = true;
+            di.level = true;
+            di.main_stat = true;
+            di.sub_stats = true;
+        }
+
         // 套装名和副词条个数依赖圣遗物副词条, 所有需要同步开启
         if di.set_name || di.sub_stats_count {
             di.sub_stats = true;
         }
 
+        // 主词条是否为百分比属性需要通过主词条值区分
+        if di.main_stat {
+            di.main_stat_value = true;
+        }
+
+        // 等级和主词条值互相校验
+        if di.level || di.main_stat_value {
+            di.level = true;
+            di.main_stat = true;
+            di.main_stat_value = true;
+        }
+
+        // 校验数值需要星级
+        if di.sub_stats || di.main_stat_value {
+            di.stars = true;
+        }
+
         if all_keys.contains(&ARTIFACT_INFO.words.equipped) {
             di.equipped = true;
         }
@@ -126,6 +202,105 @@ impl ArtifactIdentify {
     }
 }
 
+/// 识别失败时记录未知字段, 并返回默认值
+///
+/// # 参数
+///
+/// * `value` - 识别结果, `None` 表示识别失败
+/// * `field` - 字段
+/// * `unknown_fields` - 未知字段集合
+fn or_unknown<T: Default>(
+    value: Option<T>,
+    field: ArtifactField,
+    unknown_fields: &mut HashSet<ArtifactField>,
+) -> T {
+    value.unwrap_or_else(|| {
+        unknown_fields.insert(field);
+        T::default()
+    })
+}
+
+/// 数值带百分号时, 将生命值、攻击力、防御力转为对应的百分比属性
+///
+/// # 参数
+///
+/// * `name` - 属性名称
+/// * `value_text` - 识别的数值文本
+fn percent_stat_name(name: String, value_text: &str) -> String {
+    let percent_name = format!("{}百分比", name);
+    if value_text.contains('%') && ARTIFACT_INFO.stats.contains(&percent_name) {
+        percent_name
+    } else {
+        name
+    }
+}
+
+/// 通过主词条数值表交叉校验等级和主词条值, 返回两者是否不符
+///
+/// 其中一个未识别时由另一个推断, 两者不符时无法判断哪个识别错误, 都记为未知, 严格模式下返回错误
+///
+/// # 参数
+///
+/// * `main_stat` - 主词条名称
+/// * `stars` - 星级
+/// * `level` - 等级
+/// * `main_stat_value` - 主词条值
+/// * `strict_mode` - 是否为严格模式
+fn cross_check_level(
+    main_stat: Option<&str>,
+    stars: f32,
+    level: &mut Option<f32>,
+    main_stat_value: &mut Option<f32>,
+    strict_mode: bool,
+) -> Result<bool> {
+    let Some(main_stat) = main_stat else {
+        return Ok(false);
+    };
+    match (*level, *main_stat_value) {
+        (Some(known_level), Some(value)) => {
+            let Some(levels) = STAT_TABLE.main_stat_levels(main_stat, stars as u32, value) else {
+                return Ok(false);
+            };
+            if levels.contains(&(known_level as u32)) {
+                return Ok(false);
+            }
+            if strict_mode {
+                bail!(
+                    "圣遗物等级 {} 与主属性 {} 的值 {} 不符",
+                    known_level,
+                    main_stat,
+                    value
+                );
+            }
+            warn!(
+                "圣遗物等级 {} 与主属性 {} 的值 {} 不符",
+                known_level, main_stat, value
+            );
+            *level = None;
+            *main_stat_value = None;
+            Ok(true)
+        }
+        (None, Some(value)) => {
+            if let Some(levels) = STAT_TABLE.main_stat_levels(main_stat, stars as u32, value)
+                && levels.len() == 1
+            {
+                info!("由主属性值 {} 推断圣遗物等级为 {}", value, levels[0]);
+                *level = Some(levels[0] as f32);
+            }
+            Ok(false)
+        }
+        (Some(known_level), None) => {
+            *main_stat_value =
+                STAT_TABLE.main_stat_value(main_stat, stars as u32, known_level as u32);
+            if let Some(value) = main_stat_value {
+                info!("由圣遗物等级 {} 推断主属性值为 {}", known_level, value);
+            }
+            Ok(false)
+        }
+        (None, None) => Ok(false),
+    }
+}
+
 /// 圣遗物识别器
 pub struct Identifier<'a> {
     converter: &'a Converter<'a>,
@@ -208,61 +383,123 @@ impl<'a> Identifier<'a> {
         Ok(average_diff > 0)
     }
 
-    /// 识别圣遗物名称
-    fn identify_artifact_name(&self) -> Result<String> {
+    /// 识别圣遗物名称, 非严格模式下识别失败返回 `None`
+    fn identify_artifact_name(&self) -> Result<Option<String>> {
         if self.artifact_identify.name {
             let name = self.ocr_region(&self.coordinate_data.artifact_name)?;
             if let Some(name) = ARTIFACT_INFO.get_artifact_name_by_alias(&name.text) {
-                return Ok(name);
+                return Ok(Some(name));
             }
             if self.args.strict_mode {
                 bail!("未识别到圣遗物名称: {}", name.text);
             }
+            return Ok(None);
         }
-        Ok(String::new())
+        Ok(Some(String::new()))
     }
 
-    /// 识别圣遗物部位名称
-    fn identify_artifact_slot(&self) -> Result<String> {
+    /// 识别圣遗物部位名称, 非严格模式下识别失败返回 `None`
+    fn identify_artifact_slot(&self) -> Result<Option<String>> {
         if self.artifact_identify.slot {
             let slot = self.ocr_region(&self.coordinate_data.artifact_slot)?;
             if ARTIFACT_INFO.slots.contains(&slot.text) {
-                return Ok(slot.text);
+                return Ok(Some(slot.text));
             }
             if self.args.strict_mode {
                 bail!("未识别到圣遗物部位: {}", slot.text);
             }
+            return Ok(None);
         }
-        Ok(String::new())
+        Ok(Some(String::new()))
     }
 
-    /// 识别圣遗物主词条名称
-    fn identify_artifact_main_stat(&self) -> Result<String> {
+    /// 识别圣遗物主词条名称, 非严格模式下识别失败返回 `None`
+    fn identify_artifact_main_stat(&self) -> Result<Option<String>> {
         if self.artifact_identify.main_stat {
             let main_stat = self.ocr_region(&self.coordinate_data.artifact_main_stat_name)?;
             if ARTIFACT_INFO.stats.contains(&main_stat.text) {
-                return Ok(main_stat.text);
+                return Ok(Some(main_stat.text));
             }
             if self.args.strict_mode {
                 bail!("未识别到主属性: {}", main_stat.text);
             }
+            return Ok(None);
         }
-        Ok(String::new())
+        Ok(Some(String::new()))
     }
 
-    /// 识别圣遗物主词条值
-    fn identify_artifact_main_stat_value(&self) -> Result<f32> {
+    /// 识别圣遗物主词条值文本, 不需要识别时返回空字符串
+    fn ocr_artifact_main_stat_value(&self) -> Result<String> {
         if self.artifact_identify.main_stat_value {
             let main_stat_value =
                 self.ocr_region(&self.coordinate_data.artifact_main_stat_value)?;
-            let value = str_to_number::<f32>(&main_stat_value.text);
+            return Ok(main_stat_value.text);
+        }
+        Ok(String::new())
+    }
+
+    /// 校验数值识别结果, 修正后唯一有效时采用修正值
+    ///
+    /// 数值及其修正都无效时无论是否严格模式都返回错误, 修正后有多个有效数值时按识别失败处理
+    ///
+    /// # 参数
+    ///
+    /// * `check` - 校验结果, `None` 表示数值表中没有对应的星级或属性, 不做校验
+    /// * `value` - 未校验的识别结果
+    /// * `description` - 数值描述, 用于日志
+    fn checked_value(
+        &self,
+        check: Option<ValueCheck>,
+        value: f32,
+        description: &str,
+    ) -> Result<Option<f32>> {
+        match check {
+            None => Ok(Some(value)),
+            Some(ValueCheck::Valid(value)) => Ok(Some(value)),
+            Some(ValueCheck::Corrected(corrected)) => {
+                warn!("{} 识别为 {}, 已修正为 {}", description, value, corrected);
+                Ok(Some(corrected))
+            }
+            Some(ValueCheck::Ambiguous(values)) => {
+                if self.args.strict_mode {
+                    bail!("{} 的修正结果不唯一: {:?}", description, values);
+                }
+                warn!("{} 的修正结果不唯一: {:?}", description, values);
+                Ok(None)
+            }
+            Some(ValueCheck::Invalid) => bail!("{} 不是有效的数值", description),
+        }
+    }
+
+    /// 识别圣遗物主词条值, 非严格模式下识别失败返回 `None`
+    ///
+    /// 只校验数值是否为某一等级的主词条值, 与等级是否相符由 [`Self::cross_check_level`] 校验
+    ///
+    /// # 参数
+    ///
+    /// * `text` - 识别的主词条值文本
+    /// * `main_stat` - 主词条名称, 未知时不校验
+    /// * `stars` - 星级
+    fn identify_artifact_main_stat_value(
+        &self,
+        text: &str,
+        main_stat: Option<&str>,
+        stars: f32,
+    ) -> Result<Option<f32>> {
+        if self.artifact_identify.main_stat_value {
+            let value = str_to_number::<f32>(text);
             if let Ok(value) = value {
-                return Ok(value);
+                let check = main_stat.and_then(|main_stat| {
+                    STAT_TABLE.check_main_stat_value(main_stat, stars as u32, None, text)
+                });
+                let description = format!("主属性值 {}", text);
+                return self.checked_value(check, value, &description);
             } else if self.args.strict_mode {
-                bail!("未识别到主属性值: {}", main_stat_value.text);
+                bail!("未识别到主属性值: {}", text);
             }
+            return Ok(None);
         }
-        Ok(0.0)
+        Ok(Some(0.0))
     }
 
     /// 识别物品星级
@@ -294,29 +531,34 @@ impl<'a> Identifier<'a> {
         Ok(ARTIFACT_INFO.words.sanctifying_elixir == elixir.text)
     }
 
-    /// 识别圣遗物等级
+    /// 识别圣遗物等级, 非严格模式下识别失败返回 `None`
     ///
     /// # 参数
     ///
     /// * `offset` - 偏移量
-    fn identify_artifact_level(&self, offset: i32) -> Result<f32> {
+    fn identify_artifact_level(&self, offset: i32) -> Result<Option<f32>> {
         if self.artifact_identify.level {
             let level = self.ocr_region_offset_y(self.coordinate_data.artifact_level, offset)?;
             if let Ok(level) = str_to_number(&level.text) {
-                if level < 0.0 || level > 20.0 {
+                if (0.0..=20.0).contains(&level) {
+                    return Ok(Some(level));
+                } else if self.args.strict_mode {
                     bail!("圣遗物等级超出范围: {}", level);
                 }
-
-                return Ok(level);
+                return Ok(None);
             } else if self.args.strict_mode {
                 bail!("未识别到圣遗物等级: {}", level.text);
             }
+            return Ok(None);
         }
-        Ok(0.0)
+        Ok(Some(0.0))
     }
 
     /// 识别圣遗物是否已标记
-
+    ///
+    /// # 参数
+    ///
+    /// * `offset` - 偏移量
     fn identify_artifact_marked(&self, offset: i32) -> Result<bool> {
         let point = point_offset(&self.coordinate_data.artifact_mark, None, Some(offset));
         let color = self.get_pixel_color(point)?;
@@ -340,16 +582,26 @@ impl<'a> Identifier<'a> {
         Ok(distance > 65025)
     }
 
-    /// 识别圣遗物副词条名称和值
+    /// 识别圣遗物副词条名称和值, 同时返回副词条行数
+    ///
+    /// 非严格模式下跳过识别失败的行, 此时行数大于副词条个数
     ///
     /// # 参数
     ///
     /// * `offset` - 偏移量
-    fn identify_artifact_sub_stats(&self, offset: i32) -> Result<Vec<ArtifactSubStat>> {
+    /// * `stars` - 星级
+    /// * `level` - 等级, 未知时按最高等级校验数值
+    fn identify_artifact_sub_stats(
+        &self,
+        offset: i32,
+        stars: f32,
+        level: Option<u32>,
+    ) -> Result<(Vec<ArtifactSubStat>, i32)> {
         if !self.artifact_identify.sub_stats {
-            return Ok(vec![]);
+            return Ok((vec![], 0));
         }
         let mut result: Vec<ArtifactSubStat> = vec![];
+        let mut rows = 0;
         for i in 0..4 {
             let sub_stat_name = self.ocr_region_offset_y(
                 self.coordinate_data.artifact_sub_stat_start,
@@ -359,8 +611,9 @@ impl<'a> Identifier<'a> {
             if plus_index.is_none() {
                 break;
             }
+            rows += 1;
             let (stat_name, stat_value) = sub_stat_name.text.split_at(plus_index.unwrap());
-            let name = stat_name.trim().to_string();
+            let name = percent_stat_name(stat_name.trim().to_string(), stat_value);
 
             if !ARTIFACT_INFO.stats.contains(&name) {
                 if self.args.strict_mode {
@@ -371,19 +624,25 @@ impl<'a> Identifier<'a> {
             }
             let value = &stat_value[1..];
             let unactivated: bool = value.contains(&ARTIFACT_INFO.words.unactivated);
-            let value = str_to_number(value);
+            let text = value;
+            let value = str_to_number(text);
 
             if let Ok(value) = value {
-                result.push(ArtifactSubStat {
-                    name,
-                    value,
-                    unactivated,
-                });
+                let check =
+                    STAT_TABLE.check_sub_stat_value(&name, stars as u32, level, text, unactivated);
+                let description = format!("副词条 {}", sub_stat_name.text);
+                if let Some(value) = self.checked_value(check, value, &description)? {
+                    result.push(ArtifactSubStat {
+                        name,
+                        value,
+                        unactivated,
+                    });
+                }
             } else if self.args.strict_mode {
                 bail!("未识别到属性值: {}", sub_stat_name.text);
             }
         }
-        Ok(result)
+        Ok((result, rows))
     }
 
     /// 识别圣遗物套装名称
@@ -391,7 +650,7 @@ impl<'a> Identifier<'a> {
     /// # 参数
     ///
     /// * `offset` - 偏移量
-    fn identify_artifact_set_name(&self, offset: i32) -> Result<String> {
+    fn identify_artifact_set_name(&self, offset: i32) -> Result<Option<String>> {
         if self.artifact_identify.set_name {
             let start = Point {
                 x: self.coordinate_data.artifact_set_name_x,
@@ -404,12 +663,13 @@ impl<'a> Identifier<'a> {
             let set_name = self.ocr_region_offset_y(Region { start, end }, offset)?;
             let set_name = remove_special_char(&set_name.text);
             if let Some(set_name) = ARTIFACT_INFO.get_artifact_set_name_by_alias(&set_name) {
-                return Ok(set_name);
+                return Ok(Some(set_name));
             } else if self.args.strict_mode {
                 bail!("未识别到套装名称: {}", set_name);
             }
+            return Ok(None);
         }
-        return Ok(String::new());
+        Ok(Some(String::new()))
     }
 
     /// 识别圣遗物是否已装备
@@ -445,12 +705,45 @@ impl<'a> Identifier<'a> {
             }
         }
 
-        let name = self.identify_artifact_name()?;
-        let slot = self.identify_artifact_slot()?;
-        let main_stat = self.identify_artifact_main_stat()?;
-        let main_stat_value = self.identify_artifact_main_stat_value()?;
+        // 非严格模式下识别失败的字段记为未知, 依赖这些字段的规则不会执行
+        let mut unknown_fields = HashSet::new();
+        let name = or_unknown(
+            self.identify_artifact_name()?,
+            ArtifactField::Name,
+            &mut unknown_fields,
+        );
+        let slot = or_unknown(
+            self.identify_artifact_slot()?,
+            ArtifactField::Slot,
+            &mut unknown_fields,
+        );
+        let main_stat_text = self.ocr_artifact_main_stat_value()?;
+        let main_stat = self
+            .identify_artifact_main_stat()?
+            .map(|main_stat| percent_stat_name(main_stat, &main_stat_text));
         let stars = self.identify_stars()?;
-        let level = self.identify_artifact_level(offset)?;
+        let mut level = self.identify_artifact_level(offset)?;
+        let mut main_stat_value =
+            self.identify_artifact_main_stat_value(&main_stat_text, main_stat.as_deref(), stars)?;
+        let misidentified = self.artifact_identify.level
+            && cross_check_level(
+                main_stat.as_deref(),
+                stars,
+                &mut level,
+                &mut main_stat_value,
+                self.args.strict_mode,
+            )?;
+        // 未识别等级时校验数值不限制等级
+        let known_level = level
+            .filter(|_| self.artifact_identify.level)
+            .map(|level| level as u32);
+        let main_stat_value = or_unknown(
+            main_stat_value,
+            ArtifactField::MainStatValue,
+            &mut unknown_fields,
+        );
+        let main_stat = or_unknown(main_stat, ArtifactField::MainStat, &mut unknown_fields);
+        let level = or_unknown(level, ArtifactField::Level, &mut unknown_fields);
         let marked = self.identify_artifact_marked(offset)?;
         let locked = self.identify_artifact_locked(offset)?;
 
@@ -458,11 +751,18 @@ impl<'a> Identifier<'a> {
             bail!("圣遗物扫描出已标记未锁定的异常状态");
         }
 
-        let sub_stats = self.identify_artifact_sub_stats(offset)?;
+        let (sub_stats, rows) = self.identify_artifact_sub_stats(offset, stars, known_level)?;
+        if rows != sub_stats.len() as i32 {
+            unknown_fields.insert(ArtifactField::SubStats);
+        }
 
-        offset += self.coordinate_data.artifact_sub_stat_height as i32 * sub_stats.len() as i32;
+        offset += self.coordinate_data.artifact_sub_stat_height as i32 * rows;
 
-        let set_name = self.identify_artifact_set_name(offset)?;
+        let set_name = or_unknown(
+            self.identify_artifact_set_name(offset)?,
+            ArtifactField::SetName,
+            &mut unknown_fields,
+        );
         let equipped = self.identify_artifact_equipped()?;
 
         let artifact = Artifact {
@@ -478,8 +778,43 @@ impl<'a> Identifier<'a> {
             locked,
             sanctifying_elixir,
             level,
+            unknown_fields,
+            misidentified,
+            global_vars: HashMap::new(),
+        };
+
+        Ok(IdentifyResult::Artifact(Box::new(artifact)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cross_check_level() {
+        let check = |level: Option<f32>, value: Option<f32>, strict_mode: bool| {
+            let (mut level, mut value) = (level, value);
+            cross_check_level(Some("暴击率"), 5.0, &mut level, &mut value, strict_mode)
+                .map(|misidentified| (misidentified, level, value))
         };
 
-        Ok(IdentifyResult::Artifact(artifact))
+        assert_eq!(
+            check(Some(20.0), Some(31.1), false).unwrap(),
+            (false, Some(20.0), Some(31.1))
+        );
+        assert_eq!(
+            check(None, Some(31.1), false).unwrap(),
+            (false, Some(20.0), Some(31.1))
+        );
+        // 等级与主词条值不符时都记为未知, 严格模式下返回错误
+        assert_eq!(
+            check(Some(4.0), Some(31.1), false).unwrap(),
+            (true, None, None)
+        );
+        assert_eq!(
+            check(Some(4.0), Some(31.1), true).unwrap_err().to_string(),
+            "圣遗物等级 4 与主属性 暴击率 的值 31.1 不符"
+        );
     }
 }
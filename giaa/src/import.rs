@@ -0,0 +1,460 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result, anyhow, bail};
+use metadata::{ARTIFACT_INFO, GOOD_FORMAT, GOOD_KEY_TABLE, MONA_KEY_TABLE, STAT_TABLE};
+use serde::Deserialize;
+
+use crate::{
+    artifact::{Artifact, ArtifactField, ArtifactSubStat},
+    export::{CSV_HEADERS, CSV_UNACTIVATED_HEADER, ExportFormat, is_percent_stat},
+};
+
+/// GOOD 格式的副词条
+#[derive(Debug, Deserialize)]
+struct GoodSubStat {
+    key: String,
+    value: f32,
+}
+
+/// GOOD 格式的圣遗物, 只读取用到的字段
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GoodArtifact {
+    set_key: String,
+    slot_key: String,
+    level: u32,
+    rarity: u32,
+    main_stat_key: String,
+    #[serde(default)]
+    location: String,
+    #[serde(default)]
+    lock: bool,
+    #[serde(default)]
+    substats: Vec<GoodSubStat>,
+    #[serde(default)]
+    unactivated_substats: Vec<GoodSubStat>,
+}
+
+/// GOOD 格式文件
+#[derive(Debug, Deserialize)]
+struct GoodFile {
+    format: String,
+    #[serde(default)]
+    artifacts: Vec<GoodArtifact>,
+}
+
+/// mona 格式的词条
+#[derive(Debug, Deserialize)]
+struct MonaTag {
+    name: String,
+    value: f64,
+}
+
+/// mona 格式的圣遗物, 只读取用到的字段
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MonaArtifact {
+    set_name: String,
+    position: String,
+    main_tag: MonaTag,
+    #[serde(default)]
+    normal_tags: Vec<MonaTag>,
+    level: u32,
+    star: u32,
+}
+
+/// 在键名对照表中查找键名对应的名称
+///
+/// # 参数
+///
+/// * `name` - 查找结果
+/// * `key` - 键名
+fn lookup(name: Option<&str>, key: &str) -> Result<String> {
+    name.map(str::to_string)
+        .ok_or_else(|| anyhow!("键名 '{}' 没有对应的名称, 请检查键名对照表", key))
+}
+
+/// 根据套装和部位确定圣遗物名称, 套装中的圣遗物按部位顺序排列
+///
+/// # 参数
+///
+/// * `artifact` - 已确定套装和部位的圣遗物
+fn fill_name(artifact: &mut Artifact) {
+    let set = ARTIFACT_INFO
+        .sets
+        .iter()
+        .find(|set| set.name == artifact.set_name);
+    let slot_index = ARTIFACT_INFO
+        .slots
+        .iter()
+        .position(|slot| *slot == artifact.slot);
+    let name = match (set, slot_index) {
+        (Some(set), Some(slot_index)) if set.artifacts.len() == ARTIFACT_INFO.slots.len() => {
+            Some(set.artifacts[slot_index].name.clone())
+        }
+        // 只有理之冠的套装
+        (Some(set), Some(slot_index))
+            if set.artifacts.len() == 1 && slot_index == ARTIFACT_INFO.slots.len() - 1 =>
+        {
+            Some(set.artifacts[0].name.clone())
+        }
+        _ => None,
+    };
+    match name {
+        Some(name) => artifact.name = name,
+        None => {
+            artifact.unknown_fields.insert(ArtifactField::Name);
+        }
+    }
+}
+
+/// 根据星级和等级计算主词条数值, 无法计算时标记为未识别
+///
+/// # 参数
+///
+/// * `artifact` - 已确定主词条、星级和等级的圣遗物
+fn fill_main_stat_value(artifact: &mut Artifact) {
+    match STAT_TABLE.main_stat_value(
+        &artifact.main_stat,
+        artifact.stars as u32,
+        artifact.level as u32,
+    ) {
+        Some(value) => artifact.main_stat_value = value,
+        None => {
+            artifact.unknown_fields.insert(ArtifactField::MainStatValue);
+        }
+    }
+}
+
+/// 从 GOOD 格式导入圣遗物, GOOD 格式没有圣遗物名称和主词条数值, 由套装、部位、星级和等级推算
+///
+/// # 参数
+///
+/// * `content` - 文件内容
+fn import_good(content: &str) -> Result<Vec<Artifact>> {
+    let good_file: GoodFile = serde_json::from_str(content)?;
+    if good_file.format != GOOD_FORMAT {
+        bail!("不是 GOOD 格式文件, format 为 '{}'", good_file.format);
+    }
+    let table = &GOOD_KEY_TABLE;
+    let mut result = vec![];
+    for (index, good) in good_file.artifacts.iter().enumerate() {
+        let convert = || -> Result<Artifact> {
+            let mut sub_stats = vec![];
+            for (sub_stat, unactivated) in good
+                .substats
+                .iter()
+                .map(|s| (s, false))
+                .chain(good.unactivated_substats.iter().map(|s| (s, true)))
+            {
+                // 部分工具会用空键名补齐 4 个副词条
+                if sub_stat.key.is_empty() {
+                    continue;
+                }
+                sub_stats.push(ArtifactSubStat {
+                    name: lookup(table.stat_name(&sub_stat.key), &sub_stat.key)?,
+                    value: sub_stat.value,
+                    unactivated,
+                });
+            }
+            let mut artifact = Artifact {
+                slot: lookup(table.slot_name(&good.slot_key), &good.slot_key)?,
+                main_stat: lookup(table.stat_name(&good.main_stat_key), &good.main_stat_key)?,
+                stars: good.rarity as f32,
+                level: good.level as f32,
+                locked: good.lock,
+                sub_stats,
+                set_name: lookup(table.set_name(&good.set_key), &good.set_key)?,
+                equipped: !good.location.is_empty(),
+                ..Default::default()
+            };
+            fill_name(&mut artifact);
+            fill_main_stat_value(&mut artifact);
+            Ok(artifact)
+        };
+        result.push(convert().with_context(|| format!("第 {} 个圣遗物", index + 1))?);
+    }
+    Ok(result)
+}
+
+/// 转换 mona 格式的词条数值, 百分比属性的小数转换为百分数
+///
+/// # 参数
+///
+/// * `name` - 属性名称
+/// * `value` - 属性数值
+fn mona_value(name: &str, value: f64) -> f32 {
+    if is_percent_stat(name) {
+        ((value * 1000.0).round() / 10.0) as f32
+    } else {
+        value as f32
+    }
+}
+
+/// 从 mona 格式导入圣遗物, mona 格式没有锁定状态, 导入后都为未锁定
+///
+/// # 参数
+///
+/// * `content` - 文件内容
+fn import_mona(content: &str) -> Result<Vec<Artifact>> {
+    let value: serde_json::Value = serde_json::from_str(content)?;
+    let Some(positions) = value.as_object() else {
+        bail!("不是 mona 格式文件");
+    };
+    let table = &MONA_KEY_TABLE;
+    let mut result = vec![];
+    // 按部位顺序导入, 保证结果稳定
+    for slot in ARTIFACT_INFO.slots.iter() {
+        let Some(position) = table.slot_key(slot) else {
+            continue;
+        };
+        let Some(artifacts) = positions.get(position) else {
+            continue;
+        };
+        let artifacts: Vec<MonaArtifact> = serde_json::from_value(artifacts.clone())
+            .with_context(|| format!("解析部位 {} 失败", position))?;
+        for (index, mona) in artifacts.iter().enumerate() {
+            let convert = || -> Result<Artifact> {
+                let main_stat = lookup(table.stat_name(&mona.main_tag.name), &mona.main_tag.name)?;
+                let mut sub_stats = vec![];
+                for tag in mona.normal_tags.iter() {
+                    let name = lookup(table.stat_name(&tag.name), &tag.name)?;
+                    sub_stats.push(ArtifactSubStat {
+                        value: mona_value(&name, tag.value),
+                        name,
+                        unactivated: false,
+                    });
+                }
+                let mut artifact = Artifact {
+                    slot: lookup(table.slot_name(&mona.position), &mona.position)?,
+                    main_stat_value: mona_value(&main_stat, mona.main_tag.value),
+                    main_stat,
+                    stars: mona.star as f32,
+                    level: mona.level as f32,
+                    sub_stats,
+                    set_name: lookup(table.set_name(&mona.set_name), &mona.set_name)?,
+                    ..Default::default()
+                };
+                fill_name(&mut artifact);
+                Ok(artifact)
+            };
+            result.push(
+                convert()
+                    .with_context(|| format!("部位 {} 的第 {} 个圣遗物", position, index + 1))?,
+            );
+        }
+    }
+    Ok(result)
+}
+
+/// 从 CSV 格式导入圣遗物, 列名与导出的 CSV 格式一致, 副词条按列的顺序排列
+///
+/// # 参数
+///
+/// * `content` - 文件内容
+fn import_csv(content: &str) -> Result<Vec<Artifact>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    for header in CSV_HEADERS {
+        if !headers.iter().any(|h| h == header) {
+            bail!("CSV 文件缺少列 '{}'", header);
+        }
+    }
+    let sub_stat_headers: Vec<&str> = headers
+        .iter()
+        .filter(|h| STAT_TABLE.get_sub_stat(h).is_some())
+        .collect();
+
+    let mut result = vec![];
+    for (index, record) in reader.records().enumerate() {
+        let convert = || -> Result<Artifact> {
+            let record = record?;
+            let row: HashMap<&str, &str> = headers.iter().zip(record.iter()).collect();
+            let text = |header: &str| row.get(header).copied().unwrap_or_default().trim();
+            let number = |header: &str| -> Result<f32> {
+                text(header)
+                    .parse()
+                    .with_context(|| format!("列 '{}' 的值 '{}' 不是数字", header, text(header)))
+            };
+            let boolean = |header: &str| -> Result<bool> {
+                text(header).parse().with_context(|| {
+                    format!("列 '{}' 的值 '{}' 不是 true 或 false", header, text(header))
+                })
+            };
+            let unactivated = text(CSV_UNACTIVATED_HEADER);
+            let mut sub_stats = vec![];
+            for header in sub_stat_headers.iter() {
+                if text(header).is_empty() {
+                    continue;
+                }
+                sub_stats.push(ArtifactSubStat {
+                    name: header.to_string(),
+                    value: number(header)?,
+                    unactivated: *header == unactivated,
+                });
+            }
+            Ok(Artifact {
+                name: text(CSV_HEADERS[0]).to_string(),
+                set_name: text(CSV_HEADERS[1]).to_string(),
+                slot: text(CSV_HEADERS[2]).to_string(),
+                main_stat: text(CSV_HEADERS[3]).to_string(),
+                main_stat_value: number(CSV_HEADERS[4])?,
+                stars: number(CSV_HEADERS[5])?,
+                level: number(CSV_HEADERS[6])?,
+                locked: boolean(CSV_HEADERS[7])?,
+                marked: boolean(CSV_HEADERS[8])?,
+                equipped: boolean(CSV_HEADERS[9])?,
+                sub_stats,
+                ..Default::default()
+            })
+        };
+        // 第 1 行为列名
+        result.push(convert().with_context(|| format!("第 {} 行", index + 2))?);
+    }
+    Ok(result)
+}
+
+/// 从 JSON Lines 格式导入圣遗物, 每行的字段与识别出的圣遗物信息一致
+///
+/// # 参数
+///
+/// * `content` - 文件内容
+fn import_json_lines(content: &str) -> Result<Vec<Artifact>> {
+    let mut result = vec![];
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let artifact: Artifact =
+            serde_json::from_str(line).with_context(|| format!("第 {} 行", index + 1))?;
+        result.push(artifact);
+    }
+    Ok(result)
+}
+
+/// 根据文件扩展名和内容推断格式
+///
+/// # 参数
+///
+/// * `path` - 文件路径
+/// * `content` - 文件内容
+fn detect_format(path: &str, content: &str) -> Result<ExportFormat> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "csv" => return Ok(ExportFormat::Csv),
+        "jsonl" => return Ok(ExportFormat::JsonLines),
+        _ => {}
+    }
+    let value: serde_json::Value = serde_json::from_str(content)
+        .with_context(|| format!("无法推断文件 {} 的格式, 请指定格式", path))?;
+    if value["format"] == GOOD_FORMAT {
+        return Ok(ExportFormat::Good);
+    }
+    let is_mona = MONA_KEY_TABLE
+        .slots
+        .values()
+        .any(|position| value[position].is_array());
+    if is_mona {
+        return Ok(ExportFormat::Mona);
+    }
+    bail!("无法推断文件 {} 的格式, 请指定格式", path)
+}
+
+/// 从文件导入圣遗物列表, 支持的格式与导出格式相同
+///
+/// # 参数
+///
+/// * `path` - 文件路径
+/// * `format` - 文件格式, 为 `None` 时根据扩展名和内容推断
+pub fn import(path: &str, format: Option<ExportFormat>) -> Result<Vec<Artifact>> {
+    let content = fs::read_to_string(path).with_context(|| format!("读取文件 {} 失败", path))?;
+    let format = match format {
+        Some(format) => format,
+        None => detect_format(path, &content)?,
+    };
+    let mut artifacts = match format {
+        ExportFormat::Good => import_good(&content),
+        ExportFormat::Mona => import_mona(&content),
+        ExportFormat::Csv => import_csv(&content),
+        ExportFormat::JsonLines => import_json_lines(&content),
+    }
+    .with_context(|| format!("解析 {} 格式文件 {} 失败", format, path))?;
+    for (index, artifact) in artifacts.iter_mut().enumerate() {
+        artifact
+            .normalize()
+            .with_context(|| format!("文件 {} 中的第 {} 个圣遗物无效", path, index + 1))?;
+    }
+    Ok(artifacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::export_content;
+
+    use super::*;
+
+    /// 已标记的圣遗物, 用于检查标记状态的导入
+    fn marked() -> Artifact {
+        Artifact {
+            marked: true,
+            ..Artifact::sample()
+        }
+    }
+
+    /// 导出后再导入, 返回导入的圣遗物
+    fn round_trip(format: ExportFormat) -> Artifact {
+        let (content, _) = export_content(format, &[marked()]).unwrap();
+        let mut artifacts = match format {
+            ExportFormat::Good => import_good(&content),
+            ExportFormat::Mona => import_mona(&content),
+            ExportFormat::Csv => import_csv(&content),
+            ExportFormat::JsonLines => import_json_lines(&content),
+        }
+        .unwrap();
+        assert_eq!(artifacts.len(), 1);
+        artifacts.remove(0)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert!(round_trip(ExportFormat::JsonLines).same_as(&marked()));
+
+        // CSV 格式的副词条按列的顺序排列
+        let mut csv = round_trip(ExportFormat::Csv);
+        let mut expected = marked();
+        csv.sub_stats.sort_by(|a, b| a.name.cmp(&b.name));
+        expected.sub_stats.sort_by(|a, b| a.name.cmp(&b.name));
+        assert!(csv.same_as(&expected), "{}", csv);
+
+        // GOOD 格式没有标记状态
+        let good = round_trip(ExportFormat::Good);
+        assert!(good.same_as(&Artifact::sample()));
+
+        // mona 格式没有锁定状态和待激活的副词条
+        let mona = round_trip(ExportFormat::Mona);
+        let mut expected = Artifact {
+            locked: false,
+            ..Artifact::sample()
+        };
+        expected.sub_stats.pop();
+        assert!(mona.same_as(&expected), "{}", mona);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            detect_format("a.json", r#"{"format": "GOOD", "version": 2}"#).unwrap(),
+            ExportFormat::Good
+        );
+        assert_eq!(
+            detect_format("a.json", r#"{"version": "1", "flower": []}"#).unwrap(),
+            ExportFormat::Mona
+        );
+        assert_eq!(detect_format("a.CSV", "").unwrap(), ExportFormat::Csv);
+        assert!(detect_format("a.json", "{}").is_err());
+    }
+}
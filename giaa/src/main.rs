@@ -2,7 +2,7 @@ use std::path::Path;
 
 use anyhow::{Result, bail};
 use metadata::ARTIFACT_INFO;
-use metadata::{Coordinate, Rule};
+use metadata::{CharacterFile, Coordinate, Rule, RuleFile};
 use ocr::PPOcr;
 use parser::ExprVarKey;
 use parser::Parser;
@@ -11,6 +11,7 @@ use tracing::{error, info};
 use window::WinWindow;
 use window::Window;
 
+use crate::args::Command;
 use crate::identifier::ArtifactIdentify;
 use crate::log::init_log;
 use crate::rule_expr::RuleExpr;
@@ -23,20 +24,86 @@ mod args;
 mod artifact;
 mod color;
 mod converter;
+mod dominance;
 mod error;
+mod eval;
+mod export;
+mod global;
 mod identifier;
+mod import;
 mod log;
+mod rule_check;
 mod rule_expr;
+mod rule_list;
+mod rule_test;
 mod scanner;
+mod simulate;
+
+/// 按顺序加载所有规则文件及其引用的文件, 并按配置筛选规则
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+fn load_rule_file(args: &Args) -> Result<RuleFile> {
+    for rules_file in args.rules_files.iter() {
+        if !Path::new(rules_file).exists() {
+            bail!("规则文件 {} 不存在", rules_file);
+        }
+    }
+    let mut rule_file = Rule::load_all(&args.rules_files)?;
+    rule_file.select_profiles(&args.profiles)?;
+    if rule_file.rules.is_empty() {
+        bail!(
+            "规则文件 {} 为空, 请添加规则内容",
+            args.rules_files_display()
+        );
+    }
+    Ok(rule_file)
+}
 
-/// 程序入口
-fn application() -> Result<()> {
-    let args = Args::new();
+/// 创建支持所有圣遗物属性的表达式解析器
+///
+/// # 参数
+///
+/// * `characters` - 角色权重, 用于生成角色评分变量
+fn create_parser(characters: &CharacterFile) -> Result<Parser> {
+    let names = characters.get_names();
+    let mut number_keys = ARTIFACT_INFO.get_number_keys();
+    number_keys.extend(ARTIFACT_INFO.get_character_number_keys(&names));
+    let var_key = ExprVarKey::new(ARTIFACT_INFO.get_boolean_keys(), number_keys);
+    let mut parser = Parser::new(3, var_key)?;
+
+    let mut string_keys = ARTIFACT_INFO.get_string_keys();
+    let mut best_character_values = names;
+    best_character_values.push(ARTIFACT_INFO.words.no_character.clone());
+    string_keys.insert(
+        ARTIFACT_INFO.words.best_character.clone(),
+        best_character_values,
+    );
+    parser.set_string_vars(string_keys);
+    parser.set_param_number_vars(ARTIFACT_INFO.get_param_number_keys());
+    Ok(parser)
+}
 
-    init_log(&args)?;
+/// 程序入口
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+fn application(args: &Args) -> Result<()> {
+    init_log(args)?;
 
     info!("欢迎使用 GIAA (Genshin Impact Artifact Assistant) 原神圣遗物助手");
 
+    match &args.command {
+        Some(Command::Eval(eval_args)) => return eval::eval(args, eval_args),
+        Some(Command::Simulate(simulate_args)) => return simulate::simulate(args, simulate_args),
+        Some(Command::TestRules) => return rule_test::test_rules(args),
+        Some(Command::CheckRules) => return rule_check::check_rules(args),
+        Some(Command::ListRules) => return rule_list::list_rules(args),
+        None => {}
+    }
+
     if args.list_window_titles {
         info!("可用的窗口标题:");
         for title in WinWindow::list_window_titles()? {
@@ -45,26 +112,26 @@ fn application() -> Result<()> {
         return Ok(());
     }
 
-    if !Path::new(&args.rules_file).exists() {
-        bail!("规则文件 {} 不存在", args.rules_file);
-    }
-    let rules = Rule::load(&args.rules_file)?;
-    if rules.is_empty() {
-        bail!("规则文件 {} 为空, 请添加规则内容", args.rules_file);
-    }
-
-    let var_key = ExprVarKey::new(
-        ARTIFACT_INFO.get_boolean_keys(),
-        ARTIFACT_INFO.get_number_keys(),
-    );
+    // 角色权重
+    let characters = CharacterFile::load_beside(args.main_rules_file())?;
 
     // 表达式解析器
-    let parser = Parser::new(3, var_key)?;
+    let mut parser = create_parser(&characters)?;
 
     // 规则解析
-    let rule_exprs = RuleExpr::from_rules(&rules, &parser)?;
-    // 圣遗物属性识别筛选
-    let artifact_identify = ArtifactIdentify::filter(&rule_exprs)?;
+    let rule_exprs = if args.scan_only {
+        info!("仅扫描模式, 不加载规则文件, 不执行任何动作");
+        vec![]
+    } else {
+        let rule_file = load_rule_file(args)?;
+        RuleExpr::from_rules(&rule_file, &mut parser)?
+    };
+    // 圣遗物属性识别筛选, 导出时需要识别所有字段
+    let artifact_identify = if args.scan_only || !args.exports.is_empty() {
+        ArtifactIdentify::all()
+    } else {
+        ArtifactIdentify::filter(&rule_exprs)?
+    };
     // OCR 识别
     let pp_ocr = PPOcr::new()?;
 
@@ -84,10 +151,18 @@ fn application() -> Result<()> {
         &pp_ocr,
         &coordinate.data,
         &artifact_identify,
-        &args,
+        args,
     )?;
     // 动作执行器
-    let actuator = Actuator::new(&parser, &win_window, &converter, &rule_exprs, &coordinate)?;
+    let actuator = Actuator::new(
+        &parser,
+        &win_window,
+        &converter,
+        &rule_exprs,
+        &coordinate,
+        &characters,
+        args,
+    )?;
     // 圣遗物扫描器
     let mut scanner = Scanner::new(
         &converter,
@@ -96,7 +171,7 @@ fn application() -> Result<()> {
         &actuator,
         &pp_ocr,
         &win_window,
-        &args,
+        args,
     )?;
     // 开始扫描
     scanner.scan()
@@ -110,14 +185,21 @@ fn wait_for_key_press() {
 }
 
 fn main() {
-    match application() {
+    let args = Args::new();
+    // 子命令在终端中运行, 无需等待按键
+    let interactive = args.command.is_none();
+    match application(&args) {
         Ok(_) => {
             info!("程序已执行完毕");
-            wait_for_key_press();
+            if interactive {
+                wait_for_key_press();
+            }
         }
         Err(e) => {
             error!("程序存在异常: {}", e);
-            wait_for_key_press();
+            if interactive {
+                wait_for_key_press();
+            }
             std::process::exit(1);
         }
     }
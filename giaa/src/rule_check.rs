@@ -0,0 +1,433 @@
+use std::fmt;
+
+use anyhow::{Result, bail};
+use metadata::{ARTIFACT_INFO, CharacterFile, RuleAction};
+use parser::{Expr, Interval, Parser, split_param_var};
+use tracing::{error, info, warn};
+
+use crate::{args::Args, create_parser, load_rule_file, rule_expr::RuleExpr};
+
+/// 检查结果的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// 规则很可能误操作圣遗物
+    Error,
+    /// 规则可能与预期不符
+    Warning,
+    /// 仅供参考
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "错误"),
+            Severity::Warning => write!(f, "警告"),
+            Severity::Info => write!(f, "提示"),
+        }
+    }
+}
+
+/// 规则检查结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// 规则在规则文件中的位置, 从 0 开始
+    pub rule_index: usize,
+    pub message: String,
+}
+
+/// 数字变量的取值范围, 用于区间分析
+///
+/// # 参数
+///
+/// * `name` - 变量名称, 带参数的变量包含参数
+fn var_bounds(name: &str) -> Interval {
+    let words = &ARTIFACT_INFO.words;
+    if let Some((key, _)) = split_param_var(name) {
+        return if key == words.crit_score_probability {
+            Interval::new(0.0, 1.0)
+        } else if key == words.percentile {
+            Interval::new(0.0, 100.0)
+        } else if key == words.rank || key == words.group_count {
+            Interval::at_least(1.0)
+        } else if key == words.dominated_count {
+            Interval::at_least(0.0)
+        } else {
+            Interval::unbounded()
+        };
+    }
+    if name == words.star {
+        Interval::new(1.0, 5.0)
+    } else if name == words.level {
+        Interval::new(0.0, 20.0)
+    } else if name == words.sub_stats_count || name == words.initial_sub_stats {
+        Interval::new(0.0, 4.0)
+    } else if name == words.effective_rolls
+        || name == words.upgrades
+        || name == words.expected_crit_score
+        || name.starts_with(&format!("{}:", words.main_stat))
+        || ARTIFACT_INFO
+            .stats
+            .iter()
+            .any(|stat| name == stat || name == ARTIFACT_INFO.get_roll_count_key(stat))
+    {
+        Interval::at_least(0.0)
+    } else {
+        // 角色评分的权重可能为负数
+        Interval::unbounded()
+    }
+}
+
+/// 动作可能改变的状态, 分别为锁定和标记
+///
+/// # 参数
+///
+/// * `action` - 动作
+fn changed_state(action: &RuleAction) -> (bool, bool) {
+    match action {
+        RuleAction::Lock | RuleAction::UnlockUnmarked => (true, false),
+        RuleAction::Unmark => (false, true),
+        RuleAction::Record => (false, false),
+        _ => (true, true),
+    }
+}
+
+/// 动作执行后与执行前状态无关的状态, 分别为锁定和标记
+///
+/// # 参数
+///
+/// * `action` - 动作
+fn determined_state(action: &RuleAction) -> (bool, bool) {
+    match action {
+        RuleAction::ClickLock
+        | RuleAction::ClickMark
+        | RuleAction::UnlockUnmarked
+        | RuleAction::Record => (false, false),
+        RuleAction::Lock => (true, false),
+        RuleAction::Unmark => (false, true),
+        _ => (true, true),
+    }
+}
+
+/// 动作是否可能取消锁定
+///
+/// # 参数
+///
+/// * `action` - 动作
+fn may_unlock(action: &RuleAction) -> bool {
+    matches!(
+        action,
+        RuleAction::ClickLock | RuleAction::UnLockAndMark | RuleAction::UnlockUnmarked
+    )
+}
+
+/// 查找总会覆盖规则效果的后续规则
+///
+/// 后续规则的执行条件不依赖锁定和标记状态, 在当前规则命中时必然命中且不会因 stop 而跳过,
+/// 执行后当前规则可能改变的状态都与执行前无关, 此时当前规则的效果不会保留
+///
+/// # 参数
+///
+/// * `rule_exprs` - 规则与表达式映射
+/// * `gates` - 规则的执行条件
+/// * `index` - 当前规则的位置
+/// * `parser` - 表达式解析器
+fn find_overwriting_rule(
+    rule_exprs: &[RuleExpr],
+    gates: &[Expr],
+    index: usize,
+    parser: &Parser,
+) -> Option<usize> {
+    let words = &ARTIFACT_INFO.words;
+    let current = &rule_exprs[index];
+    let (lock_changed, mark_changed) = changed_state(current.rule.action.as_ref()?);
+    // 不改变状态的动作没有可覆盖的效果
+    if !lock_changed && !mark_changed {
+        return None;
+    }
+    // 当前规则及上级规则设置了 stop 时, 只有其子规则会继续执行
+    let mut limit = rule_exprs.len();
+    let mut ancestor = Some(index);
+    while let Some(i) = ancestor {
+        if rule_exprs[i].rule.stop {
+            limit = limit.min(i + rule_exprs[i].size);
+        }
+        ancestor = rule_exprs[i].parent;
+    }
+    for later_index in index + 1..limit {
+        let later = &rule_exprs[later_index];
+        if let Some(action) = &later.rule.action {
+            let (lock_determined, mark_determined) = determined_state(action);
+            let boolean_keys = gates[later_index].get_var_keys().boolean_keys;
+            // 当前规则命中而后续规则不命中的情况不存在
+            let escape = Expr::And(
+                Box::new(gates[index].clone()),
+                Box::new(Expr::Not(Box::new(gates[later_index].clone()))),
+            );
+            if (lock_determined || !lock_changed)
+                && (mark_determined || !mark_changed)
+                && !boolean_keys.contains(&words.locked)
+                && !boolean_keys.contains(&words.marked)
+                && parser.analyze(&escape, &var_bounds) == Some(false)
+            {
+                return Some(later_index);
+            }
+        }
+        // 中间的规则命中后可能结束执行
+        if later.rule.stop {
+            break;
+        }
+    }
+    None
+}
+
+/// 静态检查规则, 结果按规则顺序排列
+///
+/// 检查表达式恒为真或恒为假、效果总会被后续规则覆盖和取消锁定时未限制星级的规则,
+/// 子规则按同时满足上级规则的表达式分析, 分析时假设圣遗物的所有属性都已识别
+///
+/// # 参数
+///
+/// * `rule_exprs` - 规则与表达式映射
+/// * `parser` - 表达式解析器
+pub fn check(rule_exprs: &[RuleExpr], parser: &Parser) -> Vec<Finding> {
+    let star = &ARTIFACT_INFO.words.star;
+    let gates = RuleExpr::gate_exprs(rule_exprs);
+    let mut findings = vec![];
+    // 永远不会执行的规则, 其子规则不再重复报告
+    let mut dead = vec![false; rule_exprs.len()];
+    for (index, rule_expr) in rule_exprs.iter().enumerate() {
+        let mut add = |severity, message: String| {
+            findings.push(Finding {
+                severity,
+                rule_index: index,
+                message,
+            })
+        };
+        let rule = &rule_expr.rule;
+        let unlock = rule.action.as_ref().is_some_and(may_unlock);
+        let else_unlock = rule.else_action.as_ref().is_some_and(may_unlock);
+        if rule_expr.parent.is_some_and(|parent| dead[parent]) {
+            dead[index] = true;
+            continue;
+        }
+        match parser.analyze(&gates[index], &var_bounds) {
+            Some(false) => {
+                dead[index] = true;
+                add(
+                    Severity::Warning,
+                    String::from("表达式恒为假, 规则永远不会命中"),
+                );
+            }
+            Some(true) if unlock => add(
+                Severity::Error,
+                String::from("表达式恒为真, 所有圣遗物都可能被取消锁定"),
+            ),
+            Some(true) if rule.action.is_some() => add(
+                Severity::Info,
+                String::from("表达式恒为真, 所有圣遗物都会执行动作"),
+            ),
+            _ => {}
+        }
+        if (unlock || else_unlock) && !gates[index].get_var_keys().number_keys.contains(star) {
+            add(
+                Severity::Warning,
+                format!(
+                    "动作可能取消锁定, 但表达式未限制{}, 低星级或五星圣遗物可能被误解锁",
+                    star
+                ),
+            );
+        }
+        if dead[index] {
+            continue;
+        }
+        if let Some(later_index) = find_overwriting_rule(rule_exprs, &gates, index, parser) {
+            add(
+                Severity::Warning,
+                format!(
+                    "规则命中时第 {} 条规则 '{}' 必然命中, 效果总会被覆盖",
+                    later_index + 1,
+                    rule_exprs[later_index].rule.description
+                ),
+            );
+        }
+    }
+    findings
+}
+
+/// 静态检查规则文件并输出报告, 存在错误级别的问题时返回错误
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+pub fn check_rules(args: &Args) -> Result<()> {
+    let characters = CharacterFile::load_beside(args.main_rules_file())?;
+    let mut parser = create_parser(&characters)?;
+    let rule_file = load_rule_file(args)?;
+    let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser)?;
+
+    let findings = check(&rule_exprs, &parser);
+    for finding in findings.iter() {
+        let rule = &rule_exprs[finding.rule_index].rule;
+        let message = format!(
+            "[{}] 第 {} 条规则 '{}': {}",
+            finding.severity,
+            finding.rule_index + 1,
+            rule.description,
+            finding.message
+        );
+        match finding.severity {
+            Severity::Error => error!("{}", message),
+            Severity::Warning => warn!("{}", message),
+            Severity::Info => info!("{}", message),
+        }
+    }
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    info!(
+        "共检查 {} 条规则, 错误 {} 个, 警告 {} 个, 提示 {} 个",
+        rule_exprs.len(),
+        count(Severity::Error),
+        count(Severity::Warning),
+        count(Severity::Info)
+    );
+    if count(Severity::Error) > 0 {
+        bail!(
+            "规则文件 {} 存在 {} 个错误",
+            args.rules_files_display(),
+            count(Severity::Error)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use metadata::Rule;
+
+    use super::*;
+
+    fn check_yaml(yaml: &str) -> Vec<(Severity, usize)> {
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        let mut parser = create_parser(&CharacterFile::default()).unwrap();
+        let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser).unwrap();
+        check(&rule_exprs, &parser)
+            .into_iter()
+            .map(|finding| (finding.severity, finding.rule_index))
+            .collect()
+    }
+
+    #[test]
+    fn test_check_constant() {
+        let yaml = "
+- description: 恒为假
+  expression: 星级 == 5 && 等级 > 20
+  action: 锁定
+- description: 恒为真的解锁
+  expression: 星级 >= 1 || 已锁定
+  action: 取消锁定和标记
+- description: 正常
+  expression: 星级 == 5 && 暴击率 > 10
+  action: 锁定
+";
+        assert_eq!(
+            check_yaml(yaml),
+            vec![(Severity::Warning, 0), (Severity::Error, 1)]
+        );
+    }
+
+    #[test]
+    fn test_check_unlock_without_star() {
+        let yaml = "
+- description: 未限制星级
+  expression: 等级 == 20 && 暴击率 < 3
+  action: 取消锁定和标记
+- description: 限制星级
+  expression: 星级 < 5 && 等级 == 20
+  action: 点击锁定
+";
+        assert_eq!(check_yaml(yaml), vec![(Severity::Warning, 0)]);
+    }
+
+    #[test]
+    fn test_check_overwritten() {
+        let yaml = "
+- description: 被覆盖
+  expression: 星级 == 5 && 暴击率 > 10
+  action: 锁定和标记
+- description: 依赖锁定状态, 不会覆盖
+  expression: 已锁定 || 星级 == 5
+  action: 仅锁定
+- description: 只锁定, 不会覆盖标记
+  expression: 星级 == 5
+  action: 锁定
+- description: 覆盖
+  expression: 星级 >= 4
+  action: 仅锁定
+";
+        assert_eq!(
+            check_yaml(yaml),
+            vec![(Severity::Warning, 0), (Severity::Warning, 2)]
+        );
+    }
+
+    #[test]
+    fn test_check_nested() {
+        let yaml = "
+- description: 5星分组
+  expression: 星级 == 5
+  stop: true
+  rules:
+    - description: 与上级规则矛盾
+      expression: 星级 < 4
+      action: 锁定
+    - description: 被子规则覆盖
+      expression: 暴击率 > 10
+      action: 锁定
+      else_action: 取消锁定和标记
+    - description: 覆盖
+      expression: 暴击率 > 5
+      action: 仅锁定
+- description: stop 后执行, 不会覆盖
+  expression: 星级 >= 1
+  action: 取消锁定和标记
+";
+        assert_eq!(
+            check_yaml(yaml),
+            vec![
+                (Severity::Warning, 1),
+                (Severity::Warning, 2),
+                (Severity::Error, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_granular_actions() {
+        let yaml = "
+- description: 记录不会被覆盖
+  expression: 星级 == 5 && 暴击率 > 10
+  action: 记录
+- description: 取消标记被覆盖
+  expression: 星级 == 5 && 暴击率 > 10
+  action: 取消标记
+- description: 仅取消锁定不决定锁定状态
+  expression: 星级 == 5
+  action: 仅取消锁定
+- description: 标记决定所有状态
+  expression: 星级 == 5
+  action: 标记
+- description: 未限制星级的仅取消锁定
+  expression: 等级 == 20
+  action: 仅取消锁定
+";
+        assert_eq!(
+            check_yaml(yaml),
+            vec![
+                (Severity::Warning, 1),
+                (Severity::Warning, 2),
+                (Severity::Warning, 4),
+            ]
+        );
+    }
+}
@@ -1,44 +1,168 @@
-use anyhow::{Result, anyhow};
-use metadata::Rule;
-use parser::{Expr, ExprVarKey, Parser};
+use anyhow::{Error, Result, anyhow, bail};
+use metadata::{ARTIFACT_INFO, Rule, RuleFile};
+use parser::{Expr, ExprVarKey, Parser, split_param_var};
+
+use crate::global::GlobalVar;
 
 /// 规则与表达式映射
 #[derive(Debug, Clone)]
 pub struct RuleExpr {
     pub rule: Rule,
     pub expr: Expr,
+    /// 表达式用到的变量, 包含背包全局变量的参数用到的变量
     pub expr_var_key: ExprVarKey,
+    /// 表达式用到的背包全局变量
+    pub global_vars: Vec<GlobalVar>,
+    /// 上级规则的位置, 子规则仅在上级规则命中后执行
+    pub parent: Option<usize>,
+    /// 规则及所有子规则的数量, 规则未命中时跳过其子规则
+    pub size: usize,
 }
 
 impl RuleExpr {
-    /// 构造规则与表达式映射
+    /// 构造规则与表达式映射, 子规则需另行展开
     ///
     /// # 参数
     ///
     /// * `rule` - 规则
     /// * `parser` - 表达式解析器
-    pub fn from_rule(rule: Rule, parser: &Parser) -> Result<Self> {
-        let expr = parser
-            .parse(&rule.expression)
-            .map_err(|e| anyhow!("解析规则表达式失败: \n{}\n错误原因: {}", rule.expression, e))?;
-        let expr_var_key = expr.get_var_keys();
+    pub fn from_rule(mut rule: Rule, parser: &Parser) -> Result<Self> {
+        let rule_err =
+            |e: Error| anyhow!("解析规则表达式失败: \n{}\n错误原因: {}", rule.expression, e);
+        let expr = parser.parse(&rule.expression).map_err(rule_err)?;
+        let mut expr_var_key = expr.get_var_keys();
+        Self::check_param_vars(&expr_var_key).map_err(rule_err)?;
+        let global_vars = GlobalVar::from_var_key(&expr_var_key, parser).map_err(rule_err)?;
+        // 背包全局变量的参数用到的变量同样需要识别
+        for global_var in global_vars.iter() {
+            let var_key = &global_var.var_key;
+            for (keys, new_keys) in [
+                (&mut expr_var_key.boolean_keys, &var_key.boolean_keys),
+                (&mut expr_var_key.number_keys, &var_key.number_keys),
+                (&mut expr_var_key.string_keys, &var_key.string_keys),
+            ] {
+                for key in new_keys {
+                    if !keys.contains(key) {
+                        keys.push(key.clone());
+                    }
+                }
+            }
+        }
+        let size = rule.count();
+        rule.rules.clear();
         Ok(Self {
             rule,
             expr,
             expr_var_key,
+            global_vars,
+            parent: None,
+            size,
         })
     }
 
+    /// 检查带参数的变量, 双爆分达标概率的参数应为数字
+    ///
+    /// # 参数
+    ///
+    /// * `expr_var_key` - 表达式变量键
+    pub fn check_param_vars(expr_var_key: &ExprVarKey) -> Result<()> {
+        for name in expr_var_key.number_keys.iter() {
+            if let Some((key, param)) = split_param_var(name)
+                && *key == ARTIFACT_INFO.words.crit_score_probability
+                && param.parse::<f64>().is_err()
+            {
+                bail!("变量 '{}' 的参数应为数字", name);
+            }
+        }
+        Ok(())
+    }
+
     /// 批量构造规则与表达式映射
     ///
+    /// 先向解析器登记规则文件中的定义, 规则表达式中引用的定义会被展开,
+    /// 因此 `expr_var_key` 中只包含圣遗物自身的属性和背包全局变量
+    ///
     /// # 参数
     ///
-    /// * `rules` - 规则列表
+    /// * `rule_file` - 规则文件
     /// * `parser` - 表达式解析器
-    pub fn from_rules(rules: &[Rule], parser: &Parser) -> Result<Vec<Self>> {
-        rules
+    pub fn from_rules(rule_file: &RuleFile, parser: &mut Parser) -> Result<Vec<Self>> {
+        let defines = rule_file
+            .defines
             .iter()
-            .map(|rule| Self::from_rule(rule.clone(), parser))
-            .collect()
+            .map(|define| (define.name.clone(), define.expression.clone()))
+            .collect::<Vec<_>>();
+        parser
+            .set_defines(&defines)
+            .map_err(|e| anyhow!("解析规则定义失败: {}", e))?;
+
+        let mut rule_exprs = vec![];
+        Self::flatten(&rule_file.rules, None, parser, &mut rule_exprs)?;
+        Ok(rule_exprs)
+    }
+
+    /// 按执行顺序展开规则及其子规则, 子规则紧跟在上级规则之后
+    ///
+    /// # 参数
+    ///
+    /// * `rules` - 规则列表
+    /// * `parent` - 上级规则的位置
+    /// * `parser` - 表达式解析器
+    /// * `rule_exprs` - 展开后的规则与表达式映射
+    fn flatten(
+        rules: &[Rule],
+        parent: Option<usize>,
+        parser: &Parser,
+        rule_exprs: &mut Vec<Self>,
+    ) -> Result<()> {
+        for rule in rules {
+            let index = rule_exprs.len();
+            let mut rule_expr = Self::from_rule(rule.clone(), parser)?;
+            rule_expr.parent = parent;
+            rule_exprs.push(rule_expr);
+            Self::flatten(&rule.rules, Some(index), parser, rule_exprs)?;
+        }
+        Ok(())
+    }
+
+    /// 规则的执行条件, 子规则需同时满足所有上级规则的表达式
+    ///
+    /// # 参数
+    ///
+    /// * `rule_exprs` - 按执行顺序展开的规则与表达式映射
+    pub fn gate_exprs(rule_exprs: &[RuleExpr]) -> Vec<Expr> {
+        let mut gates: Vec<Expr> = vec![];
+        for rule_expr in rule_exprs.iter() {
+            let gate = match rule_expr.parent {
+                Some(parent) => Expr::And(
+                    Box::new(gates[parent].clone()),
+                    Box::new(rule_expr.expr.clone()),
+                ),
+                None => rule_expr.expr.clone(),
+            };
+            gates.push(gate);
+        }
+        gates
+    }
+
+    /// 规则执行条件用到的变量, 包含所有上级规则用到的变量
+    ///
+    /// # 参数
+    ///
+    /// * `rule_exprs` - 按执行顺序展开的规则与表达式映射
+    /// * `index` - 规则的位置
+    pub fn gate_var_key(rule_exprs: &[RuleExpr], index: usize) -> ExprVarKey {
+        let mut var_key = ExprVarKey::default();
+        let mut current = Some(index);
+        while let Some(index) = current {
+            let expr_var_key = &rule_exprs[index].expr_var_key;
+            var_key
+                .boolean_keys
+                .extend(expr_var_key.boolean_keys.clone());
+            var_key.number_keys.extend(expr_var_key.number_keys.clone());
+            var_key.string_keys.extend(expr_var_key.string_keys.clone());
+            current = rule_exprs[index].parent;
+        }
+        var_key
     }
 }
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use metadata::{CharacterFile, RuleAction};
+use tracing::info;
+
+use crate::{args::Args, create_parser, load_rule_file, rule_expr::RuleExpr};
+
+/// 将多行文本合并为一行
+///
+/// # 参数
+///
+/// * `text` - 文本
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 描述规则的动作、执行流程和标签
+///
+/// # 参数
+///
+/// * `rule_expr` - 规则与表达式映射
+fn describe_flow(rule_expr: &RuleExpr) -> String {
+    let rule = &rule_expr.rule;
+    let mut parts = vec![];
+    if let Some(action) = &rule.action {
+        parts.push(format!("命中: {}", action));
+    }
+    if let Some(action) = &rule.else_action {
+        parts.push(format!("未命中: {}", action));
+    }
+    if [&rule.action, &rule.else_action]
+        .iter()
+        .any(|action| **action == Some(RuleAction::Record))
+    {
+        parts.push(format!("记录列表: {}", rule.record_list_name()));
+    }
+    if rule_expr.size > 1 {
+        parts.push(format!("子规则: {} 条", rule_expr.size - 1));
+    }
+    if rule.stop {
+        parts.push(String::from("stop"));
+    }
+    if !rule.tags.is_empty() {
+        parts.push(format!("标签: [{}]", rule.tags.join(", ")));
+    }
+    parts.join(", ")
+}
+
+/// 输出合并引用文件并按配置筛选后的定义和规则列表, 子规则按层级缩进
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+pub fn list_rules(args: &Args) -> Result<()> {
+    let characters = CharacterFile::load_beside(args.main_rules_file())?;
+    let mut parser = create_parser(&characters)?;
+    let rule_file = load_rule_file(args)?;
+    let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser)?;
+
+    info!("规则文件: {}", args.rules_files_display());
+    if args.profiles.is_empty() {
+        info!("规则配置: 未指定, 执行所有规则");
+    } else {
+        info!("规则配置: {}", args.profiles.join(", "));
+    }
+    info!("命名定义 {} 个:", rule_file.defines.len());
+    for define in rule_file.defines.iter() {
+        info!("  {} = {}", define.name, single_line(&define.expression));
+    }
+
+    info!("规则 {} 条:", rule_exprs.len());
+    let mut depths: Vec<usize> = vec![];
+    for (index, rule_expr) in rule_exprs.iter().enumerate() {
+        let depth = rule_expr.parent.map_or(0, |parent| depths[parent] + 1);
+        depths.push(depth);
+        let indent = "  ".repeat(depth + 1);
+        let rule = &rule_expr.rule;
+        info!(
+            "{}#{} {} ({})",
+            indent,
+            index + 1,
+            rule.description,
+            rule.source
+        );
+        info!("{}  表达式: {}", indent, single_line(&rule.expression));
+        info!("{}  {}", indent, describe_flow(rule_expr));
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use std::slice;
+
+use anyhow::{Result, bail};
+use metadata::{CharacterFile, RuleExample};
+use parser::{Expr, ExprResult, ExprVarKey, Parser};
+use tracing::{error, info, warn};
+
+use crate::{
+    actuator::Actuator, args::Args, artifact::Artifact, create_parser, load_rule_file,
+    rule_expr::RuleExpr,
+};
+
+/// 描述布尔状态, 例如 `命中` 和 `未命中`
+///
+/// # 参数
+///
+/// * `value` - 状态
+/// * `word` - 状态为真时的描述
+fn describe(value: bool, word: &str) -> String {
+    if value {
+        word.to_string()
+    } else {
+        format!("未{}", word)
+    }
+}
+
+/// 执行单个测试用例, 返回与预期不符的结果
+///
+/// 是否命中按圣遗物初始状态单独计算当前规则的执行条件, 子规则需同时命中所有上级规则,
+/// 锁定和标记状态为按顺序执行所有规则后的状态
+///
+/// # 参数
+///
+/// * `example` - 测试用例
+/// * `gate` - 用例所属规则的执行条件
+/// * `var_key` - 执行条件用到的变量
+/// * `actuator` - 离线动作执行器
+/// * `parser` - 表达式解析器
+/// * `characters` - 角色权重
+fn run_example(
+    example: &RuleExample,
+    gate: &Expr,
+    var_key: &ExprVarKey,
+    actuator: &Actuator,
+    parser: &Parser,
+    characters: &CharacterFile,
+) -> Result<Vec<String>> {
+    let expect = &example.expect;
+    if expect.is_empty() {
+        bail!("未设置预期结果 (hit、locked 或 marked)");
+    }
+    let mut artifact = Artifact::from_yaml_value(&example.artifact)?;
+    // 背包全局变量按只有当前圣遗物的背包计算
+    actuator.fill_global_vars(slice::from_mut(&mut artifact))?;
+
+    let mut errors = vec![];
+    if let Some(expected) = expect.hit {
+        let expr_var = Actuator::generate_vars(&artifact, var_key, characters);
+        match parser.exec(gate, &expr_var)? {
+            ExprResult::Boolean(hit) if hit == expected => {}
+            ExprResult::Boolean(hit) => errors.push(format!(
+                "预期{}, 实际{}",
+                describe(expected, "命中"),
+                describe(hit, "命中")
+            )),
+            result => errors.push(format!(
+                "预期{}, 实际结果为 {}",
+                describe(expected, "命中"),
+                result
+            )),
+        }
+    }
+
+    actuator.exec(&mut artifact)?;
+    if let Some(expected) = expect.locked
+        && artifact.locked != expected
+    {
+        errors.push(format!(
+            "预期执行后{}, 实际{}",
+            describe(expected, "锁定"),
+            describe(artifact.locked, "锁定")
+        ));
+    }
+    if let Some(expected) = expect.marked
+        && artifact.marked != expected
+    {
+        errors.push(format!(
+            "预期执行后{}, 实际{}",
+            describe(expected, "标记"),
+            describe(artifact.marked, "标记")
+        ));
+    }
+    Ok(errors)
+}
+
+/// 执行规则文件中的所有测试用例并输出报告, 有用例未通过时返回错误
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+pub fn test_rules(args: &Args) -> Result<()> {
+    let characters = CharacterFile::load_beside(args.main_rules_file())?;
+    let mut parser = create_parser(&characters)?;
+    let rule_file = load_rule_file(args)?;
+    let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser)?;
+    let actuator = Actuator::offline(&parser, &rule_exprs, &characters, args)?;
+    let gates = RuleExpr::gate_exprs(&rule_exprs);
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for (rule_index, rule_expr) in rule_exprs.iter().enumerate() {
+        for (example_index, example) in rule_expr.rule.examples.iter().enumerate() {
+            let mut name = format!(
+                "#{} {} 用例 {}",
+                rule_index + 1,
+                rule_expr.rule.description,
+                example_index + 1
+            );
+            if !example.description.is_empty() {
+                name = format!("{} ({})", name, example.description);
+            }
+            let var_key = RuleExpr::gate_var_key(&rule_exprs, rule_index);
+            let gate = &gates[rule_index];
+            match run_example(example, gate, &var_key, &actuator, &parser, &characters) {
+                Ok(errors) if errors.is_empty() => {
+                    passed += 1;
+                    info!("通过: {}", name);
+                }
+                Ok(errors) => {
+                    failed += 1;
+                    error!("失败: {}, {}", name, errors.join("; "));
+                }
+                Err(e) => {
+                    failed += 1;
+                    error!("失败: {}, {}", name, e);
+                }
+            }
+        }
+    }
+
+    if passed + failed == 0 {
+        warn!(
+            "规则文件 {} 中没有测试用例 (examples)",
+            args.rules_files_display()
+        );
+        return Ok(());
+    }
+    info!(
+        "共 {} 个测试用例, 通过 {} 个, 失败 {} 个",
+        passed + failed,
+        passed,
+        failed
+    );
+    if failed > 0 {
+        bail!("{} 个规则测试用例未通过", failed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use metadata::Rule;
+
+    use super::*;
+
+    /// 加载规则并执行指定规则的所有测试用例
+    ///
+    /// 执行器借用解析器和规则, 无法一并返回, 因此在同一函数内完成加载和执行
+    ///
+    /// # 参数
+    ///
+    /// * `yaml` - 规则文件内容
+    /// * `rule_index` - 展开后规则的位置
+    fn run_examples(yaml: &str, rule_index: usize) -> Vec<Result<Vec<String>>> {
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        let characters = CharacterFile::default();
+        let mut parser = create_parser(&characters).unwrap();
+        let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser).unwrap();
+        let args = <Args as clap::Parser>::parse_from(["giaa"]);
+        let actuator = Actuator::offline(&parser, &rule_exprs, &characters, &args).unwrap();
+        let gate = &RuleExpr::gate_exprs(&rule_exprs)[rule_index];
+        let var_key = RuleExpr::gate_var_key(&rule_exprs, rule_index);
+        rule_exprs[rule_index]
+            .rule
+            .examples
+            .iter()
+            .map(|example| run_example(example, gate, &var_key, &actuator, &parser, &characters))
+            .collect()
+    }
+
+    #[test]
+    fn test_run_example() {
+        let yaml = "
+- description: 锁定双爆分达标的5星圣遗物
+  expression: 星级 == 5 && 暴击率 * 2 + 暴击伤害 >= 30
+  action: 锁定
+  examples:
+    - artifact: { stars: 5, sub_stats: [{ name: 暴击率, value: 10 }, { name: 暴击伤害, value: 10 }] }
+      expect: { hit: true, locked: true, marked: false }
+    - artifact: { stars: 4, locked: true, sub_stats: [{ name: 暴击率, value: 10 }] }
+      expect: { hit: true, locked: false }
+    - artifact: { stars: 5, sub_stats: [{ name: 暴击率, value: 10 }] }
+      expect: {}
+";
+        let results = run_examples(yaml, 0);
+        assert!(results[0].as_ref().unwrap().is_empty());
+        assert_eq!(
+            results[1].as_ref().unwrap(),
+            &vec!["预期命中, 实际未命中", "预期执行后未锁定, 实际锁定"]
+        );
+        assert_eq!(
+            results[2].as_ref().unwrap_err().to_string(),
+            "未设置预期结果 (hit、locked 或 marked)"
+        );
+    }
+
+    #[test]
+    fn test_run_example_nested() {
+        let yaml = "
+- description: 5星分组
+  expression: 星级 == 5
+  rules:
+    - description: 双爆
+      expression: 暴击率 > 0
+      action: 锁定
+      examples:
+        - description: 上级规则未命中时子规则不会执行
+          artifact: { stars: 4, sub_stats: [{ name: 暴击率, value: 10 }] }
+          expect: { hit: true }
+        - artifact: { stars: 5, sub_stats: [{ name: 暴击率, value: 10 }] }
+          expect: { hit: true, locked: true }
+";
+        let results = run_examples(yaml, 1);
+        assert_eq!(results[0].as_ref().unwrap(), &vec!["预期命中, 实际未命中"]);
+        assert!(results[1].as_ref().unwrap().is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 use std::{thread, time::Duration};
 
 use crate::{
-    actuator::{Actuator, ActuatorResult},
+    actuator::{Actuator, ActuatorResult, RecordLists},
     args::Args,
+    artifact::Artifact,
     color::{average_color_diff, color_distance},
     converter::Converter,
     error::GiaaError,
+    export,
     identifier::{Identifier, IdentifyResult},
 };
 use anyhow::{Result, anyhow, bail};
@@ -23,6 +25,16 @@ struct Scrollbar {
     scroll_length: i32,
 }
 
+/// 首次扫描时记录的圣遗物及其在列表中的位置
+#[derive(Debug)]
+struct ScannedArtifact {
+    /// 在整个列表中的行数
+    row: u32,
+    /// 列数
+    col: u32,
+    artifact: Artifact,
+}
+
 /// 扫描器
 pub struct Scanner<'a> {
     converter: &'a Converter<'a>,
@@ -36,8 +48,16 @@ pub struct Scanner<'a> {
     row_index: u32,
     page_scroll_count: u32,
     scroll_count: u32,
+    /// 是否已记录滚动与行数的关系, 记录后可直接移动多行
+    row_calibrated: bool,
     artifact_page_turn_color: image::Rgb<u8>,
     actuator_results: Vec<ActuatorResult>,
+    /// 规则用到背包全局变量时, 先扫描整个列表, 再返回修改需要更改状态的圣遗物
+    two_pass: bool,
+    /// 记录的圣遗物, 两遍扫描或导出时使用, 锁定状态为执行动作后的状态
+    scanned_artifacts: Vec<ScannedArtifact>,
+    /// 规则记录动作收集的圣遗物
+    records: RecordLists,
 }
 
 impl<'a> Scanner<'a> {
@@ -73,8 +93,12 @@ impl<'a> Scanner<'a> {
             row_index: 0,
             page_scroll_count: 0,
             scroll_count: 0,
+            row_calibrated: false,
             artifact_page_turn_color: image::Rgb([0, 0, 0]),
             actuator_results: vec![],
+            two_pass: actuator.has_global_vars(),
+            scanned_artifacts: vec![],
+            records: RecordLists::default(),
         })
     }
 
@@ -149,12 +173,20 @@ impl<'a> Scanner<'a> {
         thread::sleep(Duration::from_secs(1));
 
         // 圣遗物列表归位
+        self.reset_list_position()?;
+
+        self.artifact_page_turn_color = self.get_artifact_page_turn()?;
+        Ok(())
+    }
+
+    /// 圣遗物列表归位到顶部
+    fn reset_list_position(&mut self) -> Result<()> {
         self.move_mouse(&self.coordinate_data.artifact_list_center)?;
         self.window
             .scroll_vertical(self.coordinate_data.artifact_list_scroll_to_top_length)?;
         thread::sleep(Duration::from_secs(1));
-
-        self.artifact_page_turn_color = self.get_artifact_page_turn()?;
+        self.row_index = 0;
+        self.scroll_count = 0;
         Ok(())
     }
 
@@ -191,7 +223,7 @@ impl<'a> Scanner<'a> {
         let mut is_start = false;
 
         for y in 0..height {
-            let point = point_offset(&scrollbar_start, None, Some(y));
+            let point = point_offset(scrollbar_start, None, Some(y));
             let color = self.get_pixel_color(&point)?;
             let diff = color_distance(&color, &button_color);
             if diff < 10000 {
@@ -260,6 +292,21 @@ impl<'a> Scanner<'a> {
         Ok(diff > 1000)
     }
 
+    /// 获取当前页圣遗物卡片的中心坐标
+    ///
+    /// # 参数
+    ///
+    /// * `col` - 列数
+    /// * `row` - 行数
+    fn card_center(&self, col: u32, row: u32) -> Point {
+        Point {
+            x: self.coordinate_data.artifact_list_card_start.x
+                + (col * self.coordinate_data.artifact_list_card_horizontal_interval) as i32,
+            y: self.coordinate_data.artifact_list_card_start.y
+                + (row * self.coordinate_data.artifact_list_card_vertical_interval) as i32,
+        }
+    }
+
     /// 扫描当前页的圣遗物
     ///
     /// # 参数
@@ -276,13 +323,7 @@ impl<'a> Scanner<'a> {
 
         for row in start..start + count {
             for col in 0..self.coordinate_data.artifact_page_cols {
-                let center = Point {
-                    x: self.coordinate_data.artifact_list_card_start.x
-                        + (col * self.coordinate_data.artifact_list_card_horizontal_interval)
-                            as i32,
-                    y: self.coordinate_data.artifact_list_card_start.y
-                        + (row * self.coordinate_data.artifact_list_card_vertical_interval) as i32,
-                };
+                let center = self.card_center(col, row);
 
                 if self.window.is_mouse_right_down() {
                     return Err(GiaaError::RightClickExit);
@@ -301,8 +342,32 @@ impl<'a> Scanner<'a> {
                     Ok(artifact_result) => match artifact_result {
                         IdentifyResult::Artifact(mut artifact) => {
                             info!("识别到: {}", artifact);
-                            let actuator_result = self.actuator.exec(&mut artifact)?;
+                            if self.two_pass {
+                                self.scanned_artifacts.push(ScannedArtifact {
+                                    row: self.row_index + row,
+                                    col,
+                                    artifact: *artifact,
+                                });
+                                continue;
+                            }
+                            let (actuator_result, hits) =
+                                self.actuator.exec_traced(&mut artifact)?;
                             self.actuator_results.push(actuator_result);
+                            let label = format!(
+                                "第 {} 行第 {} 列 {}",
+                                self.row_index + row + 1,
+                                col + 1,
+                                artifact.name
+                            );
+                            self.actuator
+                                .collect_records(&hits, &label, &mut self.records);
+                            if !self.args.exports.is_empty() {
+                                self.scanned_artifacts.push(ScannedArtifact {
+                                    row: self.row_index + row,
+                                    col,
+                                    artifact: *artifact,
+                                });
+                            }
                             thread::sleep(std::time::Duration::from_millis(100));
                         }
                         IdentifyResult::ArtifactEnhancementMaterial(material) => {
@@ -389,7 +454,7 @@ impl<'a> Scanner<'a> {
         // 移到列表中心
         self.move_mouse(&self.coordinate_data.artifact_list_center)?;
 
-        if self.row_index >= rows {
+        if self.row_calibrated {
             for _ in 0..self.calculate_page_scroll_count(row_count) {
                 self.window.scroll_vertical(1)?;
                 self.scroll_count += 1;
@@ -401,6 +466,7 @@ impl<'a> Scanner<'a> {
                 self.move_row()?;
                 self.row_index += 1;
             }
+            self.row_calibrated = self.row_index >= rows;
         }
         thread::sleep(Duration::from_millis(200));
         Ok(())
@@ -431,6 +497,104 @@ impl<'a> Scanner<'a> {
         Ok(())
     }
 
+    /// 计算背包全局变量并执行规则, 再返回列表中修改需要更改状态的圣遗物
+    ///
+    /// 修改前会重新识别圣遗物, 与首次扫描的结果不一致时跳过, 避免误操作
+    fn apply_scanned_artifacts(&mut self) -> Result<(), GiaaError> {
+        let mut artifacts: Vec<Artifact> = self
+            .scanned_artifacts
+            .iter()
+            .map(|scanned| scanned.artifact.clone())
+            .collect();
+        self.actuator.fill_global_vars(&mut artifacts)?;
+        self.actuator.report_dominance(&artifacts, |index| {
+            let scanned = &self.scanned_artifacts[index];
+            format!("第 {} 行第 {} 列", scanned.row + 1, scanned.col + 1)
+        })?;
+
+        let mut changes = vec![];
+        for (index, artifact) in artifacts.iter_mut().enumerate() {
+            let scanned = &self.scanned_artifacts[index];
+            info!(
+                "执行规则, 第 {} 行第 {} 列: {}",
+                scanned.row + 1,
+                scanned.col + 1,
+                scanned.artifact
+            );
+            let (actuator_result, hits) = self.actuator.evaluate_traced(artifact)?;
+            self.actuator_results.push(actuator_result);
+            let label = format!(
+                "第 {} 行第 {} 列 {}",
+                scanned.row + 1,
+                scanned.col + 1,
+                scanned.artifact.name
+            );
+            self.actuator
+                .collect_records(&hits, &label, &mut self.records);
+            if artifact.locked != scanned.artifact.locked
+                || artifact.marked != scanned.artifact.marked
+            {
+                changes.push((index, actuator_result));
+            }
+        }
+        info!(
+            "共扫描 {} 个圣遗物, 需要更改状态: {} 个",
+            self.scanned_artifacts.len(),
+            changes.len()
+        );
+        let Some(last) = self.scanned_artifacts.last() else {
+            return Ok(());
+        };
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let rows = self.coordinate_data.artifact_page_rows;
+        // 列表滚动到底部时最后一行位于页面底部
+        let max_row_index = (last.row + 1).saturating_sub(rows);
+        self.reset_list_position()?;
+        for (index, actuator_result) in changes {
+            let (row, col) = {
+                let scanned = &self.scanned_artifacts[index];
+                (scanned.row, scanned.col)
+            };
+            if row >= self.row_index + rows {
+                self.move_rows(row.min(max_row_index) - self.row_index)?;
+            }
+
+            if self.window.is_mouse_right_down() {
+                return Err(GiaaError::RightClickExit);
+            }
+            self.click(&self.card_center(col, row - self.row_index))?;
+            thread::sleep(Duration::from_millis(self.args.screenshot_delay));
+            self.refresh_screenshot()?;
+
+            match self.identifier.identify(&self.screenshot) {
+                Ok(IdentifyResult::Artifact(artifact))
+                    if artifact.same_as(&self.scanned_artifacts[index].artifact) =>
+                {
+                    info!("更改状态: {}, 目标状态: {:?}", artifact, actuator_result);
+                    self.actuator.apply(&artifact, actuator_result)?;
+                    let scanned = &mut self.scanned_artifacts[index].artifact;
+                    scanned.locked = artifacts[index].locked;
+                    scanned.marked = artifacts[index].marked;
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Ok(_) => {
+                    warn!(
+                        "第 {} 行第 {} 列的圣遗物与首次扫描不一致, 已跳过",
+                        row + 1,
+                        col + 1
+                    );
+                }
+                Err(e) => {
+                    error!("识别圣遗物失败: {}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// 打印处理结果
     fn print_actuator_results(&self) -> Result<()> {
         let mut lock_and_mark_count = 0;
@@ -447,6 +611,7 @@ impl<'a> Scanner<'a> {
             "执行动作结果: 标记(标记和锁定): {}个, 仅锁定: {}个, 未锁定(未标记和未锁定): {}个",
             lock_and_mark_count, only_lock_count, unlock_and_unmark_count
         );
+        self.records.report();
         Ok(())
     }
 
@@ -454,13 +619,34 @@ impl<'a> Scanner<'a> {
     pub fn scan(&mut self) -> Result<()> {
         self.refresh_screenshot()?;
         self.init_backpack()?;
-        self.scan_all_page().or_else(|e| match e {
-            GiaaError::RightClickExit => {
-                warn!("{}", GiaaError::RightClickExit);
-                Ok(())
+        if self.two_pass {
+            info!("规则用到背包全局变量, 将先扫描整个列表, 计算后再返回修改需要更改状态的圣遗物");
+        }
+        self.scan_all_page()
+            .and_then(|_| {
+                if self.two_pass {
+                    self.apply_scanned_artifacts()
+                } else {
+                    Ok(())
+                }
+            })
+            .or_else(|e| match e {
+                GiaaError::RightClickExit => {
+                    warn!("{}", GiaaError::RightClickExit);
+                    Ok(())
+                }
+                _ => Err(e),
+            })?;
+        if !self.args.exports.is_empty() {
+            let artifacts: Vec<Artifact> = self
+                .scanned_artifacts
+                .iter()
+                .map(|scanned| scanned.artifact.clone())
+                .collect();
+            for target in self.args.exports.iter() {
+                export::export(target, &artifacts)?;
             }
-            _ => Err(e),
-        })?;
+        }
         self.print_actuator_results()
     }
 }
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use metadata::CharacterFile;
+use tracing::info;
+
+use crate::{
+    actuator::{Actuator, ActuatorResult, RecordLists, RuleHit},
+    args::{Args, SimulateArgs},
+    create_parser, import, load_rule_file,
+    rule_expr::RuleExpr,
+};
+
+/// 单条规则的模拟统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct RuleStats {
+    /// 命中的圣遗物数量, 不含执行 else_action 的圣遗物
+    hits: usize,
+    /// 由未锁定变为锁定的数量
+    locks: usize,
+    /// 由未标记变为标记的数量
+    marks: usize,
+    /// 由锁定变为未锁定的数量
+    unlocks: usize,
+}
+
+impl RuleStats {
+    /// 统计一次规则命中
+    ///
+    /// # 参数
+    ///
+    /// * `hit` - 命中的规则及前后状态
+    fn add(&mut self, hit: &RuleHit) {
+        let was_locked = hit.before != ActuatorResult::UnlockAndUnmark;
+        let is_locked = hit.after != ActuatorResult::UnlockAndUnmark;
+        if !hit.else_branch {
+            self.hits += 1;
+        }
+        if !was_locked && is_locked {
+            self.locks += 1;
+        }
+        if was_locked && !is_locked {
+            self.unlocks += 1;
+        }
+        if hit.before != ActuatorResult::LockAndMark && hit.after == ActuatorResult::LockAndMark {
+            self.marks += 1;
+        }
+    }
+}
+
+/// 离线模拟执行规则文件, 从背包文件导入圣遗物, 按扫描时的流程执行动作但不点击按钮
+///
+/// # 参数
+///
+/// * `args` - 命令行参数
+/// * `simulate_args` - 离线模拟参数
+pub fn simulate(args: &Args, simulate_args: &SimulateArgs) -> Result<()> {
+    let mut artifacts = import::import(&simulate_args.inventory, simulate_args.format)?;
+    info!(
+        "从 {} 导入 {} 个圣遗物",
+        simulate_args.inventory,
+        artifacts.len()
+    );
+
+    let characters = CharacterFile::load_beside(args.main_rules_file())?;
+    let mut parser = create_parser(&characters)?;
+    let rule_file = load_rule_file(args)?;
+    let rule_exprs = RuleExpr::from_rules(&rule_file, &mut parser)?;
+    let actuator = Actuator::offline(&parser, &rule_exprs, &characters, args)?;
+    if actuator.has_global_vars() {
+        actuator.fill_global_vars(&mut artifacts)?;
+        actuator.report_dominance(&artifacts, |index| format!("第 {} 个", index + 1))?;
+    }
+
+    let mut rule_stats = vec![RuleStats::default(); rule_exprs.len()];
+    let mut records = RecordLists::default();
+    let mut results = vec![];
+    let mut changed_count = 0;
+    for (index, artifact) in artifacts.iter_mut().enumerate() {
+        info!("第 {} 个: {}", index + 1, artifact);
+        let before_artifact = artifact.clone();
+        let before = ActuatorResult::from_artifact(artifact);
+        let (after, hits) = actuator.evaluate_traced(artifact)?;
+        // 离线模式下不点击按钮, 与扫描时的执行流程保持一致
+        actuator.apply(&before_artifact, after)?;
+        for hit in hits.iter() {
+            rule_stats[hit.index].add(hit);
+        }
+        let label = format!("第 {} 个 {}", index + 1, artifact.name);
+        actuator.collect_records(&hits, &label, &mut records);
+        let hit_rules: Vec<String> = hits
+            .iter()
+            .map(|hit| {
+                let suffix = if hit.else_branch { "(else)" } else { "" };
+                format!("#{}{}", hit.index + 1, suffix)
+            })
+            .collect();
+        if before != after {
+            changed_count += 1;
+        }
+        info!(
+            "第 {} 个: {} → {}, 命中规则: [{}]",
+            index + 1,
+            before,
+            after,
+            hit_rules.join(", ")
+        );
+        results.push(after);
+    }
+
+    info!("规则模拟结果:");
+    for (index, (rule_expr, stats)) in rule_exprs.iter().zip(rule_stats.iter()).enumerate() {
+        info!(
+            "  #{} {}: 命中 {} 个, 锁定 {} 个, 标记 {} 个, 取消锁定 {} 个",
+            index + 1,
+            rule_expr.rule.description,
+            stats.hits,
+            stats.locks,
+            stats.marks,
+            stats.unlocks
+        );
+    }
+    let count = |result: ActuatorResult| results.iter().filter(|r| **r == result).count();
+    info!(
+        "执行动作结果: 标记(标记和锁定): {}个, 仅锁定: {}个, 未锁定(未标记和未锁定): {}个, 状态改变: {}个",
+        count(ActuatorResult::LockAndMark),
+        count(ActuatorResult::OnlyLock),
+        count(ActuatorResult::UnlockAndUnmark),
+        changed_count
+    );
+    records.report();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_stats() {
+        let hit = |before, after| RuleHit {
+            index: 0,
+            else_branch: false,
+            before,
+            after,
+        };
+        let mut stats = RuleStats::default();
+        stats.add(&hit(
+            ActuatorResult::UnlockAndUnmark,
+            ActuatorResult::OnlyLock,
+        ));
+        stats.add(&hit(
+            ActuatorResult::UnlockAndUnmark,
+            ActuatorResult::LockAndMark,
+        ));
+        stats.add(&hit(
+            ActuatorResult::LockAndMark,
+            ActuatorResult::UnlockAndUnmark,
+        ));
+        stats.add(&hit(ActuatorResult::OnlyLock, ActuatorResult::OnlyLock));
+        stats.add(&RuleHit {
+            else_branch: true,
+            ..hit(ActuatorResult::OnlyLock, ActuatorResult::UnlockAndUnmark)
+        });
+        assert_eq!(
+            stats,
+            RuleStats {
+                hits: 4,
+                locks: 2,
+                marks: 1,
+                unlocks: 2,
+            }
+        );
+    }
+}
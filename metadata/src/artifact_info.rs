@@ -5,6 +5,8 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
+use crate::STAT_TABLE;
+
 /// 圣遗物名称
 #[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
 pub struct Artifact {
@@ -48,6 +50,44 @@ pub struct ArtifactWord {
     pub sub_stats_count: String,
     #[schemars(title = "待激活")]
     pub unactivated: String,
+    #[schemars(title = "名称")]
+    pub name: String,
+    #[schemars(title = "部位")]
+    pub slot: String,
+    #[schemars(title = "套装")]
+    pub set_name: String,
+    #[schemars(title = "主词条")]
+    pub main_stat_name: String,
+    #[schemars(title = "副词条强化次数后缀")]
+    pub roll_count: String,
+    #[schemars(title = "有效词条数")]
+    pub effective_rolls: String,
+    #[schemars(title = "总强化次数")]
+    pub upgrades: String,
+    #[schemars(title = "初始词条数")]
+    pub initial_sub_stats: String,
+    #[schemars(title = "强化次数不唯一")]
+    pub ambiguous_rolls: String,
+    #[schemars(title = "满级双爆分期望")]
+    pub expected_crit_score: String,
+    #[schemars(title = "满级双爆分达到参数的概率")]
+    pub crit_score_probability: String,
+    #[schemars(title = "角色评分前缀")]
+    pub character_score: String,
+    #[schemars(title = "最高角色评分")]
+    pub best_character_score: String,
+    #[schemars(title = "最适角色")]
+    pub best_character: String,
+    #[schemars(title = "没有适用的角色")]
+    pub no_character: String,
+    #[schemars(title = "背包内同组圣遗物中的评分排名")]
+    pub rank: String,
+    #[schemars(title = "背包内同组圣遗物中的评分百分位")]
+    pub percentile: String,
+    #[schemars(title = "背包内同组圣遗物的数量")]
+    pub group_count: String,
+    #[schemars(title = "背包内支配该圣遗物的同套装、同部位、同主词条圣遗物数量")]
+    pub dominated_count: String,
 
     #[schemars(title = "暂无满足条件的圣遗物")]
     pub no_match_artifacts: String,
@@ -138,6 +178,7 @@ impl ArtifactInfo {
             self.words.marked.clone(),
             self.words.locked.clone(),
             self.words.sanctifying_elixir.clone(),
+            self.words.ambiguous_rolls.clone(),
         ];
 
         result.extend(self.slots.clone());
@@ -147,18 +188,85 @@ impl ArtifactInfo {
         result
     }
 
+    /// 获取所有字符串型关键字及其所有取值
+    pub fn get_string_keys(&self) -> HashMap<String, Vec<String>> {
+        let dedup = |mut values: Vec<String>| {
+            values.sort();
+            values.dedup();
+            values
+        };
+        HashMap::from([
+            (self.words.name.clone(), dedup(self.get_artifact_names())),
+            (self.words.slot.clone(), self.slots.clone()),
+            (
+                self.words.set_name.clone(),
+                dedup(self.get_artifact_set_names()),
+            ),
+            (self.words.main_stat_name.clone(), self.stats.clone()),
+        ])
+    }
+
+    /// 获取副词条强化次数的变量名称, 例如 "暴击率次数"
+    ///
+    /// # 参数
+    ///
+    /// * `stat` - 属性名称
+    pub fn get_roll_count_key(&self, stat: &str) -> String {
+        format!("{}{}", stat, self.words.roll_count)
+    }
+
+    /// 获取角色评分关键字
+    ///
+    /// # 参数
+    ///
+    /// * `character` - 角色名称
+    pub fn get_character_score_key(&self, character: &str) -> String {
+        format!("{}:{}", self.words.character_score, character)
+    }
+
+    /// 获取所有角色相关的数字型关键字
+    ///
+    /// # 参数
+    ///
+    /// * `characters` - 角色名称
+    pub fn get_character_number_keys(&self, characters: &[String]) -> Vec<String> {
+        let mut result = vec![self.words.best_character_score.clone()];
+        for character in characters {
+            result.push(self.get_character_score_key(character));
+        }
+        result
+    }
+
+    /// 获取所有带参数的数字型关键字
+    pub fn get_param_number_keys(&self) -> Vec<String> {
+        vec![
+            self.words.crit_score_probability.clone(),
+            self.words.rank.clone(),
+            self.words.percentile.clone(),
+            self.words.group_count.clone(),
+            self.words.dominated_count.clone(),
+        ]
+    }
+
     /// 获取所有数字型关键字
     pub fn get_number_keys(&self) -> Vec<String> {
         let mut result = vec![
             self.words.star.clone(),
             self.words.level.clone(),
             self.words.sub_stats_count.clone(),
+            self.words.effective_rolls.clone(),
+            self.words.upgrades.clone(),
+            self.words.initial_sub_stats.clone(),
+            self.words.expected_crit_score.clone(),
         ];
 
         for stat in self.stats.iter() {
             result.push(stat.clone());
             result.push(format!("{}:{}", self.words.main_stat, stat));
         }
+        for sub_stat in STAT_TABLE.sub_stats.iter() {
+            result.push(self.get_roll_count_key(&sub_stat.name));
+        }
         result
     }
 }
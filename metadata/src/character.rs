@@ -0,0 +1,256 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{ARTIFACT_INFO, STAT_TABLE};
+
+/// 角色权重文件名, 与规则文件放在同一目录
+pub const CHARACTER_FILE_NAME: &str = "characters.yaml";
+
+/// 评分时副词条数值的换算基准星级, 评分单位为该星级的最高档强化数值
+const SCORE_BASE_STARS: u32 = 5;
+
+/// 角色对圣遗物的需求
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct CharacterProfile {
+    #[schemars(title = "角色名称")]
+    pub name: String,
+    #[schemars(title = "副词条权重(一般为 0 到 1, 未列出的属性权重为 0)")]
+    pub weights: HashMap<String, f64>,
+    #[schemars(title = "适用套装(为空表示不限套装)")]
+    #[serde(default)]
+    pub sets: Vec<String>,
+    #[schemars(title = "各部位适用主词条(未列出的部位不限主词条)")]
+    #[serde(default)]
+    pub main_stats: HashMap<String, Vec<String>>,
+}
+
+/// 角色权重文件
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CharacterFile {
+    #[schemars(title = "角色列表")]
+    pub characters: Vec<CharacterProfile>,
+}
+
+impl CharacterProfile {
+    /// 检查权重、套装和主词条名称是否有效, 并将套装别名转为套装名称
+    fn validate(&mut self) -> Result<()> {
+        for (stat, weight) in self.weights.iter() {
+            if STAT_TABLE.get_sub_stat(stat).is_none() {
+                bail!(
+                    "角色 '{}' 的权重属性 '{}' 不是有效的副词条",
+                    self.name,
+                    stat
+                );
+            }
+            if !weight.is_finite() {
+                bail!(
+                    "角色 '{}' 的属性 '{}' 权重 {} 不是有效的数值",
+                    self.name,
+                    stat,
+                    weight
+                );
+            }
+        }
+        for set in self.sets.iter_mut() {
+            match ARTIFACT_INFO.get_artifact_set_name_by_alias(set) {
+                Some(name) => *set = name,
+                None => bail!("角色 '{}' 的适用套装 '{}' 不存在", self.name, set),
+            }
+        }
+        for (slot, main_stats) in self.main_stats.iter() {
+            if !ARTIFACT_INFO.slots.contains(slot) {
+                bail!("角色 '{}' 的部位 '{}' 不存在", self.name, slot);
+            }
+            for main_stat in main_stats {
+                if !ARTIFACT_INFO.stats.contains(main_stat) {
+                    bail!("角色 '{}' 的主词条 '{}' 不存在", self.name, main_stat);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 计算圣遗物对角色的评分
+    ///
+    /// 评分为副词条折算成 5 星最高档强化次数后的加权和, 套装或主词条不适用时评分为 0
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - 部位
+    /// * `main_stat` - 主词条名称
+    /// * `set_name` - 套装名称
+    /// * `sub_stats` - 副词条名称、数值和是否待激活
+    pub fn score(
+        &self,
+        slot: &str,
+        main_stat: &str,
+        set_name: &str,
+        sub_stats: &[(&str, f32, bool)],
+    ) -> f64 {
+        if !self.sets.is_empty() && !self.sets.iter().any(|set| set == set_name) {
+            return 0.0;
+        }
+        if let Some(main_stats) = self.main_stats.get(slot)
+            && !main_stats.iter().any(|stat| stat == main_stat)
+        {
+            return 0.0;
+        }
+        sub_stats
+            .iter()
+            .filter(|(_, _, unactivated)| !unactivated)
+            .filter_map(|(name, value, _)| {
+                let weight = self.weights.get(*name)?;
+                let top_roll = STAT_TABLE
+                    .get_sub_stat(name)?
+                    .values
+                    .get(&SCORE_BASE_STARS)?
+                    .last()?;
+                Some(weight * *value as f64 / top_roll)
+            })
+            .sum()
+    }
+}
+
+impl CharacterFile {
+    /// 加载角色权重文件
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 文件路径
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).context("读取角色权重文件失败")?;
+        let mut character_file: CharacterFile =
+            serde_yaml::from_slice(&data).context("解析角色权重文件失败, 请检查格式是否正确")?;
+        let mut names = vec![];
+        for character in character_file.characters.iter_mut() {
+            if names.contains(&character.name) {
+                bail!("角色 '{}' 重复", character.name);
+            }
+            names.push(character.name.clone());
+            character.validate()?;
+        }
+        Ok(character_file)
+    }
+
+    /// 加载与规则文件同目录的角色权重文件, 文件不存在时返回空列表
+    ///
+    /// # 参数
+    ///
+    /// * `rules_file` - 规则文件路径
+    pub fn load_beside(rules_file: &str) -> Result<Self> {
+        let path = Path::new(rules_file).with_file_name(CHARACTER_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load(&path)
+    }
+
+    /// 获取所有角色名称
+    pub fn get_names(&self) -> Vec<String> {
+        self.characters
+            .iter()
+            .map(|character| character.name.clone())
+            .collect()
+    }
+
+    /// 计算圣遗物对每个角色的评分, 顺序与角色列表一致
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - 部位
+    /// * `main_stat` - 主词条名称
+    /// * `set_name` - 套装名称
+    /// * `sub_stats` - 副词条名称、数值和是否待激活
+    pub fn scores(
+        &self,
+        slot: &str,
+        main_stat: &str,
+        set_name: &str,
+        sub_stats: &[(&str, f32, bool)],
+    ) -> Vec<(&str, f64)> {
+        self.characters
+            .iter()
+            .map(|character| {
+                (
+                    character.name.as_str(),
+                    character.score(slot, main_stat, set_name, sub_stats),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(yaml: &str) -> CharacterProfile {
+        let mut profile: CharacterProfile = serde_yaml::from_str(yaml).unwrap();
+        profile.validate().unwrap();
+        profile
+    }
+
+    #[test]
+    fn test_score() {
+        let hu_tao = profile(
+            "
+name: 胡桃
+weights: { 暴击率: 1, 暴击伤害: 1, 生命值百分比: 0.8, 元素精通: 0.75 }
+sets: [追忆之注连, 炽烈的炎之魔女]
+main_stats:
+  时之沙: [生命值百分比, 元素精通]
+",
+        );
+        let sub_stats = [
+            ("暴击率", 7.8, false),
+            ("暴击伤害", 7.77, false),
+            ("元素精通", 23.31, false),
+            ("攻击力", 19.0, false),
+        ];
+        let score = hu_tao.score("时之沙", "元素精通", "炽烈的炎之魔女", &sub_stats);
+        assert!((score - (7.8 / 3.89 + 1.0 + 0.75)).abs() < 1e-6);
+
+        // 套装或主词条不适用
+        assert_eq!(
+            hu_tao.score("时之沙", "攻击力百分比", "炽烈的炎之魔女", &sub_stats),
+            0.0
+        );
+        assert_eq!(
+            hu_tao.score("时之沙", "元素精通", "角斗士的终幕礼", &sub_stats),
+            0.0
+        );
+
+        // 待激活的副词条不计分
+        let sub_stats = [("暴击伤害", 7.77, true)];
+        assert_eq!(
+            hu_tao.score("生之花", "生命值", "追忆之注连", &sub_stats),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut profile: CharacterProfile =
+            serde_yaml::from_str("name: 胡桃\nweights: { 暴击: 1 }").unwrap();
+        assert_eq!(
+            profile.validate().unwrap_err().to_string(),
+            "角色 '胡桃' 的权重属性 '暴击' 不是有效的副词条"
+        );
+
+        for weight in [".inf", "-.inf", ".nan"] {
+            let mut profile: CharacterProfile =
+                serde_yaml::from_str(&format!("name: 胡桃\nweights: {{ 暴击率: {} }}", weight))
+                    .unwrap();
+            assert!(
+                profile
+                    .validate()
+                    .unwrap_err()
+                    .to_string()
+                    .starts_with("角色 '胡桃' 的属性 '暴击率' 权重")
+            );
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// GOOD 格式名称
+pub const GOOD_FORMAT: &str = "GOOD";
+
+/// GOOD 格式版本
+pub const GOOD_VERSION: u32 = 2;
+
+/// mona 格式版本
+pub const MONA_VERSION: &str = "1";
+
+/// 导出格式的键名对照表, 例如 GOOD (Genshin Open Object Description) 和 mona
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct KeyTable {
+    #[schemars(title = "部位名称与键名")]
+    pub slots: HashMap<String, String>,
+    #[schemars(title = "属性名称与键名")]
+    pub stats: HashMap<String, String>,
+    #[schemars(title = "套装名称与键名")]
+    pub sets: HashMap<String, String>,
+}
+
+/// 在对照表中查找键名对应的名称
+///
+/// # 参数
+///
+/// * `map` - 名称与键名对照表
+/// * `key` - 键名
+fn find_name<'a>(map: &'a HashMap<String, String>, key: &str) -> Option<&'a str> {
+    map.iter()
+        .find(|(_, value)| *value == key)
+        .map(|(name, _)| name.as_str())
+}
+
+impl KeyTable {
+    /// 获取部位的键名
+    ///
+    /// # 参数
+    ///
+    /// * `slot` - 部位名称
+    pub fn slot_key(&self, slot: &str) -> Option<&str> {
+        self.slots.get(slot).map(String::as_str)
+    }
+
+    /// 获取属性的键名
+    ///
+    /// # 参数
+    ///
+    /// * `stat` - 属性名称
+    pub fn stat_key(&self, stat: &str) -> Option<&str> {
+        self.stats.get(stat).map(String::as_str)
+    }
+
+    /// 获取套装的键名
+    ///
+    /// # 参数
+    ///
+    /// * `set_name` - 套装名称
+    pub fn set_key(&self, set_name: &str) -> Option<&str> {
+        self.sets.get(set_name).map(String::as_str)
+    }
+
+    /// 获取键名对应的部位名称
+    ///
+    /// # 参数
+    ///
+    /// * `key` - 键名
+    pub fn slot_name(&self, key: &str) -> Option<&str> {
+        find_name(&self.slots, key)
+    }
+
+    /// 获取键名对应的属性名称
+    ///
+    /// # 参数
+    ///
+    /// * `key` - 键名
+    pub fn stat_name(&self, key: &str) -> Option<&str> {
+        find_name(&self.stats, key)
+    }
+
+    /// 获取键名对应的套装名称
+    ///
+    /// # 参数
+    ///
+    /// * `key` - 键名
+    pub fn set_name(&self, key: &str) -> Option<&str> {
+        find_name(&self.sets, key)
+    }
+}
+
+/// 加载键名对照表, 优先使用当前目录下的同名文件
+///
+/// # 参数
+///
+/// * `file_name` - 文件名称
+/// * `default_yaml` - 内置的对照表内容
+fn load_key_table(file_name: &str, default_yaml: &str) -> KeyTable {
+    let key_table_file = Path::new(file_name);
+    let yaml_str: String = if key_table_file.exists() {
+        info!("加载本地 {} 文件", file_name);
+        fs::read_to_string(key_table_file).unwrap()
+    } else {
+        String::from(default_yaml)
+    };
+    serde_yaml::from_str(&yaml_str).unwrap()
+}
+
+lazy_static! {
+    pub static ref GOOD_KEY_TABLE: KeyTable =
+        load_key_table("good_keys.yaml", include_str!("../good_keys.yaml"));
+    pub static ref MONA_KEY_TABLE: KeyTable =
+        load_key_table("mona_keys.yaml", include_str!("../mona_keys.yaml"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ARTIFACT_INFO;
+
+    use super::*;
+
+    #[test]
+    fn test_all_names_have_keys() {
+        for key_table in [&*GOOD_KEY_TABLE, &*MONA_KEY_TABLE] {
+            for slot in ARTIFACT_INFO.slots.iter() {
+                assert!(key_table.slot_key(slot).is_some(), "{}", slot);
+            }
+            for stat in ARTIFACT_INFO.stats.iter() {
+                assert!(key_table.stat_key(stat).is_some(), "{}", stat);
+            }
+            for set in ARTIFACT_INFO.sets.iter() {
+                assert!(key_table.set_key(&set.name).is_some(), "{}", set.name);
+            }
+            assert_eq!(key_table.sets.len(), ARTIFACT_INFO.sets.len());
+        }
+    }
+
+    #[test]
+    fn test_reverse_lookup() {
+        assert_eq!(GOOD_KEY_TABLE.slot_name("sands"), Some("时之沙"));
+        assert_eq!(GOOD_KEY_TABLE.stat_name("critDMG_"), Some("暴击伤害"));
+        assert_eq!(
+            GOOD_KEY_TABLE.set_name("GladiatorsFinale"),
+            Some("角斗士的终幕礼")
+        );
+        assert_eq!(GOOD_KEY_TABLE.set_name("Unknown"), None);
+        assert_eq!(MONA_KEY_TABLE.slot_name("sand"), Some("时之沙"));
+        assert_eq!(
+            MONA_KEY_TABLE.set_name("gladiatorFinale"),
+            Some("角斗士的终幕礼")
+        );
+    }
+}
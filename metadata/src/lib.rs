@@ -1,7 +1,15 @@
 mod artifact_info;
+mod character;
 mod coordinate;
+mod key_table;
+mod potential;
 mod rule;
+mod stat_table;
 
 pub use artifact_info::*;
+pub use character::*;
 pub use coordinate::*;
+pub use key_table::*;
+pub use potential::*;
 pub use rule::*;
+pub use stat_table::*;
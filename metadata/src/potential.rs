@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use crate::StatTable;
+
+/// 评分离散化的倍数, 评分按 0.01 为单位计算
+const SCORE_SCALE: f64 = 100.0;
+
+/// 双爆分的权重, 双爆分 = 暴击率 * 2 + 暴击伤害
+pub const CRIT_SCORE_WEIGHTS: [(&str, f64); 2] = [("暴击率", 2.0), ("暴击伤害", 1.0)];
+
+/// 离散的评分分布, 键为以 0.01 为单位的评分, 值为概率
+type Distribution = HashMap<i64, f64>;
+
+/// 评分转为以 0.01 为单位的整数
+///
+/// # 参数
+///
+/// * `score` - 评分
+fn to_units(score: f64) -> i64 {
+    (score * SCORE_SCALE).round() as i64
+}
+
+/// 两个独立分布之和的分布
+///
+/// # 参数
+///
+/// * `a` - 分布
+/// * `b` - 分布
+fn convolve(a: &Distribution, b: &Distribution) -> Distribution {
+    let mut result = Distribution::new();
+    for (x, p) in a {
+        for (y, q) in b {
+            *result.entry(x + y).or_insert(0.0) += p * q;
+        }
+    }
+    result
+}
+
+/// 将分布按概率 `p` 累加到结果中
+///
+/// # 参数
+///
+/// * `result` - 结果分布
+/// * `dist` - 分布
+/// * `p` - 概率
+fn accumulate(result: &mut Distribution, dist: Distribution, p: f64) {
+    for (score, q) in dist {
+        *result.entry(score).or_insert(0.0) += p * q;
+    }
+}
+
+/// 圣遗物满级时评分的分布
+#[derive(Debug, Clone)]
+pub struct ScoreDistribution {
+    /// 以 0.01 为单位的评分及其概率, 按评分由低到高排列
+    outcomes: Vec<(i64, f64)>,
+}
+
+impl ScoreDistribution {
+    /// 评分的期望
+    pub fn expected(&self) -> f64 {
+        self.outcomes
+            .iter()
+            .map(|(score, p)| *score as f64 / SCORE_SCALE * p)
+            .sum()
+    }
+
+    /// 评分大于等于目标的概率
+    ///
+    /// # 参数
+    ///
+    /// * `target` - 目标评分
+    pub fn probability_at_least(&self, target: f64) -> f64 {
+        let target = to_units(target);
+        self.outcomes
+            .iter()
+            .filter(|(score, _)| *score >= target)
+            .map(|(_, p)| p)
+            .sum::<f64>()
+            .min(1.0)
+    }
+}
+
+/// 计算评分分布时不变的参数
+struct Upgrade<'a> {
+    table: &'a StatTable,
+    stars: u32,
+    main_stat: &'a str,
+    weights: &'a HashMap<String, f64>,
+}
+
+impl Upgrade<'_> {
+    /// 属性强化一次增加的评分的分布, 4 档数值概率相同
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    fn roll(&self, name: &str) -> Option<Distribution> {
+        let weight = self.weights.get(name).copied().unwrap_or(0.0);
+        let values = self.table.get_sub_stat(name)?.values.get(&self.stars)?;
+        let mut result = Distribution::new();
+        for value in values {
+            *result.entry(to_units(weight * value)).or_insert(0.0) += 1.0 / values.len() as f64;
+        }
+        Some(result)
+    }
+
+    /// 完成剩余强化后评分的分布
+    ///
+    /// 副词条不足 4 条时, 先激活待激活的副词条, 再按权重随机新增副词条;
+    /// 副词条满 4 条后每次强化随机选择一条副词条
+    ///
+    /// # 参数
+    ///
+    /// * `lines` - 已生效的副词条名称
+    /// * `pending` - 待激活的副词条名称和数值
+    /// * `score` - 当前评分, 以 0.01 为单位
+    /// * `remaining` - 剩余强化次数
+    fn finish(
+        &self,
+        lines: &[&str],
+        pending: &[(&str, f32)],
+        score: i64,
+        remaining: u32,
+    ) -> Option<Distribution> {
+        if remaining == 0 {
+            return Some(Distribution::from([(score, 1.0)]));
+        }
+
+        if lines.len() < 4 {
+            let mut lines = lines.to_vec();
+            if let Some(((name, value), rest)) = pending.split_first() {
+                let weight = self.weights.get(*name).copied().unwrap_or(0.0);
+                lines.push(name);
+                return self.finish(
+                    &lines,
+                    rest,
+                    score + to_units(weight * *value as f64),
+                    remaining - 1,
+                );
+            }
+
+            let candidates: Vec<_> = self
+                .table
+                .sub_stats
+                .iter()
+                .filter(|sub_stat| sub_stat.name != self.main_stat)
+                .filter(|sub_stat| !lines.contains(&sub_stat.name.as_str()))
+                .collect();
+            let total_weight: u32 = candidates.iter().map(|sub_stat| sub_stat.weight).sum();
+            let mut result = Distribution::new();
+            for candidate in candidates {
+                let p = candidate.weight as f64 / total_weight as f64;
+                let mut next_lines = lines.clone();
+                next_lines.push(&candidate.name);
+                for (increment, q) in self.roll(&candidate.name)? {
+                    let dist = self.finish(&next_lines, &[], score + increment, remaining - 1)?;
+                    accumulate(&mut result, dist, p * q);
+                }
+            }
+            return Some(result);
+        }
+
+        // 每次强化随机选择一条副词条
+        let mut step = Distribution::new();
+        for name in lines {
+            accumulate(&mut step, self.roll(name)?, 1.0 / lines.len() as f64);
+        }
+        let mut result = Distribution::from([(score, 1.0)]);
+        for _ in 0..remaining {
+            result = convolve(&result, &step);
+        }
+        Some(result)
+    }
+}
+
+impl StatTable {
+    /// 计算圣遗物强化到满级时评分的分布, 评分为副词条数值的加权和
+    ///
+    /// 星级或副词条不在数值表中时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `stars` - 星级
+    /// * `level` - 等级
+    /// * `main_stat` - 主词条名称, 新增副词条不会与主词条相同
+    /// * `sub_stats` - 副词条名称、数值和是否待激活
+    /// * `weights` - 各属性的权重, 未列出的属性权重为 0
+    pub fn score_distribution(
+        &self,
+        stars: u32,
+        level: u32,
+        main_stat: &str,
+        sub_stats: &[(&str, f32, bool)],
+        weights: &HashMap<String, f64>,
+    ) -> Option<ScoreDistribution> {
+        let rarity = self.get_rarity(stars)?;
+        // 每 4 级强化一次, 等级不是 4 的倍数时按已经过的强化档位计算
+        let remaining = rarity.max_level / 4 - level.min(rarity.max_level) / 4;
+        let upgrade = Upgrade {
+            table: self,
+            stars,
+            main_stat,
+            weights,
+        };
+
+        let mut lines = vec![];
+        let mut pending = vec![];
+        let mut score = 0;
+        for (name, value, unactivated) in sub_stats {
+            if *unactivated {
+                pending.push((*name, *value));
+            } else {
+                lines.push(*name);
+                score += to_units(weights.get(*name).copied().unwrap_or(0.0) * *value as f64);
+            }
+        }
+
+        let mut outcomes: Vec<(i64, f64)> = upgrade
+            .finish(&lines, &pending, score, remaining)?
+            .into_iter()
+            .collect();
+        outcomes.sort_by_key(|(score, _)| *score);
+        Some(ScoreDistribution { outcomes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::STAT_TABLE;
+
+    use super::*;
+
+    fn crit_weights() -> HashMap<String, f64> {
+        CRIT_SCORE_WEIGHTS
+            .iter()
+            .map(|(name, weight)| (name.to_string(), *weight))
+            .collect()
+    }
+
+    #[test]
+    fn test_score_distribution_full_level() {
+        // 满级圣遗物的评分是确定的
+        let dist = STAT_TABLE
+            .score_distribution(
+                5,
+                20,
+                "生命值",
+                &[
+                    ("暴击率", 10.5, false),
+                    ("暴击伤害", 14.0, false),
+                    ("攻击力", 19.0, false),
+                    ("生命值百分比", 5.8, false),
+                ],
+                &crit_weights(),
+            )
+            .unwrap();
+        assert!((dist.expected() - 35.0).abs() < 1e-9);
+        assert_eq!(dist.probability_at_least(35.0), 1.0);
+        assert_eq!(dist.probability_at_least(35.01), 0.0);
+    }
+
+    #[test]
+    fn test_score_distribution_single_roll() {
+        // 16 级时剩余 1 次强化, 1/4 概率强化暴击率, 平均每档 3.305
+        let dist = STAT_TABLE
+            .score_distribution(
+                5,
+                16,
+                "生命值",
+                &[
+                    ("暴击率", 3.9, false),
+                    ("攻击力", 19.0, false),
+                    ("防御力", 23.0, false),
+                    ("元素精通", 23.0, false),
+                ],
+                &crit_weights(),
+            )
+            .unwrap();
+        assert!((dist.expected() - (7.8 + 0.25 * 2.0 * 3.305)).abs() < 1e-6);
+        // 只有强化到暴击率最高两档时达到 14.8
+        assert!((dist.probability_at_least(14.8) - 0.125).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_score_distribution_partial_level() {
+        let sub_stats = [
+            ("暴击率", 3.9, false),
+            ("攻击力", 19.0, false),
+            ("防御力", 23.0, false),
+            ("元素精通", 23.0, false),
+        ];
+        let expected = |stars: u32, level: u32| {
+            STAT_TABLE
+                .score_distribution(stars, level, "生命值", &sub_stats, &crit_weights())
+                .unwrap()
+                .expected()
+        };
+        // +1 还有 5 次强化, 与 +0 相同
+        assert!((expected(5, 1) - (7.8 + 5.0 * 0.25 * 2.0 * 3.305)).abs() < 1e-6);
+        assert!((expected(5, 1) - expected(5, 0)).abs() < 1e-9);
+        // +17 和 +19 还有 1 次强化, 与 +16 相同
+        for level in [17, 19] {
+            assert!((expected(5, level) - (7.8 + 0.25 * 2.0 * 3.305)).abs() < 1e-6);
+        }
+        assert!(expected(5, 19) > expected(5, 20));
+        // 4 星满级为 16 级, +15 还有 1 次强化
+        assert!((expected(4, 15) - expected(4, 12)).abs() < 1e-9);
+        assert!(expected(4, 15) > expected(4, 16));
+    }
+
+    #[test]
+    fn test_score_distribution_unactivated() {
+        let sub_stats = [
+            ("暴击率", 3.9, false),
+            ("攻击力", 19.0, false),
+            ("防御力", 23.0, false),
+            ("暴击伤害", 7.8, true),
+        ];
+        let dist = STAT_TABLE
+            .score_distribution(5, 0, "生命值", &sub_stats, &crit_weights())
+            .unwrap();
+        // 第一次强化激活暴击伤害, 剩余 4 次强化各有 1/2 概率强化双爆
+        let expected = 7.8 + 7.8 + 4.0 * 0.25 * (2.0 * 3.305 + 6.605);
+        assert!((dist.expected() - expected).abs() < 1e-6);
+
+        // 没有待激活副词条时按权重新增副词条, 双爆分期望更低
+        let dist = STAT_TABLE
+            .score_distribution(5, 0, "生命值", &sub_stats[..3], &crit_weights())
+            .unwrap();
+        assert!(dist.expected() < expected);
+        assert!(dist.probability_at_least(0.0) > 0.999_999);
+    }
+}
@@ -1,7 +1,10 @@
-use std::fs;
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Context;
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow, bail};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -26,6 +29,36 @@ pub enum RuleAction {
     #[schemars(title = "取消锁定和标记")]
     #[serde(rename = "取消锁定和标记")]
     UnLockAndMark,
+    #[schemars(title = "取消标记")]
+    #[serde(rename = "取消标记")]
+    Unmark,
+    #[schemars(title = "仅取消锁定")]
+    #[serde(rename = "仅取消锁定")]
+    UnlockUnmarked,
+    #[schemars(title = "标记")]
+    #[serde(rename = "标记")]
+    Mark,
+    #[schemars(title = "记录")]
+    #[serde(rename = "记录")]
+    Record,
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::ClickLock => "点击锁定",
+            Self::ClickMark => "点击标记",
+            Self::Lock => "锁定",
+            Self::OnlyLock => "仅锁定",
+            Self::LockAndMark => "锁定和标记",
+            Self::UnLockAndMark => "取消锁定和标记",
+            Self::Unmark => "取消标记",
+            Self::UnlockUnmarked => "仅取消锁定",
+            Self::Mark => "标记",
+            Self::Record => "记录",
+        };
+        write!(f, "{}", name)
+    }
 }
 
 /// 规则
@@ -35,20 +68,511 @@ pub struct Rule {
     pub description: String,
     #[schemars(title = "规则表达式")]
     pub expression: String,
-    #[schemars(title = "表达式命中后执行操作")]
-    pub action: RuleAction,
+    #[schemars(title = "表达式命中后执行操作, 只用于组织子规则时可省略")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<RuleAction>,
+    #[schemars(title = "表达式未命中时执行操作, 结果未知时不执行")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub else_action: Option<RuleAction>,
+    #[schemars(title = "记录动作的列表名称, 未设置时使用规则描述")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_list: Option<String>,
+    #[schemars(title = "命中后不再执行后续规则(子规则仍会执行)")]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stop: bool,
+    #[schemars(title = "子规则, 仅在表达式命中后按顺序执行")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+    #[schemars(title = "标签, 用于规则配置 profiles 筛选")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[schemars(title = "规则测试用例, 使用 giaa test-rules 离线检查")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<RuleExample>,
+    /// 规则所在的文件, 加载文件时设置
+    #[serde(skip)]
+    pub source: String,
+}
+
+/// 规则测试用例的预期结果, 至少设置一项
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RuleExpect {
+    #[schemars(title = "是否命中当前规则(按圣遗物初始状态单独求值)")]
+    pub hit: Option<bool>,
+    #[schemars(title = "按顺序执行所有规则后是否锁定")]
+    pub locked: Option<bool>,
+    #[schemars(title = "按顺序执行所有规则后是否标记")]
+    pub marked: Option<bool>,
+}
+
+impl RuleExpect {
+    /// 是否没有设置任何预期结果
+    pub fn is_empty(&self) -> bool {
+        self.hit.is_none() && self.locked.is_none() && self.marked.is_none()
+    }
+}
+
+/// 规则测试用例
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct RuleExample {
+    #[schemars(title = "用例描述")]
+    #[serde(default)]
+    pub description: String,
+    #[schemars(
+        title = "圣遗物信息(字段与 giaa eval 的圣遗物文件一致)",
+        with = "serde_json::Value"
+    )]
+    pub artifact: serde_yaml::Value,
+    #[schemars(title = "预期结果")]
+    pub expect: RuleExpect,
+}
+
+/// 命名定义, 可在规则表达式中像变量一样引用
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct RuleDefine {
+    #[schemars(title = "定义名称")]
+    pub name: String,
+    #[schemars(title = "定义表达式(数字、布尔或字符串表达式)")]
+    pub expression: String,
+}
+
+/// 规则配置, 通过 `--profile` 选择启用的规则
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct RuleProfile {
+    #[schemars(title = "配置名称")]
+    pub name: String,
+    #[schemars(title = "启用带有其中任一标签的规则, 没有标签的规则总是启用")]
+    pub tags: Vec<String>,
+}
+
+/// 规则文件
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RuleFile {
+    #[schemars(
+        title = "引用的其他规则文件, 相对路径相对于当前文件所在目录, 其定义和规则排在当前文件之前"
+    )]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[schemars(title = "命名定义")]
+    #[serde(default)]
+    pub defines: Vec<RuleDefine>,
+    #[schemars(title = "规则配置, 使用 --profile 选择")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<RuleProfile>,
+    #[schemars(title = "规则列表")]
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl RuleFile {
+    /// 将另一个规则文件的内容追加到当前文件之后, 同名配置的标签合并
+    ///
+    /// # 参数
+    ///
+    /// * `other` - 规则文件
+    pub fn merge(&mut self, other: RuleFile) {
+        self.defines.extend(other.defines);
+        self.rules.extend(other.rules);
+        for profile in other.profiles {
+            match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+                Some(existing) => {
+                    for tag in profile.tags {
+                        if !existing.tags.contains(&tag) {
+                            existing.tags.push(tag);
+                        }
+                    }
+                }
+                None => self.profiles.push(profile),
+            }
+        }
+    }
+
+    /// 按配置筛选规则, 只保留没有标签或带有配置中任一标签的规则, 未指定配置时保留所有规则
+    ///
+    /// # 参数
+    ///
+    /// * `names` - 配置名称
+    pub fn select_profiles(&mut self, names: &[String]) -> Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        let mut tags = vec![];
+        for name in names {
+            let Some(profile) = self.profiles.iter().find(|p| p.name == *name) else {
+                let available: Vec<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+                bail!(
+                    "规则配置 '{}' 不存在, 可用的配置: [{}]",
+                    name,
+                    available.join(", ")
+                );
+            };
+            tags.extend(profile.tags.iter().cloned());
+        }
+
+        fn retain(rules: &mut Vec<Rule>, tags: &[String]) {
+            rules.retain(|rule| rule.tags.is_empty() || rule.tags.iter().any(|t| tags.contains(t)));
+            for rule in rules.iter_mut() {
+                retain(&mut rule.rules, tags);
+            }
+        }
+        retain(&mut self.rules, &tags);
+        Ok(())
+    }
+}
+
+/// 规则文件加载器, 处理 `include` 引用
+#[derive(Default)]
+struct RuleLoader {
+    /// 已加载的文件, 被多次引用时只加载一次
+    loaded: HashSet<PathBuf>,
+    /// 正在加载的引用链, 用于检测循环引用
+    stack: Vec<PathBuf>,
+}
+
+impl RuleLoader {
+    /// 加载规则文件, 引用的文件排在当前文件之前
+    ///
+    /// # 参数
+    ///
+    /// * `path` - 规则文件路径
+    fn load(&mut self, path: &Path) -> Result<RuleFile> {
+        let canonical = path
+            .canonicalize()
+            .map_err(|e| anyhow!("读取规则文件 {} 失败: {}", path.display(), e))?;
+        if let Some(start) = self.stack.iter().position(|p| *p == canonical) {
+            let chain: Vec<String> = self.stack[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            bail!("规则文件循环引用: {}", chain.join(" → "));
+        }
+        if !self.loaded.insert(canonical.clone()) {
+            return Ok(RuleFile::default());
+        }
+
+        let rules_data =
+            fs::read(path).map_err(|e| anyhow!("读取规则文件 {} 失败: {}", path.display(), e))?;
+        let mut rule_file = Rule::load_from_slice(&rules_data)
+            .map_err(|e| anyhow!("规则文件 {}: {}", path.display(), e))?;
+        fn set_source(rules: &mut [Rule], source: &str) {
+            for rule in rules.iter_mut() {
+                rule.source = source.to_string();
+                set_source(&mut rule.rules, source);
+            }
+        }
+        set_source(&mut rule_file.rules, &path.display().to_string());
+
+        self.stack.push(canonical);
+        let mut result = RuleFile::default();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for include in std::mem::take(&mut rule_file.include) {
+            result.merge(self.load(&dir.join(include))?);
+        }
+        self.stack.pop();
+        result.merge(rule_file);
+        Ok(result)
+    }
+}
+
+/// 规则文件格式
+///
+/// 可以直接书写规则列表, 也可以书写包含 `defines` 和 `rules` 的完整格式
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RuleFileFormat {
+    Rules(Vec<Rule>),
+    File(RuleFile),
 }
 
 impl Rule {
-    /// 通过文件名加载规则
+    /// 规则及所有子规则的数量
+    pub fn count(&self) -> usize {
+        1 + self.rules.iter().map(Rule::count).sum::<usize>()
+    }
+
+    /// 检查规则及子规则, 每条规则至少需要设置一种操作或子规则
+    fn validate(&self) -> Result<()> {
+        if self.action.is_none() && self.else_action.is_none() && self.rules.is_empty() {
+            bail!(
+                "规则 '{}' 未设置 action、else_action 或子规则 rules",
+                self.description
+            );
+        }
+        let records = [&self.action, &self.else_action]
+            .iter()
+            .any(|action| **action == Some(RuleAction::Record));
+        if self.record_list.is_some() && !records {
+            bail!(
+                "规则 '{}' 设置了 record_list, 但 action 和 else_action 都不是记录",
+                self.description
+            );
+        }
+        self.rules.iter().try_for_each(Rule::validate)
+    }
+
+    /// 记录动作的列表名称, 未设置时使用规则描述
+    pub fn record_list_name(&self) -> &str {
+        self.record_list.as_deref().unwrap_or(&self.description)
+    }
+
+    /// 通过文件名加载规则, 包括 `include` 引用的文件
     ///
     /// # 参数
     ///
     /// * `rules_file` - 规则文件名
-    pub fn load(rules_file: &str) -> Result<Vec<Rule>> {
-        let rules_data = fs::read(&rules_file).context("读取规则文件失败")?;
-        let rules = serde_yaml::from_slice::<Vec<Rule>>(rules_data.as_slice())
-            .context("解析规则文件失败, 请检查格式是否正确")?;
-        Ok(rules)
+    pub fn load(rules_file: &str) -> Result<RuleFile> {
+        Self::load_all(&[rules_file])
+    }
+
+    /// 按顺序加载多个规则文件并合并, 被多个文件引用的文件只加载一次
+    ///
+    /// # 参数
+    ///
+    /// * `rules_files` - 规则文件名
+    pub fn load_all(rules_files: &[impl AsRef<Path>]) -> Result<RuleFile> {
+        let mut loader = RuleLoader::default();
+        let mut result = RuleFile::default();
+        for rules_file in rules_files {
+            result.merge(loader.load(rules_file.as_ref())?);
+        }
+        Ok(result)
+    }
+
+    /// 从文件内容加载规则
+    ///
+    /// # 参数
+    ///
+    /// * `rules_data` - 规则文件内容
+    pub fn load_from_slice(rules_data: &[u8]) -> Result<RuleFile> {
+        let context = "解析规则文件失败, 请检查格式是否正确";
+        let value = serde_yaml::from_slice::<serde_yaml::Value>(rules_data).context(context)?;
+        // 兼容仅包含规则列表的旧格式
+        let rule_file = if value.is_sequence() {
+            RuleFile {
+                rules: serde_yaml::from_value(value).context(context)?,
+                ..Default::default()
+            }
+        } else {
+            serde_yaml::from_value::<RuleFile>(value).context(context)?
+        };
+        rule_file.rules.iter().try_for_each(Rule::validate)?;
+        Ok(rule_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_rule_list() {
+        let yaml = "
+- description: 测试
+  expression: 星级 == 5
+  action: 锁定
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        assert!(rule_file.defines.is_empty());
+        assert_eq!(rule_file.rules.len(), 1);
+        assert_eq!(rule_file.rules[0].action, Some(RuleAction::Lock));
+        assert!(!rule_file.rules[0].stop);
+    }
+
+    #[test]
+    fn test_load_rule_file_with_defines() {
+        let yaml = "
+defines:
+  - name: 双爆分
+    expression: 暴击率 * 2 + 暴击伤害
+rules:
+  - description: 测试
+    expression: 双爆分 >= 30
+    action: 锁定
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        assert_eq!(rule_file.defines.len(), 1);
+        assert_eq!(rule_file.defines[0].name, "双爆分");
+        assert_eq!(rule_file.rules[0].expression, "双爆分 >= 30");
+    }
+
+    #[test]
+    fn test_load_rule_examples() {
+        let yaml = "
+- description: 测试
+  expression: 星级 == 5
+  action: 锁定
+  examples:
+    - description: 5星
+      artifact:
+        stars: 5
+        locked: false
+      expect:
+        hit: true
+        locked: true
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        let examples = &rule_file.rules[0].examples;
+        assert_eq!(examples.len(), 1);
+        assert_eq!(examples[0].description, "5星");
+        assert_eq!(examples[0].artifact["stars"], 5);
+        assert_eq!(examples[0].expect.hit, Some(true));
+        assert_eq!(examples[0].expect.marked, None);
+        assert!(!examples[0].expect.is_empty());
+    }
+
+    #[test]
+    fn test_load_nested_rules() {
+        let yaml = "
+- description: 5星
+  expression: 星级 == 5
+  stop: true
+  rules:
+    - description: 双爆
+      expression: 暴击率 > 0
+      action: 锁定
+      else_action: 取消锁定和标记
+    - description: 分组
+      expression: 等级 == 20
+      rules:
+        - description: 满级
+          expression: 暴击伤害 > 0
+          action: 锁定和标记
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        let rule = &rule_file.rules[0];
+        assert!(rule.stop);
+        assert_eq!(rule.action, None);
+        assert_eq!(rule.count(), 4);
+        assert_eq!(rule.rules[0].else_action, Some(RuleAction::UnLockAndMark));
+
+        let yaml = "
+- description: 空规则
+  expression: 星级 == 5
+";
+        assert_eq!(
+            Rule::load_from_slice(yaml.as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "规则 '空规则' 未设置 action、else_action 或子规则 rules"
+        );
+    }
+
+    #[test]
+    fn test_load_record_actions() {
+        let yaml = "
+- description: 记录高分
+  expression: 星级 == 5
+  action: 记录
+  record_list: 高分候选
+- description: 记录默认列表
+  expression: 星级 == 4
+  action: 标记
+  else_action: 记录
+";
+        let rule_file = Rule::load_from_slice(yaml.as_bytes()).unwrap();
+        assert_eq!(rule_file.rules[0].record_list_name(), "高分候选");
+        assert_eq!(rule_file.rules[1].record_list_name(), "记录默认列表");
+        assert_eq!(rule_file.rules[1].action, Some(RuleAction::Mark));
+
+        let yaml = "
+- description: 多余的列表名称
+  expression: 星级 == 5
+  action: 取消标记
+  record_list: 高分候选
+";
+        assert_eq!(
+            Rule::load_from_slice(yaml.as_bytes())
+                .unwrap_err()
+                .to_string(),
+            "规则 '多余的列表名称' 设置了 record_list, 但 action 和 else_action 都不是记录"
+        );
+    }
+
+    #[test]
+    fn test_load_include_and_profiles() {
+        let dir = std::env::temp_dir().join(format!("giaa_rule_include_{}", std::process::id()));
+        fs::create_dir_all(dir.join("base")).unwrap();
+        let write = |name: &str, content: &str| fs::write(dir.join(name), content).unwrap();
+        write(
+            "base/common.yaml",
+            "
+defines:
+  - name: 双爆分
+    expression: 暴击率 * 2 + 暴击伤害
+profiles:
+  - name: 日常
+    tags: [日常]
+rules:
+  - description: 基础
+    expression: 星级 == 5
+    action: 锁定
+",
+        );
+        write(
+            "base/shared.yaml",
+            "
+include: [common.yaml]
+rules:
+  - description: 共享
+    expression: 双爆分 >= 30
+    action: 仅锁定
+    tags: [日常]
+",
+        );
+        write(
+            "personal.yaml",
+            "
+include: [base/shared.yaml, base/common.yaml]
+profiles:
+  - name: 日常
+    tags: [个人]
+  - name: 清包
+    tags: [清包]
+rules:
+  - description: 个人
+    expression: 双爆分 >= 40
+    action: 锁定和标记
+    tags: [个人]
+  - description: 清包
+    expression: 星级 < 5
+    action: 取消锁定和标记
+    tags: [清包]
+",
+        );
+
+        let personal = dir.join("personal.yaml");
+        let rule_file = Rule::load_all(&[&personal]).unwrap();
+        let descriptions = |rule_file: &RuleFile| -> Vec<String> {
+            rule_file
+                .rules
+                .iter()
+                .map(|rule| rule.description.clone())
+                .collect()
+        };
+        assert_eq!(rule_file.defines.len(), 1);
+        assert_eq!(descriptions(&rule_file), ["基础", "共享", "个人", "清包"]);
+        assert!(rule_file.rules[0].source.ends_with("common.yaml"));
+        assert_eq!(rule_file.profiles[0].tags, ["日常", "个人"]);
+
+        let mut selected = rule_file.clone();
+        selected.select_profiles(&[String::from("日常")]).unwrap();
+        assert_eq!(descriptions(&selected), ["基础", "共享", "个人"]);
+        let mut selected = rule_file.clone();
+        assert_eq!(
+            selected
+                .select_profiles(&[String::from("周末")])
+                .unwrap_err()
+                .to_string(),
+            "规则配置 '周末' 不存在, 可用的配置: [日常, 清包]"
+        );
+
+        write(
+            "base/common.yaml",
+            "include: [../personal.yaml]\nrules: []\n",
+        );
+        let err = Rule::load_all(&[&personal]).unwrap_err().to_string();
+        assert!(err.starts_with("规则文件循环引用: "), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
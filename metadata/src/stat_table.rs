@@ -0,0 +1,598 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use lazy_static::lazy_static;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// 每档数值的误差, 表中数值只保留两位小数
+const TIER_ERROR: f64 = 0.005;
+
+/// OCR 容易混淆的数字
+const CONFUSABLE_DIGITS: [(char, char); 4] = [('1', '7'), ('7', '1'), ('6', '8'), ('8', '6')];
+
+/// 圣遗物星级信息
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct Rarity {
+    #[schemars(title = "星级")]
+    pub stars: u32,
+    #[schemars(title = "最高等级")]
+    pub max_level: u32,
+    #[schemars(title = "可能的初始词条数")]
+    pub initial_sub_stats: Vec<u32>,
+}
+
+/// 副词条每次强化的数值
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct SubStatValues {
+    #[schemars(title = "属性名称")]
+    pub name: String,
+    #[schemars(title = "新增副词条时的权重")]
+    pub weight: u32,
+    #[schemars(title = "显示的小数位数")]
+    pub decimals: u32,
+    #[schemars(title = "各星级每次强化的数值(由低到高 4 档)")]
+    pub values: HashMap<u32, Vec<f64>>,
+}
+
+/// 主词条各等级的数值
+#[derive(JsonSchema, Serialize, Deserialize, Debug, Clone)]
+pub struct MainStatValues {
+    #[schemars(title = "属性名称")]
+    pub name: String,
+    #[schemars(title = "显示的小数位数")]
+    pub decimals: u32,
+    #[schemars(title = "各星级每个等级显示的数值(由等级 0 起)")]
+    pub values: HashMap<u32, Vec<f64>>,
+}
+
+/// 圣遗物属性数值表
+#[derive(JsonSchema, Serialize, Deserialize, Debug)]
+pub struct StatTable {
+    #[schemars(title = "圣遗物星级")]
+    pub rarities: Vec<Rarity>,
+    #[schemars(title = "副词条数值")]
+    pub sub_stats: Vec<SubStatValues>,
+    #[schemars(title = "主词条数值")]
+    pub main_stats: Vec<MainStatValues>,
+    #[schemars(title = "有效副词条")]
+    pub effective_stats: Vec<String>,
+}
+
+/// 副词条的强化次数和档位
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubStatRolls {
+    pub name: String,
+    /// 强化次数, 包含初始词条
+    pub rolls: u32,
+    /// 每次强化的档位, 0 为最低档, 由低到高排列
+    pub tiers: Vec<usize>,
+}
+
+/// 圣遗物副词条强化分析结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollAnalysis {
+    /// 各副词条的强化次数, 顺序与输入一致
+    pub sub_stats: Vec<SubStatRolls>,
+    /// 初始词条数
+    pub initial_sub_stats: u32,
+    /// 升级获得的强化次数
+    pub upgrades: u32,
+    /// 是否有多种强化次数的拆分都符合
+    pub ambiguous: bool,
+}
+
+/// 数值校验结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueCheck {
+    /// 识别的数值有效
+    Valid(f32),
+    /// 识别的数值无效, 修正后只有一个有效数值
+    Corrected(f32),
+    /// 识别的数值无效, 修正后有多个有效数值
+    Ambiguous(Vec<f32>),
+    /// 识别的数值及其修正都无效
+    Invalid,
+}
+
+/// 生成 OCR 数字文本可能的修正, 不包含原文本
+///
+/// 修正方式为替换一个容易混淆的数字 (1 和 7, 6 和 8), 以及在没有小数点时于末尾 `decimals` 位前插入小数点
+///
+/// # 参数
+///
+/// * `digits` - 只包含数字和小数点的文本
+/// * `decimals` - 显示的小数位数
+fn number_corrections(digits: &str, decimals: u32) -> Vec<String> {
+    let chars: Vec<char> = digits.chars().collect();
+    let mut swapped = vec![digits.to_string()];
+    for (i, c) in chars.iter().enumerate() {
+        for (from, to) in CONFUSABLE_DIGITS {
+            if *c == from {
+                let mut candidate = chars.clone();
+                candidate[i] = to;
+                swapped.push(candidate.into_iter().collect());
+            }
+        }
+    }
+
+    let mut result = vec![];
+    for candidate in swapped {
+        let decimals = decimals as usize;
+        if decimals > 0 && !candidate.contains('.') && candidate.len() > decimals {
+            let (integer, fraction) = candidate.split_at(candidate.len() - decimals);
+            result.push(format!("{}.{}", integer, fraction));
+        }
+        result.push(candidate);
+    }
+    result.retain(|candidate| candidate != digits);
+    result
+}
+
+/// 校验 OCR 识别的数字文本, 数值无效时尝试修正
+///
+/// # 参数
+///
+/// * `text` - 识别的文本, 非数字和小数点的字符会被忽略
+/// * `decimals` - 显示的小数位数
+/// * `is_valid` - 判断数值是否有效
+pub fn check_value(text: &str, decimals: u32, is_valid: impl Fn(f32) -> bool) -> ValueCheck {
+    let digits: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    if let Ok(value) = digits.parse::<f32>()
+        && is_valid(value)
+    {
+        return ValueCheck::Valid(value);
+    }
+
+    let mut values: Vec<f32> = vec![];
+    for candidate in number_corrections(&digits, decimals) {
+        if let Ok(value) = candidate.parse::<f32>()
+            && is_valid(value)
+            && !values.contains(&value)
+        {
+            values.push(value);
+        }
+    }
+    match values.len() {
+        0 => ValueCheck::Invalid,
+        1 => ValueCheck::Corrected(values[0]),
+        _ => ValueCheck::Ambiguous(values),
+    }
+}
+
+/// 枚举长度为 `len` 的档位组合, 每个组合由低到高排列
+///
+/// # 参数
+///
+/// * `tiers` - 档位个数
+/// * `len` - 组合长度
+fn tier_combinations(tiers: usize, len: usize) -> Vec<Vec<usize>> {
+    if len == 0 {
+        return vec![vec![]];
+    }
+    let mut result = vec![];
+    for mut combination in tier_combinations(tiers, len - 1) {
+        let start = combination.last().copied().unwrap_or(0);
+        for tier in start..tiers {
+            combination.push(tier);
+            result.push(combination.clone());
+            combination.pop();
+        }
+    }
+    result
+}
+
+impl StatTable {
+    /// 获取星级信息
+    ///
+    /// # 参数
+    ///
+    /// * `stars` - 星级
+    pub fn get_rarity(&self, stars: u32) -> Option<&Rarity> {
+        self.rarities.iter().find(|rarity| rarity.stars == stars)
+    }
+
+    /// 获取副词条数值
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    pub fn get_sub_stat(&self, name: &str) -> Option<&SubStatValues> {
+        self.sub_stats.iter().find(|sub_stat| sub_stat.name == name)
+    }
+
+    /// 获取主词条数值
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    pub fn get_main_stat(&self, name: &str) -> Option<&MainStatValues> {
+        self.main_stats
+            .iter()
+            .find(|main_stat| main_stat.name == name)
+    }
+
+    /// 校验并修正副词条数值, 星级或属性不在数值表中时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    /// * `stars` - 星级
+    /// * `level` - 等级, 未知时按最高等级计算可能的强化次数
+    /// * `text` - 识别的数值文本
+    /// * `unactivated` - 是否待激活
+    pub fn check_sub_stat_value(
+        &self,
+        name: &str,
+        stars: u32,
+        level: Option<u32>,
+        text: &str,
+        unactivated: bool,
+    ) -> Option<ValueCheck> {
+        let rarity = self.get_rarity(stars)?;
+        let sub_stat = self.get_sub_stat(name)?;
+        sub_stat.values.get(&stars)?;
+        let level = level.unwrap_or(rarity.max_level).min(rarity.max_level);
+        let max_rolls = if unactivated { 1 } else { 1 + level / 4 };
+        Some(check_value(text, sub_stat.decimals, |value| {
+            !self
+                .sub_stat_tiers(name, stars, value, max_rolls)
+                .is_empty()
+        }))
+    }
+
+    /// 校验并修正主词条数值, 星级或属性不在数值表中时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    /// * `stars` - 星级
+    /// * `level` - 等级, 未知时任一等级的数值都有效
+    /// * `text` - 识别的数值文本
+    pub fn check_main_stat_value(
+        &self,
+        name: &str,
+        stars: u32,
+        level: Option<u32>,
+        text: &str,
+    ) -> Option<ValueCheck> {
+        let main_stat = self.get_main_stat(name)?;
+        let values = main_stat.values.get(&stars)?;
+        let half_unit = 0.5 / 10f64.powi(main_stat.decimals as i32);
+        let matches = |expected: &f64, value: f32| (expected - value as f64).abs() < half_unit;
+        Some(check_value(text, main_stat.decimals, |value| match level {
+            Some(level) => values
+                .get(level as usize)
+                .is_some_and(|expected| matches(expected, value)),
+            None => values.iter().any(|expected| matches(expected, value)),
+        }))
+    }
+
+    /// 获取主词条数值与显示数值相符的所有等级, 星级或属性不在数值表中时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    /// * `stars` - 星级
+    /// * `value` - 显示的数值
+    pub fn main_stat_levels(&self, name: &str, stars: u32, value: f32) -> Option<Vec<u32>> {
+        let main_stat = self.get_main_stat(name)?;
+        let half_unit = 0.5 / 10f64.powi(main_stat.decimals as i32);
+        let levels = main_stat
+            .values
+            .get(&stars)?
+            .iter()
+            .enumerate()
+            .filter(|(_, expected)| (*expected - value as f64).abs() < half_unit)
+            .map(|(level, _)| level as u32)
+            .collect();
+        Some(levels)
+    }
+
+    /// 获取主词条在指定等级显示的数值
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    /// * `stars` - 星级
+    /// * `level` - 等级
+    pub fn main_stat_value(&self, name: &str, stars: u32, level: u32) -> Option<f32> {
+        let values = self.get_main_stat(name)?.values.get(&stars)?;
+        values.get(level as usize).map(|value| *value as f32)
+    }
+
+    /// 获取与显示数值相符的所有档位组合
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 属性名称
+    /// * `stars` - 星级
+    /// * `value` - 显示的数值
+    /// * `max_rolls` - 最多强化次数
+    pub fn sub_stat_tiers(
+        &self,
+        name: &str,
+        stars: u32,
+        value: f32,
+        max_rolls: u32,
+    ) -> Vec<Vec<usize>> {
+        let Some(sub_stat) = self.get_sub_stat(name) else {
+            return vec![];
+        };
+        let Some(values) = sub_stat.values.get(&stars) else {
+            return vec![];
+        };
+        // 显示数值四舍五入到指定小数位
+        let half_unit = 0.5 / 10f64.powi(sub_stat.decimals as i32);
+        (1..=max_rolls as usize)
+            .flat_map(|len| tier_combinations(values.len(), len))
+            .filter(|tiers| {
+                let sum: f64 = tiers.iter().map(|tier| values[*tier]).sum();
+                (sum - value as f64).abs() <= half_unit + TIER_ERROR * tiers.len() as f64
+            })
+            .collect()
+    }
+
+    /// 推断各副词条的强化次数、档位和初始词条数
+    ///
+    /// 各副词条强化次数之和减去升级获得的强化次数即为初始词条数, 只保留初始词条数合理的拆分,
+    /// 有多种拆分时选择强化次数最少的一种。星级或属性不在数值表中, 或没有合理的拆分时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `stars` - 星级
+    /// * `level` - 等级
+    /// * `sub_stats` - 副词条名称、数值和是否待激活
+    pub fn analyze_rolls(
+        &self,
+        stars: u32,
+        level: u32,
+        sub_stats: &[(&str, f32, bool)],
+    ) -> Option<RollAnalysis> {
+        let rarity = self.get_rarity(stars)?;
+        let upgrades = level.min(rarity.max_level) / 4;
+
+        // 每个副词条可能的强化次数, 及该次数下与显示数值最接近的档位组合
+        let mut candidates: Vec<Vec<(u32, Vec<usize>)>> = vec![];
+        for (name, value, unactivated) in sub_stats {
+            let max_rolls = if *unactivated { 1 } else { 1 + upgrades };
+            let values = &self.get_sub_stat(name)?.values.get(&stars)?;
+            let distance = |tiers: &Vec<usize>| {
+                let sum: f64 = tiers.iter().map(|tier| values[*tier]).sum();
+                (sum - *value as f64).abs()
+            };
+            let mut options: Vec<(u32, Vec<usize>)> = vec![];
+            for tiers in self.sub_stat_tiers(name, stars, *value, max_rolls) {
+                let rolls = tiers.len() as u32;
+                match options.iter_mut().find(|(n, _)| *n == rolls) {
+                    Some(option) if distance(&tiers) < distance(&option.1) => option.1 = tiers,
+                    Some(_) => {}
+                    None => options.push((rolls, tiers)),
+                }
+            }
+            if options.is_empty() {
+                return None;
+            }
+            candidates.push(options);
+        }
+
+        // 枚举各副词条强化次数的组合, 保留与等级和初始词条数一致的组合
+        let active_count = sub_stats.iter().filter(|(_, _, u)| !u).count() as u32;
+        let mut fits: Vec<(u32, Vec<usize>)> = vec![];
+        let mut choice = vec![0; candidates.len()];
+        loop {
+            let total: u32 = choice
+                .iter()
+                .zip(candidates.iter())
+                .zip(sub_stats.iter())
+                .filter(|(_, (_, _, unactivated))| !unactivated)
+                .map(|((i, options), _)| options[*i].0)
+                .sum();
+            if let Some(initial) = total.checked_sub(upgrades)
+                && rarity.initial_sub_stats.contains(&initial)
+                && active_count == (initial + upgrades).min(4)
+            {
+                fits.push((total, choice.clone()));
+            }
+
+            // 下一个组合
+            let Some(i) = (0..choice.len()).find(|i| choice[*i] + 1 < candidates[*i].len()) else {
+                break;
+            };
+            choice[i] += 1;
+            for reset in choice.iter_mut().take(i) {
+                *reset = 0;
+            }
+        }
+
+        let ambiguous = fits.len() > 1;
+        let (total, choice) = fits.into_iter().min()?;
+        Some(RollAnalysis {
+            sub_stats: sub_stats
+                .iter()
+                .zip(candidates.iter())
+                .zip(choice.iter())
+                .map(|(((name, _, _), options), i)| SubStatRolls {
+                    name: name.to_string(),
+                    rolls: options[*i].0,
+                    tiers: options[*i].1.clone(),
+                })
+                .collect(),
+            initial_sub_stats: total - upgrades,
+            upgrades,
+            ambiguous,
+        })
+    }
+}
+
+lazy_static! {
+    pub static ref STAT_TABLE: StatTable = {
+        let stat_table_file = Path::new("stat_table.yaml");
+        let yaml_str: String = if stat_table_file.exists() {
+            info!("加载本地 stat_table.yaml 文件");
+            fs::read_to_string(stat_table_file).unwrap()
+        } else {
+            String::from(include_str!("../stat_table.yaml"))
+        };
+        serde_yaml::from_str(&yaml_str).unwrap()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sub_stat_tiers() {
+        assert_eq!(
+            STAT_TABLE.sub_stat_tiers("暴击率", 5, 3.9, 6),
+            vec![vec![3]]
+        );
+        assert_eq!(
+            STAT_TABLE.sub_stat_tiers("暴击伤害", 5, 14.0, 6),
+            vec![vec![1, 3], vec![2, 2]]
+        );
+        assert!(STAT_TABLE.sub_stat_tiers("暴击率", 5, 39.0, 6).is_empty());
+        assert!(
+            STAT_TABLE
+                .sub_stat_tiers("火元素伤害加成", 5, 7.0, 6)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_check_value() {
+        // 丢失小数点
+        assert_eq!(
+            STAT_TABLE.check_sub_stat_value("暴击率", 5, Some(0), "39%", false),
+            Some(ValueCheck::Corrected(3.9))
+        );
+        assert_eq!(
+            STAT_TABLE.check_sub_stat_value("暴击率", 5, Some(20), "3.9%", false),
+            Some(ValueCheck::Valid(3.9))
+        );
+        // 1 和 7 混淆, 19 超出 0 级攻击力的范围
+        assert_eq!(
+            STAT_TABLE.check_sub_stat_value("攻击力", 5, Some(0), "79", false),
+            Some(ValueCheck::Corrected(19.0))
+        );
+        assert_eq!(
+            STAT_TABLE.check_sub_stat_value("暴击率", 5, Some(0), "99", false),
+            Some(ValueCheck::Invalid)
+        );
+        assert_eq!(
+            STAT_TABLE.check_sub_stat_value("火元素伤害加成", 5, Some(0), "7.0", false),
+            None
+        );
+
+        assert_eq!(
+            STAT_TABLE.check_main_stat_value("攻击力百分比", 5, Some(20), "466%"),
+            Some(ValueCheck::Corrected(46.6))
+        );
+        assert_eq!(
+            STAT_TABLE.check_main_stat_value("生命值", 5, None, "4780"),
+            Some(ValueCheck::Valid(4780.0))
+        );
+        assert_eq!(
+            STAT_TABLE.check_main_stat_value("暴击率", 5, Some(20), "37.1%"),
+            Some(ValueCheck::Corrected(31.1))
+        );
+        assert_eq!(
+            STAT_TABLE.check_main_stat_value("暴击率", 5, Some(20), "30.0%"),
+            Some(ValueCheck::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_main_stat_levels() {
+        assert_eq!(
+            STAT_TABLE.main_stat_levels("攻击力百分比", 5, 46.6),
+            Some(vec![20])
+        );
+        assert_eq!(
+            STAT_TABLE.main_stat_levels("生命值", 4, 645.0),
+            Some(vec![0])
+        );
+        assert_eq!(STAT_TABLE.main_stat_levels("暴击率", 5, 30.5), Some(vec![]));
+        assert_eq!(STAT_TABLE.main_stat_levels("防御力", 5, 10.0), None);
+        assert_eq!(STAT_TABLE.main_stat_value("暴击伤害", 5, 20), Some(62.2));
+        assert_eq!(STAT_TABLE.main_stat_value("暴击伤害", 5, 21), None);
+    }
+
+    #[test]
+    fn test_analyze_rolls() {
+        let analysis = STAT_TABLE
+            .analyze_rolls(
+                5,
+                20,
+                &[
+                    ("暴击率", 10.5, false),
+                    ("暴击伤害", 14.0, false),
+                    ("攻击力", 19.0, false),
+                    ("生命值", 538.0, false),
+                ],
+            )
+            .unwrap();
+        let rolls: Vec<u32> = analysis.sub_stats.iter().map(|s| s.rolls).collect();
+        assert_eq!(rolls, vec![3, 2, 1, 2]);
+        assert_eq!(analysis.initial_sub_stats, 3);
+        assert_eq!(analysis.upgrades, 5);
+        assert!(!analysis.ambiguous);
+
+        // 待激活的副词条不计入初始词条数
+        let analysis = STAT_TABLE
+            .analyze_rolls(
+                5,
+                0,
+                &[
+                    ("暴击率", 3.9, false),
+                    ("暴击伤害", 7.8, false),
+                    ("攻击力", 19.0, false),
+                    ("生命值", 299.0, true),
+                ],
+            )
+            .unwrap();
+        assert_eq!(analysis.initial_sub_stats, 3);
+        assert_eq!(analysis.upgrades, 0);
+
+        // 强化次数之和与等级不符
+        assert!(
+            STAT_TABLE
+                .analyze_rolls(
+                    5,
+                    20,
+                    &[
+                        ("暴击率", 3.9, false),
+                        ("暴击伤害", 7.8, false),
+                        ("攻击力", 19.0, false),
+                        ("生命值", 299.0, false),
+                    ],
+                )
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_analyze_rolls_ambiguous() {
+        // 暴击率和暴击伤害都可能是 3 次或 4 次强化
+        let analysis = STAT_TABLE
+            .analyze_rolls(
+                5,
+                20,
+                &[
+                    ("暴击率", 11.3, false),
+                    ("暴击伤害", 22.5, false),
+                    ("攻击力", 19.0, false),
+                    ("生命值", 299.0, false),
+                ],
+            )
+            .unwrap();
+        let rolls: Vec<u32> = analysis.sub_stats.iter().map(|s| s.rolls).collect();
+        assert_eq!(rolls, vec![3, 3, 1, 1]);
+        assert_eq!(analysis.initial_sub_stats, 3);
+        assert!(analysis.ambiguous);
+    }
+}
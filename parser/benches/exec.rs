@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use parser::{Expr, ExprVar, ExprVarKey, Parser, RecordLayout};
+
+/// 模拟的圣遗物名称个数, 与圣遗物信息中名称、部位和套装名的数量级一致
+const NAME_COUNT: usize = 300;
+
+/// 基准测试使用的规则表达式
+const RULES: [&str; 6] = [
+    "星级 == 5 && 等级 < 20 && 暴击率 > 0 && 暴击伤害 > 0",
+    "星级 == 5 && 理之冠 && (主:暴击率 > 0 || 主:暴击伤害 > 0) && 暴击率 * 2 + 暴击伤害 >= 30",
+    "星级 == 5 && !理之冠 && 暴击率 * 2 + 暴击伤害 >= 40",
+    "空之杯 && 星级 >= 5 && 主:火元素伤害加成 > 0 && max(暴击率 * 2, 暴击伤害) >= 20",
+    "名称_ab || 名称_ac || 名称_ad || 名称_ae || 名称_af",
+    "已锁定 && !已标记 && 副词条个数 == 4 && 攻击力百分比 + 元素精通 / 4 > 20",
+];
+
+/// 变量名称不能包含数字, 用字母为名称编号
+fn name(i: usize) -> String {
+    let letter = |n: usize| (b'a' + n as u8) as char;
+    format!("名称_{}{}", letter(i / 26), letter(i % 26))
+}
+
+fn names() -> Vec<String> {
+    let mut names: Vec<String> = (0..NAME_COUNT).map(name).collect();
+    names.extend(["理之冠", "空之杯", "已锁定", "已标记"].map(String::from));
+    names
+}
+
+fn numbers() -> HashMap<String, f32> {
+    HashMap::from(
+        [
+            ("星级", 5.0),
+            ("等级", 16.0),
+            ("暴击率", 7.8),
+            ("暴击伤害", 14.0),
+            ("主:暴击率", 0.0),
+            ("主:暴击伤害", 62.2),
+            ("主:火元素伤害加成", 0.0),
+            ("副词条个数", 4.0),
+            ("攻击力百分比", 5.8),
+            ("元素精通", 23.0),
+        ]
+        .map(|(name, value)| (String::from(name), value)),
+    )
+}
+
+/// 与旧的执行方式一致: 每个圣遗物构造包含所有名称的变量表, 再按规则筛选所需变量
+fn generate_vars(var_key: &ExprVarKey, numbers: &HashMap<String, f32>) -> ExprVar {
+    let mut boolean_vars = HashMap::new();
+    for name in names() {
+        boolean_vars.insert(name, false);
+    }
+    boolean_vars.insert(String::from("理之冠"), true);
+    boolean_vars.insert(String::from("已锁定"), true);
+    let boolean_vars = boolean_vars
+        .into_iter()
+        .filter(|(name, _)| var_key.boolean_keys.contains(name))
+        .collect();
+    let number_vars = numbers
+        .iter()
+        .filter(|(name, _)| var_key.number_keys.contains(name))
+        .map(|(name, value)| (name.clone(), *value))
+        .collect();
+    ExprVar {
+        boolean_vars,
+        number_vars,
+        ..Default::default()
+    }
+}
+
+fn bench_exec(c: &mut Criterion) {
+    let var_key = ExprVarKey::new(names(), numbers().into_keys().collect());
+    let parser = Parser::new(3, var_key).unwrap();
+    let exprs: Vec<Expr> = RULES
+        .iter()
+        .map(|rule| parser.parse(rule).unwrap())
+        .collect();
+    let numbers = numbers();
+
+    c.bench_function("exec", |b| {
+        b.iter(|| {
+            for expr in exprs.iter() {
+                let expr_var = generate_vars(&expr.get_var_keys(), &numbers);
+                black_box(parser.exec(expr, &expr_var).unwrap());
+            }
+        })
+    });
+
+    let mut all_keys = ExprVarKey::default();
+    for expr in exprs.iter() {
+        let var_key = expr.get_var_keys();
+        all_keys.boolean_keys.extend(var_key.boolean_keys);
+        all_keys.number_keys.extend(var_key.number_keys);
+    }
+    let layout = RecordLayout::new(&all_keys);
+    let programs: Vec<_> = exprs
+        .iter()
+        .map(|expr| parser.compile(expr, &layout).unwrap())
+        .collect();
+
+    c.bench_function("compiled", |b| {
+        b.iter(|| {
+            let record = layout
+                .record(
+                    |name| numbers.get(name).copied(),
+                    |name| Some(name == "理之冠" || name == "已锁定"),
+                    |_| None,
+                )
+                .unwrap();
+            for program in programs.iter() {
+                black_box(program.eval(&record).unwrap());
+            }
+        })
+    });
+}
+
+criterion_group!(benches, bench_exec);
+criterion_main!(benches);
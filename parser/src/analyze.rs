@@ -0,0 +1,602 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use rust_decimal::prelude::*;
+
+use crate::{Expr, ExprResult, ExprVar, ParamType, Parser};
+
+/// 数值区间, 端点为无穷大时表示该侧无界
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub low: f64,
+    pub high: f64,
+    /// 下限是否不包含在区间内
+    pub low_open: bool,
+    /// 上限是否不包含在区间内
+    pub high_open: bool,
+}
+
+impl Interval {
+    /// 创建闭区间
+    ///
+    /// # 参数
+    ///
+    /// * `low` - 下限
+    /// * `high` - 上限
+    pub fn new(low: f64, high: f64) -> Self {
+        Self {
+            low,
+            high,
+            low_open: false,
+            high_open: false,
+        }
+    }
+
+    /// 创建只包含一个值的区间
+    ///
+    /// # 参数
+    ///
+    /// * `value` - 值
+    pub fn point(value: f64) -> Self {
+        Self::new(value, value)
+    }
+
+    /// 创建无界区间
+    pub fn unbounded() -> Self {
+        Self::new(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// 创建大于等于下限的区间
+    ///
+    /// # 参数
+    ///
+    /// * `low` - 下限
+    pub fn at_least(low: f64) -> Self {
+        Self::new(low, f64::INFINITY)
+    }
+
+    /// 区间是否为空
+    pub fn is_empty(&self) -> bool {
+        self.low > self.high || (self.low == self.high && (self.low_open || self.high_open))
+    }
+
+    /// 区间只包含一个值时返回该值
+    fn as_point(&self) -> Option<f64> {
+        (self.low == self.high && !self.is_empty()).then_some(self.low)
+    }
+
+    /// 运算结果的区间, 两侧按比例放宽以抵消浮点误差, 无效的端点视为无界
+    ///
+    /// # 参数
+    ///
+    /// * `low` - 下限
+    /// * `high` - 上限
+    fn closed(low: f64, high: f64) -> Self {
+        let margin = |v: f64| 1e-9 * v.abs();
+        let low = if low.is_nan() { f64::NEG_INFINITY } else { low };
+        let high = if high.is_nan() { f64::INFINITY } else { high };
+        Self::new(low - margin(low), high + margin(high))
+    }
+
+    /// 交集
+    fn intersect(&self, other: &Self) -> Self {
+        let (low, low_open) = match self.low.partial_cmp(&other.low) {
+            Some(Ordering::Greater) => (self.low, self.low_open),
+            Some(Ordering::Less) => (other.low, other.low_open),
+            _ => (self.low, self.low_open || other.low_open),
+        };
+        let (high, high_open) = match self.high.partial_cmp(&other.high) {
+            Some(Ordering::Less) => (self.high, self.high_open),
+            Some(Ordering::Greater) => (other.high, other.high_open),
+            _ => (self.high, self.high_open || other.high_open),
+        };
+        Self {
+            low,
+            high,
+            low_open,
+            high_open,
+        }
+    }
+
+    /// 包含两个区间的最小区间
+    fn hull(&self, other: &Self) -> Self {
+        let (low, low_open) = match self.low.partial_cmp(&other.low) {
+            Some(Ordering::Less) => (self.low, self.low_open),
+            Some(Ordering::Greater) => (other.low, other.low_open),
+            _ => (self.low, self.low_open && other.low_open),
+        };
+        let (high, high_open) = match self.high.partial_cmp(&other.high) {
+            Some(Ordering::Greater) => (self.high, self.high_open),
+            Some(Ordering::Less) => (other.high, other.high_open),
+            _ => (self.high, self.high_open && other.high_open),
+        };
+        Self {
+            low,
+            high,
+            low_open,
+            high_open,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self::closed(self.low + other.low, self.high + other.high)
+    }
+
+    fn sub(&self, other: &Self) -> Self {
+        Self::closed(self.low - other.high, self.high - other.low)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        // 区间运算中约定 0 乘以无穷大为 0
+        let mul = |a: f64, b: f64| if a == 0.0 || b == 0.0 { 0.0 } else { a * b };
+        let products = [
+            mul(self.low, other.low),
+            mul(self.low, other.high),
+            mul(self.high, other.low),
+            mul(self.high, other.high),
+        ];
+        Self::closed(
+            products.iter().copied().fold(f64::INFINITY, f64::min),
+            products.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+
+    fn div(&self, other: &Self) -> Self {
+        if other.low <= 0.0 && other.high >= 0.0 {
+            return Self::unbounded();
+        }
+        self.mul(&Self::new(1.0 / other.high, 1.0 / other.low))
+    }
+
+    fn max(&self, other: &Self) -> Self {
+        Self::closed(self.low.max(other.low), self.high.max(other.high))
+    }
+
+    fn min(&self, other: &Self) -> Self {
+        Self::closed(self.low.min(other.low), self.high.min(other.high))
+    }
+
+    fn abs(&self) -> Self {
+        if self.low >= 0.0 {
+            Self::closed(self.low, self.high)
+        } else if self.high <= 0.0 {
+            Self::closed(-self.high, -self.low)
+        } else {
+            Self::closed(0.0, self.high.max(-self.low))
+        }
+    }
+}
+
+/// 比较运算符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compare {
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+    LessThanEqual,
+    GreaterThanEqual,
+}
+
+impl Compare {
+    /// 拆分比较表达式为运算符、左侧和右侧
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    fn split(expr: &Expr) -> Option<(Self, &Expr, &Expr)> {
+        let (op, left, right) = match expr {
+            Expr::Equal(left, right) => (Self::Equal, left, right),
+            Expr::NotEqual(left, right) => (Self::NotEqual, left, right),
+            Expr::LessThan(left, right) => (Self::LessThan, left, right),
+            Expr::GreaterThan(left, right) => (Self::GreaterThan, left, right),
+            Expr::LessThanEqual(left, right) => (Self::LessThanEqual, left, right),
+            Expr::GreaterThanEqual(left, right) => (Self::GreaterThanEqual, left, right),
+            _ => return None,
+        };
+        Some((op, left, right))
+    }
+
+    /// 取反, `!(a < b)` 等价于 `a >= b`
+    fn negate(self) -> Self {
+        match self {
+            Self::Equal => Self::NotEqual,
+            Self::NotEqual => Self::Equal,
+            Self::LessThan => Self::GreaterThanEqual,
+            Self::GreaterThan => Self::LessThanEqual,
+            Self::LessThanEqual => Self::GreaterThan,
+            Self::GreaterThanEqual => Self::LessThan,
+        }
+    }
+
+    /// 交换两侧, `a < b` 等价于 `b > a`
+    fn swap(self) -> Self {
+        match self {
+            Self::LessThan => Self::GreaterThan,
+            Self::GreaterThan => Self::LessThan,
+            Self::LessThanEqual => Self::GreaterThanEqual,
+            Self::GreaterThanEqual => Self::LessThanEqual,
+            op => op,
+        }
+    }
+
+    /// 比较两个区间, 区间内任意取值的结果都相同时返回该结果
+    ///
+    /// # 参数
+    ///
+    /// * `left` - 左侧区间
+    /// * `right` - 右侧区间
+    fn eval(self, left: &Interval, right: &Interval) -> Option<bool> {
+        let less_than = |a: &Interval, b: &Interval| {
+            if a.high < b.low || (a.high == b.low && (a.high_open || b.low_open)) {
+                Some(true)
+            } else if a.low >= b.high {
+                Some(false)
+            } else {
+                None
+            }
+        };
+        let less_than_equal = |a: &Interval, b: &Interval| {
+            if a.high <= b.low {
+                Some(true)
+            } else if a.low > b.high || (a.low == b.high && (a.low_open || b.high_open)) {
+                Some(false)
+            } else {
+                None
+            }
+        };
+        match self {
+            Self::Equal => match (left.as_point(), right.as_point()) {
+                (Some(l), Some(r)) if l == r => Some(true),
+                _ if left.intersect(right).is_empty() => Some(false),
+                _ => None,
+            },
+            Self::NotEqual => Self::Equal.eval(left, right).map(|b| !b),
+            Self::LessThan => less_than(left, right),
+            Self::GreaterThan => less_than(right, left),
+            Self::LessThanEqual => less_than_equal(left, right),
+            Self::GreaterThanEqual => less_than_equal(right, left),
+        }
+    }
+
+    /// 满足 `x op y` 的 `x` 的区间, 其中 `y` 为区间内的任意值
+    ///
+    /// # 参数
+    ///
+    /// * `other` - 右侧区间
+    fn constrain(self, other: &Interval) -> Interval {
+        let mut result = Interval::unbounded();
+        match self {
+            Self::Equal => result = *other,
+            Self::NotEqual => {}
+            Self::LessThan | Self::LessThanEqual => {
+                result.high = other.high;
+                result.high_open = self == Self::LessThan || other.high_open;
+            }
+            Self::GreaterThan | Self::GreaterThanEqual => {
+                result.low = other.low;
+                result.low_open = self == Self::GreaterThan || other.low_open;
+            }
+        }
+        result
+    }
+}
+
+/// 数字变量在当前分支中收窄后的取值范围
+type Env = HashMap<String, Interval>;
+
+/// 表达式静态分析器
+struct Analyzer<'a> {
+    parser: &'a Parser,
+    bounds: &'a dyn Fn(&str) -> Interval,
+}
+
+impl Analyzer<'_> {
+    /// 不含变量的子表达式直接求值
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    fn constant(&self, expr: &Expr) -> Option<ExprResult> {
+        let var_key = expr.get_var_keys();
+        if !var_key.boolean_keys.is_empty()
+            || !var_key.number_keys.is_empty()
+            || !var_key.string_keys.is_empty()
+        {
+            return None;
+        }
+        match self.parser.exec(expr, &ExprVar::default()) {
+            Ok(ExprResult::Unknown) | Err(_) => None,
+            Ok(result) => Some(result),
+        }
+    }
+
+    /// 数字表达式的取值区间, 不是数字表达式时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `env` - 收窄后的变量范围
+    fn interval(&self, expr: &Expr, env: &Env) -> Option<Interval> {
+        let binary = |left: &Expr, right: &Expr| -> Option<(Interval, Interval)> {
+            Some((self.interval(left, env)?, self.interval(right, env)?))
+        };
+        Some(match expr {
+            Expr::Number(n) => Interval::point(n.to_f64()?),
+            Expr::NumberVariable(name) => env
+                .get(name)
+                .copied()
+                .unwrap_or_else(|| (self.bounds)(name)),
+            Expr::Plus(left, right) => binary(left, right).map(|(l, r)| l.add(&r))?,
+            Expr::Minus(left, right) => binary(left, right).map(|(l, r)| l.sub(&r))?,
+            Expr::Times(left, right) => binary(left, right).map(|(l, r)| l.mul(&r))?,
+            Expr::Divide(left, right) => binary(left, right).map(|(l, r)| l.div(&r))?,
+            Expr::Call(name, args) => return self.call_interval(name, args, env),
+            _ => return None,
+        })
+    }
+
+    /// 函数调用的取值区间
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 函数名称
+    /// * `args` - 参数
+    /// * `env` - 收窄后的变量范围
+    fn call_interval(&self, name: &str, args: &[Expr], env: &Env) -> Option<Interval> {
+        let function = self.parser.functions.get(name)?;
+        if function.ret == ParamType::Generic {
+            let then = self.interval(&args[1], env);
+            let otherwise = self.interval(&args[2], env);
+            return match self.truth(&args[0], env) {
+                Some(true) => then,
+                Some(false) => otherwise,
+                None => Some(then?.hull(&otherwise?)),
+            };
+        }
+        if function.ret != ParamType::Number {
+            return None;
+        }
+        let values = args
+            .iter()
+            .map(|arg| self.interval(arg, env))
+            .collect::<Option<Vec<_>>>()?;
+        let first = values[0];
+        Some(match name {
+            "max" => values[1..].iter().fold(first, |a, b| a.max(b)),
+            "min" => values[1..].iter().fold(first, |a, b| a.min(b)),
+            "abs" => first.abs(),
+            "clamp" => first.max(&values[1]).min(&values[2]),
+            // 取整后与原值相差不超过 1
+            "round" | "floor" | "ceil" => first.add(&Interval::new(-1.0, 1.0)),
+            _ => Interval::unbounded(),
+        })
+    }
+
+    /// 布尔表达式恒为真或恒为假时返回结果
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `env` - 收窄后的变量范围
+    fn truth(&self, expr: &Expr, env: &Env) -> Option<bool> {
+        match expr {
+            Expr::Boolean(b) => Some(*b),
+            Expr::BooleanVariable(_) => None,
+            Expr::Not(expr) => self.truth(expr, env).map(|b| !b),
+            // 右侧只需考虑左侧成立的情况
+            Expr::And(left, right) => {
+                let Some(left_env) = self.refine(left, env, true) else {
+                    return Some(false);
+                };
+                match (self.truth(left, env), self.truth(right, &left_env)) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            // 右侧只需考虑左侧不成立的情况
+            Expr::Or(left, right) => {
+                let Some(left_env) = self.refine(left, env, false) else {
+                    return Some(true);
+                };
+                match (self.truth(left, env), self.truth(right, &left_env)) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }
+            }
+            _ => {
+                if let Some(ExprResult::Boolean(b)) = self.constant(expr) {
+                    return Some(b);
+                }
+                match expr {
+                    Expr::In(left, list) => self.member(left, list),
+                    Expr::Call(name, args) if name == "if" => match self.truth(&args[0], env)? {
+                        true => self.truth(&args[1], env),
+                        false => self.truth(&args[2], env),
+                    },
+                    _ => {
+                        let (op, left, right) = Compare::split(expr)?;
+                        op.eval(&self.interval(left, env)?, &self.interval(right, env)?)
+                    }
+                }
+            }
+        }
+    }
+
+    /// 字符串变量的所有取值都在 (或都不在) 列表中时返回结果
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 成员运算的左侧
+    /// * `list` - 列表
+    fn member(&self, expr: &Expr, list: &[String]) -> Option<bool> {
+        let Expr::StringVariable(name) = expr else {
+            return None;
+        };
+        let values = self.parser.string_values.get(name)?;
+        if values.iter().all(|value| list.contains(value)) {
+            Some(true)
+        } else if values.iter().all(|value| !list.contains(value)) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// 假设表达式的结果为 `assume`, 收窄数字变量的范围, 假设不可能成立时返回 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `env` - 收窄前的变量范围
+    /// * `assume` - 假设的结果
+    fn refine(&self, expr: &Expr, env: &Env, assume: bool) -> Option<Env> {
+        match expr {
+            Expr::Not(expr) => self.refine(expr, env, !assume),
+            Expr::And(left, right) if assume => {
+                let env = self.refine(left, env, true)?;
+                self.refine(right, &env, true)
+            }
+            Expr::Or(left, right) if !assume => {
+                let env = self.refine(left, env, false)?;
+                self.refine(right, &env, false)
+            }
+            // 两侧之一满足假设即可, 取两个分支的并集
+            Expr::And(left, right) | Expr::Or(left, right) => union(
+                self.refine(left, env, assume),
+                self.refine(right, env, assume),
+            ),
+            _ => {
+                if self.truth(expr, env) == Some(!assume) {
+                    return None;
+                }
+                let Some((op, left, right)) = Compare::split(expr) else {
+                    return Some(env.clone());
+                };
+                let op = if assume { op } else { op.negate() };
+                let mut env = env.clone();
+                for (op, var, other) in [(op, left, right), (op.swap(), right, left)] {
+                    let Expr::NumberVariable(name) = var else {
+                        continue;
+                    };
+                    let (Some(current), Some(other)) =
+                        (self.interval(var, &env), self.interval(other, &env))
+                    else {
+                        continue;
+                    };
+                    let refined = current.intersect(&op.constrain(&other));
+                    if refined.is_empty() {
+                        return None;
+                    }
+                    env.insert(name.clone(), refined);
+                }
+                Some(env)
+            }
+        }
+    }
+}
+
+/// 合并两个分支的变量范围, 分支不可能成立时为 `None`
+///
+/// # 参数
+///
+/// * `left` - 左侧分支
+/// * `right` - 右侧分支
+fn union(left: Option<Env>, right: Option<Env>) -> Option<Env> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(
+            left.iter()
+                .filter_map(|(name, l)| right.get(name).map(|r| (name.clone(), l.hull(r))))
+                .collect(),
+        ),
+        (env, None) | (None, env) => env,
+    }
+}
+
+impl Parser {
+    /// 静态分析布尔表达式, 恒为真或恒为假时返回该结果, 无法确定时返回 `None`
+    ///
+    /// 不含变量的子表达式直接求值, 数字变量按取值范围做区间分析,
+    /// `&&` 和 `||` 的右侧按左侧成立 (或不成立) 收窄变量范围, 例如 `星级 == 5 && 星级 < 4` 恒为假.
+    /// 分析时假设所有变量的值都已知
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `bounds` - 数字变量的取值范围, 参数为变量名称
+    pub fn analyze(&self, expr: &Expr, bounds: &dyn Fn(&str) -> Interval) -> Option<bool> {
+        let analyzer = Analyzer {
+            parser: self,
+            bounds,
+        };
+        analyzer.truth(expr, &Env::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ExprVarKey;
+
+    use super::*;
+
+    fn parser() -> Parser {
+        let var_key = ExprVarKey::new(
+            vec![String::from("已锁定")],
+            vec![
+                String::from("暴击率"),
+                String::from("暴击伤害"),
+                String::from("星级"),
+            ],
+        );
+        let mut parser = Parser::new(2, var_key).unwrap();
+        parser.set_string_vars(HashMap::from([(
+            String::from("部位"),
+            vec![String::from("生之花"), String::from("死之羽")],
+        )]));
+        parser
+    }
+
+    fn analyze(input: &str) -> Option<bool> {
+        let parser = parser();
+        let expr = parser.parse(input).unwrap();
+        parser.analyze(&expr, &|name| match name {
+            "星级" => Interval::new(1.0, 5.0),
+            _ => Interval::at_least(0.0),
+        })
+    }
+
+    #[test]
+    fn test_analyze_constant() {
+        assert_eq!(analyze("1 + 2 == 3"), Some(true));
+        assert_eq!(analyze("max(1, 2) < 2 && 暴击率 > 0"), Some(false));
+        assert_eq!(analyze("部位 in [生之花, 死之羽]"), Some(true));
+        assert_eq!(analyze("已锁定 || true"), Some(true));
+        assert_eq!(analyze("已锁定"), None);
+    }
+
+    #[test]
+    fn test_analyze_interval() {
+        assert_eq!(analyze("星级 >= 1"), Some(true));
+        assert_eq!(analyze("星级 > 5"), Some(false));
+        assert_eq!(analyze("暴击率 * 2 + 暴击伤害 >= 0"), Some(true));
+        assert_eq!(analyze("暴击率 * 2 + 暴击伤害 < 0"), Some(false));
+        assert_eq!(analyze("暴击率 > 10"), None);
+        assert_eq!(analyze("if(已锁定, 1, 2) <= 2"), Some(true));
+    }
+
+    #[test]
+    fn test_analyze_refine() {
+        assert_eq!(analyze("星级 == 5 && 星级 < 4"), Some(false));
+        assert_eq!(analyze("星级 > 3 && !(星级 > 2)"), Some(false));
+        assert_eq!(analyze("暴击率 > 10 && 暴击率 <= 10"), Some(false));
+        assert_eq!(analyze("暴击率 >= 10 && 暴击率 <= 10"), None);
+        assert_eq!(analyze("星级 < 4 || 星级 >= 4"), Some(true));
+        assert_eq!(analyze("(星级 == 4 || 星级 == 5) && 星级 < 4"), Some(false));
+        assert_eq!(analyze("星级 == 5 && 暴击率 > 星级"), None);
+        assert_eq!(
+            analyze("星级 == 5 && 暴击率 > 星级 && 暴击率 < 5"),
+            Some(false)
+        );
+    }
+}
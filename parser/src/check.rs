@@ -0,0 +1,307 @@
+use anyhow::{Error, Result, anyhow};
+
+use crate::{Expr, ExprSpan, ExprType, ParamType, Parser};
+
+/// 计算字节位置所在的行号和列号, 均从 1 开始
+///
+/// # 参数
+///
+/// * `input` - 原始字符串
+/// * `offset` - 字节位置
+fn line_column(input: &str, offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// 构造带位置信息的错误
+///
+/// # 参数
+///
+/// * `input` - 原始字符串
+/// * `span` - 出错的子表达式位置
+/// * `message` - 错误信息
+fn span_error(input: &str, span: &ExprSpan, message: String) -> Error {
+    let (line, column) = line_column(input, span.start);
+    anyhow!(
+        "第 {} 行第 {} 列 '{}': {}",
+        line,
+        column,
+        &input[span.start..span.end],
+        message
+    )
+}
+
+impl Parser {
+    /// 获取变量类型
+    ///
+    /// 变量同时属于多种类型时(例如副词条名称), 或变量不存在时, 以语法上下文的类型为准
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称
+    /// * `context` - 语法上下文类型
+    fn var_type(&self, name: &str, context: ExprType) -> ExprType {
+        let types: Vec<ExprType> = [ExprType::Number, ExprType::Boolean, ExprType::String]
+            .into_iter()
+            .filter(|expr_type| self.var_names(*expr_type).any(|key| key == name))
+            .collect();
+        match types[..] {
+            [expr_type] => expr_type,
+            _ => context,
+        }
+    }
+
+    /// 按变量的实际类型修正变量节点
+    ///
+    /// 语法按上下文区分变量, 例如 `战狂 == true` 中的 `战狂` 会被解析为数字变量, 需在类型检查后修正
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    pub(crate) fn resolve_var_types(&self, expr: &Expr) -> Expr {
+        expr.replace(&|expr| {
+            let (name, context) = match expr {
+                Expr::NumberVariable(name) => (name, ExprType::Number),
+                Expr::BooleanVariable(name) => (name, ExprType::Boolean),
+                Expr::StringVariable(name) => (name, ExprType::String),
+                _ => return None,
+            };
+            let name = name.clone();
+            Some(match self.var_type(&name, context) {
+                ExprType::Number => Expr::NumberVariable(name),
+                ExprType::Boolean => Expr::BooleanVariable(name),
+                ExprType::String => Expr::StringVariable(name),
+            })
+        })
+    }
+
+    /// 检查表达式类型, 并返回表达式结果类型
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `span` - 表达式位置
+    /// * `input` - 原始表达式字符串
+    pub fn check_types(&self, expr: &Expr, span: &ExprSpan, input: &str) -> Result<ExprType> {
+        // 检查第 index 个操作数的类型
+        let operand = |index: usize, expr: &Expr, op: &str, expected: ExprType| -> Result<()> {
+            let child = &span.children[index];
+            let actual = self.check_types(expr, child, input)?;
+            if actual != expected {
+                let mut message = format!(
+                    "运算符 '{}' 需要{}类型的操作数, 实际为{}类型",
+                    op, expected, actual
+                );
+                // 变量名称属于另一种类型是常见错误, 例如把数字类型的主词条当作布尔变量使用
+                if let Expr::NumberVariable(name)
+                | Expr::BooleanVariable(name)
+                | Expr::StringVariable(name) = expr
+                {
+                    message.push_str(&format!(", '{}' 是{}变量", name, actual));
+                }
+                return Err(span_error(input, child, message));
+            }
+            Ok(())
+        };
+        let arithmetic = |op: &str, left: &Expr, right: &Expr| -> Result<ExprType> {
+            operand(0, left, op, ExprType::Number)?;
+            operand(1, right, op, ExprType::Number)?;
+            Ok(ExprType::Number)
+        };
+        let logical = |op: &str, left: &Expr, right: &Expr| -> Result<ExprType> {
+            operand(0, left, op, ExprType::Boolean)?;
+            operand(1, right, op, ExprType::Boolean)?;
+            Ok(ExprType::Boolean)
+        };
+        let comparison = |op: &str, left: &Expr, right: &Expr| -> Result<ExprType> {
+            operand(0, left, op, ExprType::Number)?;
+            operand(1, right, op, ExprType::Number)?;
+            Ok(ExprType::Boolean)
+        };
+        let equality = |op: &str, left: &Expr, right: &Expr| -> Result<ExprType> {
+            let left_type = self.check_types(left, &span.children[0], input)?;
+            let right_type = self.check_types(right, &span.children[1], input)?;
+            if left_type != right_type {
+                return Err(span_error(
+                    input,
+                    span,
+                    format!(
+                        "运算符 '{}' 两侧类型不一致, 左侧为{}类型, 右侧为{}类型",
+                        op, left_type, right_type
+                    ),
+                ));
+            }
+            Ok(ExprType::Boolean)
+        };
+
+        match expr {
+            Expr::Number(_) => Ok(ExprType::Number),
+            Expr::Boolean(_) => Ok(ExprType::Boolean),
+            Expr::String(_) => Ok(ExprType::String),
+            Expr::NumberVariable(name) => Ok(self.var_type(name, ExprType::Number)),
+            Expr::BooleanVariable(name) => Ok(self.var_type(name, ExprType::Boolean)),
+            Expr::StringVariable(name) => Ok(self.var_type(name, ExprType::String)),
+            Expr::Plus(left, right) => arithmetic("+", left, right),
+            Expr::Minus(left, right) => arithmetic("-", left, right),
+            Expr::Times(left, right) => arithmetic("*", left, right),
+            Expr::Divide(left, right) => arithmetic("/", left, right),
+            Expr::And(left, right) => logical("&&", left, right),
+            Expr::Or(left, right) => logical("||", left, right),
+            Expr::Not(expr) => {
+                operand(0, expr, "!", ExprType::Boolean)?;
+                Ok(ExprType::Boolean)
+            }
+            Expr::LessThan(left, right) => comparison("<", left, right),
+            Expr::GreaterThan(left, right) => comparison(">", left, right),
+            Expr::LessThanEqual(left, right) => comparison("<=", left, right),
+            Expr::GreaterThanEqual(left, right) => comparison(">=", left, right),
+            Expr::Equal(left, right) => equality("==", left, right),
+            Expr::NotEqual(left, right) => equality("!=", left, right),
+            Expr::Call(name, args) => self.check_call_types(name, args, span, input),
+            Expr::In(expr, _) => {
+                operand(0, expr, "in", ExprType::String)?;
+                Ok(ExprType::Boolean)
+            }
+        }
+    }
+
+    /// 检查函数调用的参数个数和类型, 并返回函数结果类型
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 函数名称
+    /// * `args` - 函数参数
+    /// * `span` - 函数调用位置
+    /// * `input` - 原始表达式字符串
+    fn check_call_types(
+        &self,
+        name: &str,
+        args: &[Expr],
+        span: &ExprSpan,
+        input: &str,
+    ) -> Result<ExprType> {
+        let function = self
+            .get_function(name)
+            .map_err(|e| span_error(input, span, e.to_string()))?;
+        if !function.check_arity(args.len()) {
+            return Err(span_error(
+                input,
+                span,
+                format!(
+                    "函数 '{}' 需要{} {} 个参数, 实际为 {} 个",
+                    name,
+                    if function.variadic { "至少" } else { "" },
+                    function.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        let mut generic_type = None;
+        for (index, (arg, arg_span)) in args.iter().zip(span.children.iter()).enumerate() {
+            let arg_type = self.check_types(arg, arg_span, input)?;
+            let expected = match function.param_type(index) {
+                ParamType::Number => ExprType::Number,
+                ParamType::Boolean => ExprType::Boolean,
+                ParamType::String => ExprType::String,
+                ParamType::Generic => *generic_type.get_or_insert(arg_type),
+            };
+            if arg_type != expected {
+                return Err(span_error(
+                    input,
+                    arg_span,
+                    format!(
+                        "函数 '{}' 第 {} 个参数应为{}类型, 实际为{}类型",
+                        name,
+                        index + 1,
+                        expected,
+                        arg_type
+                    ),
+                ));
+            }
+        }
+
+        Ok(match function.ret {
+            ParamType::Number => ExprType::Number,
+            ParamType::Boolean => ExprType::Boolean,
+            ParamType::String => ExprType::String,
+            ParamType::Generic => generic_type.unwrap_or(ExprType::Number),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ExprVarKey;
+
+    use super::*;
+
+    fn parser() -> Parser {
+        let var_key = ExprVarKey::new(
+            vec![String::from("暴击率"), String::from("战狂")],
+            vec![
+                String::from("暴击率"),
+                String::from("星级"),
+                String::from("生命值"),
+            ],
+        );
+        Parser::new(2, var_key).unwrap()
+    }
+
+    #[test]
+    fn test_check_types_operand() {
+        let parser = parser();
+
+        let err = parser.parse("暴击率 && 星级").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 8 列 '星级': 运算符 '&&' 需要布尔类型的操作数, 实际为数字类型, '星级' 是数字变量"
+        );
+
+        let err = parser
+            .parse("星级 == 5\n&& (生命值 > 3) + 1 > 2")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 2 行第 4 列 '(生命值 > 3)': 运算符 '+' 需要数字类型的操作数, 实际为布尔类型"
+        );
+
+        let err = parser.parse("战狂 == 星级").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 1 列 '战狂 == 星级': 运算符 '==' 两侧类型不一致, 左侧为布尔类型, 右侧为数字类型"
+        );
+    }
+
+    #[test]
+    fn test_check_types_call() {
+        let parser = parser();
+
+        let err = parser.parse("if(战狂, 1, true)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 11 列 'true': 函数 'if' 第 3 个参数应为数字类型, 实际为布尔类型"
+        );
+
+        let err = parser.parse("星级 > 4 && abs(1, 2) > 0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 11 列 'abs(1, 2)': 函数 'abs' 需要 1 个参数, 实际为 2 个"
+        );
+    }
+
+    #[test]
+    fn test_check_types_result() {
+        let parser = parser();
+
+        let err = parser.parse("暴击率 * 2 + 星级").unwrap_err();
+        assert_eq!(err.to_string(), "表达式结果应为布尔类型, 实际为数字类型");
+
+        parser
+            .parse("暴击率 && 暴击率 > 3 && (战狂 == true)")
+            .unwrap();
+    }
+}
@@ -0,0 +1,545 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use anyhow::{Result, anyhow, bail};
+use rust_decimal::prelude::*;
+
+use crate::{Expr, ExprVarKey, FunctionImpl, Parser};
+
+/// 编译后的求值函数, `None` 表示结果未知
+type Eval<T> = Box<dyn Fn(&Record) -> Result<Option<T>> + Send + Sync>;
+
+/// 编译后的字符串求值函数, 变量值直接借用记录中的字符串
+type StrEval = Box<dyn for<'a> Fn(&'a Record) -> Result<Option<Cow<'a, str>>> + Send + Sync>;
+
+/// 编译后的表达式节点
+enum Compiled {
+    Number(Eval<Decimal>),
+    Boolean(Eval<bool>),
+    String(StrEval),
+}
+
+/// 记录布局, 将变量名称映射为记录中的槽位
+///
+/// 槽位即变量名称在对应列表中的下标
+#[derive(Debug, Clone, Default)]
+pub struct RecordLayout {
+    pub number_keys: Vec<String>,
+    pub boolean_keys: Vec<String>,
+    pub string_keys: Vec<String>,
+}
+
+/// 扁平记录, 按 `RecordLayout` 的槽位存放变量值, `None` 表示变量值未知
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    pub numbers: Vec<Option<Decimal>>,
+    pub booleans: Vec<Option<bool>>,
+    pub strings: Vec<Option<String>>,
+}
+
+/// 获取名称在列表中的槽位
+///
+/// # 参数
+///
+/// * `keys` - 名称列表
+/// * `name` - 名称
+fn slot(keys: &[String], name: &str) -> Option<usize> {
+    keys.iter().position(|key| key == name)
+}
+
+impl RecordLayout {
+    /// 根据表达式变量键创建记录布局, 重复的名称只占用一个槽位
+    ///
+    /// # 参数
+    ///
+    /// * `var_key` - 表达式变量键, 通常为所有规则表达式变量键的合集
+    pub fn new(var_key: &ExprVarKey) -> Self {
+        let dedup = |keys: &[String]| {
+            let mut result: Vec<String> = vec![];
+            for key in keys {
+                if !result.contains(key) {
+                    result.push(key.clone());
+                }
+            }
+            result
+        };
+        Self {
+            number_keys: dedup(&var_key.number_keys),
+            boolean_keys: dedup(&var_key.boolean_keys),
+            string_keys: dedup(&var_key.string_keys),
+        }
+    }
+
+    /// 按槽位依次获取变量值, 生成记录
+    ///
+    /// 获取函数返回 `None` 表示变量值未知
+    ///
+    /// # 参数
+    ///
+    /// * `number` - 获取数字变量值
+    /// * `boolean` - 获取布尔变量值
+    /// * `string` - 获取字符串变量值
+    pub fn record(
+        &self,
+        number: impl Fn(&str) -> Option<f32>,
+        boolean: impl Fn(&str) -> Option<bool>,
+        string: impl Fn(&str) -> Option<String>,
+    ) -> Result<Record> {
+        let numbers = self
+            .number_keys
+            .iter()
+            .map(|name| match number(name) {
+                Some(n) => Decimal::from_f32(n)
+                    .map(Some)
+                    .ok_or_else(|| anyhow!("数字变量 '{}' 的值无效: {}", name, n)),
+                None => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let booleans = self.boolean_keys.iter().map(|name| boolean(name)).collect();
+        let strings = self.string_keys.iter().map(|name| string(name)).collect();
+        Ok(Record {
+            numbers,
+            booleans,
+            strings,
+        })
+    }
+}
+
+/// 编译后的布尔表达式
+pub struct Program {
+    eval: Eval<bool>,
+}
+
+impl Program {
+    /// 执行编译后的表达式, 返回 `None` 表示结果未知
+    ///
+    /// # 参数
+    ///
+    /// * `record` - 按编译时布局生成的记录
+    pub fn eval(&self, record: &Record) -> Result<Option<bool>> {
+        (self.eval)(record)
+    }
+}
+
+/// 取出数字节点
+fn number(compiled: Compiled, op: &str) -> Result<Eval<Decimal>> {
+    match compiled {
+        Compiled::Number(eval) => Ok(eval),
+        _ => Err(anyhow!("无效的操作数类型: {}", op)),
+    }
+}
+
+/// 取出布尔节点
+fn boolean(compiled: Compiled, op: &str) -> Result<Eval<bool>> {
+    match compiled {
+        Compiled::Boolean(eval) => Ok(eval),
+        _ => Err(anyhow!("无效的操作数类型: {}", op)),
+    }
+}
+
+/// 取出字符串节点
+fn string(compiled: Compiled, op: &str) -> Result<StrEval> {
+    match compiled {
+        Compiled::String(eval) => Ok(eval),
+        _ => Err(anyhow!("无效的操作数类型: {}", op)),
+    }
+}
+
+/// 对两个操作数求值, 任一操作数未知时返回 `None`
+macro_rules! both {
+    ($l:expr, $r:expr, $record:expr) => {
+        match ($l($record)?, $r($record)?) {
+            (Some(l), Some(r)) => (l, r),
+            _ => return Ok(None),
+        }
+    };
+}
+
+impl Parser {
+    /// 将解析后的表达式编译为可快速执行的程序
+    ///
+    /// 变量在编译时解析为记录槽位, `&&` 和 `||` 按三值逻辑短路求值
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 由 `parse` 得到的表达式
+    /// * `layout` - 记录布局
+    pub fn compile(&self, expr: &Expr, layout: &RecordLayout) -> Result<Program> {
+        let eval = boolean(self.compile_expr(expr, layout)?, "表达式结果")?;
+        Ok(Program { eval })
+    }
+
+    /// 编译表达式节点
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `layout` - 记录布局
+    fn compile_expr(&self, expr: &Expr, layout: &RecordLayout) -> Result<Compiled> {
+        let precision = self.precision;
+        let c = |expr: &Expr| self.compile_expr(expr, layout);
+
+        let arithmetic = |op: &str,
+                          f: fn(Decimal, Decimal) -> Result<Decimal>,
+                          left: &Expr,
+                          right: &Expr|
+         -> Result<Compiled> {
+            let (l, r) = (number(c(left)?, op)?, number(c(right)?, op)?);
+            Ok(Compiled::Number(Box::new(move |record| {
+                let (l, r) = both!(l, r, record);
+                f(l, r).map(Some)
+            })))
+        };
+        let comparison = |op: &str,
+                          f: fn(&Decimal, &Decimal) -> bool,
+                          left: &Expr,
+                          right: &Expr|
+         -> Result<Compiled> {
+            let (l, r) = (number(c(left)?, op)?, number(c(right)?, op)?);
+            Ok(Compiled::Boolean(Box::new(move |record| {
+                let (l, r) = both!(l, r, record);
+                Ok(Some(f(&l.round_dp(precision), &r.round_dp(precision))))
+            })))
+        };
+        let equality = |op: &str, negate: bool, left: &Expr, right: &Expr| -> Result<Compiled> {
+            let eval: Eval<bool> = match (c(left)?, c(right)?) {
+                (Compiled::Number(l), Compiled::Number(r)) => Box::new(move |record| {
+                    let (l, r) = both!(l, r, record);
+                    Ok(Some(
+                        (l.round_dp(precision) == r.round_dp(precision)) != negate,
+                    ))
+                }),
+                (Compiled::Boolean(l), Compiled::Boolean(r)) => Box::new(move |record| {
+                    let (l, r) = both!(l, r, record);
+                    Ok(Some((l == r) != negate))
+                }),
+                (Compiled::String(l), Compiled::String(r)) => Box::new(move |record| {
+                    let (l, r) = both!(l, r, record);
+                    Ok(Some((l == r) != negate))
+                }),
+                _ => bail!("无效的操作数类型: {}", op),
+            };
+            Ok(Compiled::Boolean(eval))
+        };
+
+        Ok(match expr {
+            Expr::Number(n) => {
+                let n = *n;
+                Compiled::Number(Box::new(move |_| Ok(Some(n))))
+            }
+            Expr::Boolean(b) => {
+                let b = *b;
+                Compiled::Boolean(Box::new(move |_| Ok(Some(b))))
+            }
+            Expr::String(s) => {
+                let s = s.clone();
+                Compiled::String(Box::new(move |_| Ok(Some(Cow::Owned(s.clone())))))
+            }
+            Expr::NumberVariable(name) => {
+                let slot = slot(&layout.number_keys, name)
+                    .ok_or_else(|| anyhow!("数字变量 '{}' 不在记录布局中", name))?;
+                Compiled::Number(Box::new(move |record| Ok(record.numbers[slot])))
+            }
+            Expr::BooleanVariable(name) => {
+                let slot = slot(&layout.boolean_keys, name)
+                    .ok_or_else(|| anyhow!("布尔变量 '{}' 不在记录布局中", name))?;
+                Compiled::Boolean(Box::new(move |record| Ok(record.booleans[slot])))
+            }
+            Expr::StringVariable(name) => {
+                let slot = slot(&layout.string_keys, name)
+                    .ok_or_else(|| anyhow!("字符串变量 '{}' 不在记录布局中", name))?;
+                let name = name.clone();
+                let values: Option<HashSet<String>> = self
+                    .string_values
+                    .get(&name)
+                    .map(|values| values.iter().cloned().collect());
+                Compiled::String(Box::new(move |record| {
+                    let Some(value) = &record.strings[slot] else {
+                        return Ok(None);
+                    };
+                    if let Some(values) = &values
+                        && !values.contains(value)
+                    {
+                        bail!("'{}' 不是有效的{}", value, name);
+                    }
+                    Ok(Some(Cow::Borrowed(value.as_str())))
+                }))
+            }
+            Expr::Plus(left, right) => arithmetic("+", |l, r| Ok(l + r), left, right)?,
+            Expr::Minus(left, right) => arithmetic("-", |l, r| Ok(l - r), left, right)?,
+            Expr::Times(left, right) => arithmetic("*", |l, r| Ok(l * r), left, right)?,
+            Expr::Divide(left, right) => arithmetic(
+                "/",
+                |l, r| l.checked_div(r).ok_or_else(|| anyhow!("除数不能为零")),
+                left,
+                right,
+            )?,
+            // 假 && 未知 = 假, 真 || 未知 = 真
+            Expr::And(left, right) => {
+                let (l, r) = (boolean(c(left)?, "&&")?, boolean(c(right)?, "&&")?);
+                Compiled::Boolean(Box::new(move |record| match l(record)? {
+                    Some(false) => Ok(Some(false)),
+                    Some(true) => r(record),
+                    None => Ok(r(record)?.filter(|r| !r)),
+                }))
+            }
+            Expr::Or(left, right) => {
+                let (l, r) = (boolean(c(left)?, "||")?, boolean(c(right)?, "||")?);
+                Compiled::Boolean(Box::new(move |record| match l(record)? {
+                    Some(true) => Ok(Some(true)),
+                    Some(false) => r(record),
+                    None => Ok(r(record)?.filter(|r| *r)),
+                }))
+            }
+            Expr::Not(expr) => {
+                let eval = boolean(c(expr)?, "!")?;
+                Compiled::Boolean(Box::new(move |record| Ok(eval(record)?.map(|b| !b))))
+            }
+            Expr::LessThan(left, right) => comparison("<", Decimal::lt, left, right)?,
+            Expr::GreaterThan(left, right) => comparison(">", Decimal::gt, left, right)?,
+            Expr::LessThanEqual(left, right) => comparison("<=", Decimal::le, left, right)?,
+            Expr::GreaterThanEqual(left, right) => comparison(">=", Decimal::ge, left, right)?,
+            Expr::Equal(left, right) => equality("==", false, left, right)?,
+            Expr::NotEqual(left, right) => equality("!=", true, left, right)?,
+            Expr::Call(name, args) => self.compile_call(name, args, layout)?,
+            Expr::In(expr, members) => {
+                let eval = string(c(expr)?, "in")?;
+                let members: HashSet<String> = members.iter().cloned().collect();
+                Compiled::Boolean(Box::new(move |record| {
+                    Ok(eval(record)?.map(|value| members.contains(value.as_ref())))
+                }))
+            }
+        })
+    }
+
+    /// 编译函数调用, 任一参数未知时结果未知, `if` 只要求条件和所选分支已知
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 函数名称
+    /// * `args` - 函数参数
+    /// * `layout` - 记录布局
+    fn compile_call(&self, name: &str, args: &[Expr], layout: &RecordLayout) -> Result<Compiled> {
+        let function = self.get_function(name)?;
+        let args = args
+            .iter()
+            .map(|arg| self.compile_expr(arg, layout))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(match function.imp {
+            FunctionImpl::If => {
+                let [cond, then, otherwise]: [Compiled; 3] = args
+                    .try_into()
+                    .map_err(|_| anyhow!("函数 '{}' 参数个数错误", name))?;
+                let cond = boolean(cond, name)?;
+                match (then, otherwise) {
+                    (Compiled::Number(t), Compiled::Number(o)) => {
+                        Compiled::Number(Box::new(move |record| match cond(record)? {
+                            Some(true) => t(record),
+                            Some(false) => o(record),
+                            None => Ok(None),
+                        }))
+                    }
+                    (Compiled::Boolean(t), Compiled::Boolean(o)) => {
+                        Compiled::Boolean(Box::new(move |record| match cond(record)? {
+                            Some(true) => t(record),
+                            Some(false) => o(record),
+                            None => Ok(None),
+                        }))
+                    }
+                    (Compiled::String(t), Compiled::String(o)) => {
+                        Compiled::String(Box::new(move |record| match cond(record)? {
+                            Some(true) => t(record),
+                            Some(false) => o(record),
+                            None => Ok(None),
+                        }))
+                    }
+                    _ => bail!("无效的操作数类型: {}", name),
+                }
+            }
+            FunctionImpl::Number(f) => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| number(arg, name))
+                    .collect::<Result<Vec<_>>>()?;
+                Compiled::Number(Box::new(move |record| {
+                    let values = args
+                        .iter()
+                        .map(|arg| arg(record))
+                        .collect::<Result<Option<Vec<_>>>>()?;
+                    values.map(|values| f(&values)).transpose()
+                }))
+            }
+            FunctionImpl::String(f) => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| string(arg, name))
+                    .collect::<Result<Vec<_>>>()?;
+                Compiled::Boolean(Box::new(move |record| {
+                    let values = args
+                        .iter()
+                        .map(|arg| arg(record))
+                        .collect::<Result<Option<Vec<_>>>>()?;
+                    Ok(values.map(|values| {
+                        let values: Vec<&str> = values.iter().map(|value| value.as_ref()).collect();
+                        f(&values)
+                    }))
+                }))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{ExprResult, ExprVar};
+
+    use super::*;
+
+    #[test]
+    fn test_compile_matches_exec() {
+        let var_key = ExprVarKey::new(
+            vec![String::from("战狂"), String::from("已锁定")],
+            vec![
+                String::from("暴击率"),
+                String::from("暴击伤害"),
+                String::from("星级"),
+            ],
+        );
+        let mut parser = Parser::new(2, var_key).unwrap();
+        parser.set_string_vars(HashMap::from([(
+            String::from("主词条"),
+            vec![String::from("暴击率"), String::from("攻击力百分比")],
+        )]));
+
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("暴击率"), 3.9);
+        expr_var.number_vars.insert(String::from("暴击伤害"), 14.0);
+        expr_var.number_vars.insert(String::from("星级"), 5.0);
+        expr_var.boolean_vars.insert(String::from("战狂"), true);
+        expr_var.boolean_vars.insert(String::from("已锁定"), false);
+        expr_var
+            .string_vars
+            .insert(String::from("主词条"), String::from("暴击率"));
+
+        let inputs = [
+            "暴击率 * 2 + 暴击伤害 >= 21.8",
+            "暴击率 * 2 + 暴击伤害 > 21.8",
+            "10 / 3 == 3.33",
+            "战狂 && !已锁定 && 星级 == 5",
+            "已锁定 || max(暴击率, 暴击伤害 / 4) > 3.5",
+            "if(战狂, 暴击率, 暴击伤害) == 3.9",
+            "主词条 in [暴击率] && 主词条 != \"攻击力百分比\"",
+            "starts_with(主词条, \"暴击\") == 战狂",
+            "暴击伤害 > 100 && 星级 == 5",
+            "已锁定 || 暴击伤害 > 10",
+            "战狂 && min(暴击率, 暴击伤害) > 1",
+            "if(已锁定, 暴击伤害, 暴击率) == 3.9",
+        ];
+        // 先全部已知, 再将暴击伤害设为未知
+        for unknown in [false, true] {
+            if unknown {
+                expr_var.unknown_vars.insert(String::from("暴击伤害"));
+            }
+            for input in inputs {
+                let expr = parser.parse(input).unwrap();
+                let layout = RecordLayout::new(&expr.get_var_keys());
+                let record = layout
+                    .record(
+                        |name| {
+                            if expr_var.unknown_vars.contains(name) {
+                                return None;
+                            }
+                            expr_var.number_vars.get(name).copied()
+                        },
+                        |name| expr_var.boolean_vars.get(name).copied(),
+                        |name| expr_var.string_vars.get(name).cloned(),
+                    )
+                    .unwrap();
+                let program = parser.compile(&expr, &layout).unwrap();
+                let expected = match parser.exec(&expr, &expr_var).unwrap() {
+                    ExprResult::Boolean(b) => Some(b),
+                    ExprResult::Unknown => None,
+                    result => panic!("表达式结果应为布尔类型: {}, {:?}", input, result),
+                };
+                assert_eq!(program.eval(&record).unwrap(), expected, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_short_circuit() {
+        let var_key = ExprVarKey::new(vec![String::from("a")], vec![String::from("b")]);
+        let parser = Parser::new(2, var_key).unwrap();
+        let layout = RecordLayout::new(&ExprVarKey::new(
+            vec![String::from("a")],
+            vec![String::from("b")],
+        ));
+        let record = layout
+            .record(|_| Some(0.0), |_| Some(false), |_| None)
+            .unwrap();
+
+        // 右侧除数为零, 短路求值时不会执行
+        let expr = parser.parse("a && 1 / b > 0").unwrap();
+        let program = parser.compile(&expr, &layout).unwrap();
+        assert_eq!(program.eval(&record).unwrap(), Some(false));
+
+        let expr = parser.parse("!a || 1 / b > 0").unwrap();
+        let program = parser.compile(&expr, &layout).unwrap();
+        assert_eq!(program.eval(&record).unwrap(), Some(true));
+
+        let expr = parser.parse("1 / b > 0").unwrap();
+        let program = parser.compile(&expr, &layout).unwrap();
+        assert_eq!(
+            program.eval(&record).unwrap_err().to_string(),
+            "除数不能为零"
+        );
+    }
+
+    #[test]
+    fn test_divide_by_zero() {
+        let var_key = ExprVarKey::new(vec![], vec![String::from("暴击率")]);
+        let parser = Parser::new(2, var_key.clone()).unwrap();
+        let layout = RecordLayout::new(&var_key);
+        let record = layout.record(|_| Some(10.0), |_| None, |_| None).unwrap();
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("暴击率"), 10.0);
+
+        // 两种求值方式都返回错误, 而不是 panic
+        for input in ["暴击率 / 0 > 1", "1 / (暴击率 - 10) > 1"] {
+            let expr = parser.parse(input).unwrap();
+            let program = parser.compile(&expr, &layout).unwrap();
+            assert_eq!(
+                program.eval(&record).unwrap_err().to_string(),
+                "除数不能为零"
+            );
+            assert_eq!(
+                parser.exec(&expr, &expr_var).unwrap_err().to_string(),
+                "除数不能为零"
+            );
+        }
+    }
+
+    #[test]
+    fn test_compile_layout() {
+        let parser = Parser::new(2, ExprVarKey::new(vec![], vec![String::from("a")])).unwrap();
+        let expr = parser.parse("a > 1").unwrap();
+
+        let err = parser
+            .compile(&expr, &RecordLayout::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "数字变量 'a' 不在记录布局中");
+
+        // 获取不到的变量值为未知
+        let layout = RecordLayout::new(&expr.get_var_keys());
+        let record = layout.record(|_| None, |_| None, |_| None).unwrap();
+        let program = parser.compile(&expr, &layout).unwrap();
+        assert_eq!(program.eval(&record).unwrap(), None);
+
+        let err = layout
+            .record(|_| Some(f32::NAN), |_| None, |_| None)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "数字变量 'a' 的值无效: NaN");
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt;
+
+use anyhow::Result;
+use rust_decimal::prelude::*;
+
+use crate::{Expr, ExprResult, ExprVar, FunctionImpl, Parser};
+
+/// 表达式求值解释, 记录每个子表达式的值
+#[derive(Debug, Clone)]
+pub struct Explain<'a> {
+    pub expr: &'a Expr,
+    /// 子表达式的值, `None` 表示因短路求值未计算
+    pub result: Option<ExprResult>,
+    /// 操作数的解释, 顺序与书写顺序一致
+    pub children: Vec<Explain<'a>>,
+}
+
+/// 运算符优先级, 数字越大结合越紧
+///
+/// # 参数
+///
+/// * `expr` - 表达式
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::And(..) | Expr::Or(..) => 1,
+        Expr::Not(_) => 2,
+        Expr::Equal(..)
+        | Expr::NotEqual(..)
+        | Expr::LessThan(..)
+        | Expr::GreaterThan(..)
+        | Expr::LessThanEqual(..)
+        | Expr::GreaterThanEqual(..)
+        | Expr::In(..) => 3,
+        Expr::Plus(..) | Expr::Minus(..) => 4,
+        Expr::Times(..) | Expr::Divide(..) => 5,
+        _ => 6,
+    }
+}
+
+/// 拆分二元运算表达式为左侧、运算符和右侧
+///
+/// # 参数
+///
+/// * `expr` - 表达式
+fn binary(expr: &Expr) -> Option<(&Expr, &'static str, &Expr)> {
+    let (left, op, right) = match expr {
+        Expr::Plus(left, right) => (left, "+", right),
+        Expr::Minus(left, right) => (left, "-", right),
+        Expr::Times(left, right) => (left, "*", right),
+        Expr::Divide(left, right) => (left, "/", right),
+        Expr::And(left, right) => (left, "&&", right),
+        Expr::Or(left, right) => (left, "||", right),
+        Expr::Equal(left, right) => (left, "==", right),
+        Expr::NotEqual(left, right) => (left, "!=", right),
+        Expr::LessThan(left, right) => (left, "<", right),
+        Expr::GreaterThan(left, right) => (left, ">", right),
+        Expr::LessThanEqual(left, right) => (left, "<=", right),
+        Expr::GreaterThanEqual(left, right) => (left, ">=", right),
+        _ => return None,
+    };
+    Some((left, op, right))
+}
+
+impl Expr {
+    /// 按书写顺序获取操作数
+    pub(crate) fn operands(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Not(expr) | Expr::In(expr, _) => vec![expr],
+            Expr::Call(_, args) => args.iter().collect(),
+            _ => match binary(self) {
+                Some((left, _, right)) => vec![left, right],
+                None => vec![],
+            },
+        }
+    }
+
+    /// 是否为字面量或变量
+    fn is_leaf(&self) -> bool {
+        self.operands().is_empty() && !matches!(self, Expr::Call(..))
+    }
+}
+
+impl Explain<'_> {
+    /// 子表达式的值, 未计算时为 "未计算"
+    fn value(&self) -> String {
+        match &self.result {
+            Some(result) => result.to_string(),
+            None => String::from("未计算"),
+        }
+    }
+
+    /// 判断结果为假的表达式是否接近命中
+    ///
+    /// 导致结果为假的都是数字大小比较, 且两侧差距都不超过右侧的 `ratio` 倍时视为接近命中
+    ///
+    /// # 参数
+    ///
+    /// * `ratio` - 差距比例, 例如 0.1 表示相差 10% 以内
+    pub fn is_near_miss(&self, ratio: f32) -> bool {
+        let Some(ratio) = Decimal::from_f32(ratio) else {
+            return false;
+        };
+        self.is_near_miss_decimal(ratio)
+    }
+
+    /// 按十进制比例判断是否接近命中
+    ///
+    /// # 参数
+    ///
+    /// * `ratio` - 差距比例
+    fn is_near_miss_decimal(&self, ratio: Decimal) -> bool {
+        if self.result != Some(ExprResult::Boolean(false)) {
+            return false;
+        }
+        match self.expr {
+            Expr::And(..) => self
+                .children
+                .iter()
+                .filter(|child| child.result == Some(ExprResult::Boolean(false)))
+                .all(|child| child.is_near_miss_decimal(ratio)),
+            Expr::Or(..) => self
+                .children
+                .iter()
+                .all(|child| child.is_near_miss_decimal(ratio)),
+            Expr::LessThan(..)
+            | Expr::GreaterThan(..)
+            | Expr::LessThanEqual(..)
+            | Expr::GreaterThanEqual(..) => {
+                match (&self.children[0].result, &self.children[1].result) {
+                    (Some(ExprResult::Number(l)), Some(ExprResult::Number(r))) => {
+                        (l - r).abs() <= r.abs() * ratio
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 输出表达式, 附带解释时在变量后标注变量值, 在复合操作数后标注计算结果
+///
+/// # 参数
+///
+/// * `f` - 输出
+/// * `expr` - 表达式
+/// * `explain` - 表达式对应的解释, 为 `None` 时只输出原始表达式
+fn write_expr(f: &mut fmt::Formatter, expr: &Expr, explain: Option<&Explain>) -> fmt::Result {
+    let child = |i: usize| explain.and_then(|explain| explain.children.get(i));
+    match expr {
+        Expr::Number(n) => write!(f, "{}", n),
+        Expr::Boolean(b) => write!(f, "{}", b),
+        Expr::String(s) => write!(f, "\"{}\"", s),
+        Expr::NumberVariable(name) | Expr::BooleanVariable(name) | Expr::StringVariable(name) => {
+            write!(f, "{}", name)?;
+            if let Some(explain) = explain {
+                write!(f, "({})", explain.value())?;
+            }
+            Ok(())
+        }
+        Expr::Not(operand) => {
+            write!(f, "!")?;
+            write_operand(f, expr, operand, child(0), false)
+        }
+        Expr::Call(name, args) => {
+            write!(f, "{}(", name)?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                // 未选择的条件分支按原样输出
+                write_expr(f, arg, child(i).filter(|e| e.result.is_some()))?;
+            }
+            write!(f, ")")
+        }
+        Expr::In(operand, members) => {
+            write_operand(f, expr, operand, child(0), false)?;
+            let members: Vec<String> = members.iter().map(|m| format!("\"{}\"", m)).collect();
+            write!(f, " in [{}]", members.join(", "))
+        }
+        _ => {
+            let Some((left, op, right)) = binary(expr) else {
+                unreachable!("非二元运算表达式: {:?}", expr)
+            };
+            write_operand(f, expr, left, child(0), false)?;
+            write!(f, " {} ", op)?;
+            write_operand(f, expr, right, child(1), true)
+        }
+    }
+}
+
+/// 输出操作数, 按优先级添加括号
+///
+/// 附带解释时, 逻辑运算的复合操作数加括号并标注结果, 比较运算的复合操作数标注计算结果
+///
+/// # 参数
+///
+/// * `f` - 输出
+/// * `parent` - 操作数所属的表达式
+/// * `expr` - 操作数
+/// * `explain` - 操作数对应的解释
+/// * `right` - 是否为右侧操作数
+fn write_operand(
+    f: &mut fmt::Formatter,
+    parent: &Expr,
+    expr: &Expr,
+    explain: Option<&Explain>,
+    right: bool,
+) -> fmt::Result {
+    let parent_precedence = precedence(parent);
+    if let Some(explain) = explain
+        && !expr.is_leaf()
+        && parent_precedence <= 2
+    {
+        write!(f, "(")?;
+        write_expr(f, expr, Some(explain).filter(|e| e.result.is_some()))?;
+        return write!(f, " → {})", explain.value());
+    }
+
+    // 比较运算不可结合, 其余二元运算左结合
+    let precedence = precedence(expr);
+    let paren = precedence < parent_precedence
+        || (precedence == parent_precedence && (right || parent_precedence == 3));
+    if paren {
+        write!(f, "(")?;
+    }
+    write_expr(f, expr, explain)?;
+    if paren {
+        write!(f, ")")?;
+    }
+    if let Some(explain) = explain
+        && !expr.is_leaf()
+        && parent_precedence == 3
+    {
+        write!(f, " = {}", explain.value())?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_expr(f, self, None)
+    }
+}
+
+impl fmt::Display for Explain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_expr(f, self.expr, Some(self))?;
+        if !self.expr.is_leaf() {
+            write!(f, " → {}", self.value())?;
+        }
+        Ok(())
+    }
+}
+
+impl Parser {
+    /// 执行表达式, 并记录每个子表达式的值
+    ///
+    /// 与 `exec` 的求值规则一致, 因短路求值或条件分支未计算的子表达式值为 `None`
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `expr_var` - 表达式变量
+    pub fn explain<'a>(&self, expr: &'a Expr, expr_var: &ExprVar) -> Result<Explain<'a>> {
+        let result = self.exec(expr, expr_var)?;
+        let is_if = match expr {
+            Expr::Call(name, _) => matches!(self.get_function(name)?.imp, FunctionImpl::If),
+            _ => false,
+        };
+
+        let mut children: Vec<Explain> = vec![];
+        for (i, operand) in expr.operands().into_iter().enumerate() {
+            let first = children.first().and_then(|child| child.result.as_ref());
+            let skipped = match (expr, first) {
+                (Expr::And(..), Some(ExprResult::Boolean(false))) => true,
+                (Expr::Or(..), Some(ExprResult::Boolean(true))) => true,
+                (_, Some(ExprResult::Boolean(cond))) if is_if => i == if *cond { 2 } else { 1 },
+                (_, Some(ExprResult::Unknown)) if is_if => true,
+                _ => false,
+            };
+            children.push(if skipped {
+                Explain {
+                    expr: operand,
+                    result: None,
+                    children: vec![],
+                }
+            } else {
+                self.explain(operand, expr_var)?
+            });
+        }
+
+        Ok(Explain {
+            expr,
+            result: Some(result),
+            children,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ExprVarKey;
+
+    use super::*;
+
+    fn parser() -> Parser {
+        let var_key = ExprVarKey::new(
+            vec![String::from("战狂"), String::from("已锁定")],
+            vec![
+                String::from("暴击率"),
+                String::from("暴击伤害"),
+                String::from("星级"),
+            ],
+        );
+        Parser::new(2, var_key).unwrap()
+    }
+
+    fn expr_var() -> ExprVar {
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("暴击率"), 3.9);
+        expr_var.number_vars.insert(String::from("暴击伤害"), 14.0);
+        expr_var.number_vars.insert(String::from("星级"), 5.0);
+        expr_var.boolean_vars.insert(String::from("战狂"), true);
+        expr_var.boolean_vars.insert(String::from("已锁定"), false);
+        expr_var
+    }
+
+    #[test]
+    fn test_display_expr() {
+        let parser = parser();
+        for (input, expected) in [
+            ("暴击率*2+暴击伤害>=30", "暴击率 * 2 + 暴击伤害 >= 30"),
+            ("(暴击率 + 暴击伤害) * 2 > 1", "(暴击率 + 暴击伤害) * 2 > 1"),
+            ("暴击率 - (星级 - 1) > 1", "暴击率 - (星级 - 1) > 1"),
+            (
+                "战狂 && (已锁定 || 星级 == 5)",
+                "战狂 && (已锁定 || 星级 == 5)",
+            ),
+            ("!(战狂 && 已锁定)", "!(战狂 && 已锁定)"),
+            ("max(暴击率, 1) <= 3", "max(暴击率, 1) <= 3"),
+        ] {
+            let expr = parser.parse(input).unwrap();
+            assert_eq!(expr.to_string(), expected);
+            // 输出结果能重新解析为相同的表达式
+            assert_eq!(parser.parse(expected).unwrap(), expr);
+        }
+    }
+
+    #[test]
+    fn test_explain() {
+        let parser = parser();
+        let expr_var = expr_var();
+        for (input, expected) in [
+            (
+                "暴击率 * 2 + 暴击伤害 >= 30",
+                "暴击率(3.9) * 2 + 暴击伤害(14) = 21.8 >= 30 → false",
+            ),
+            (
+                "星级 == 5 && 战狂",
+                "(星级(5) == 5 → true) && 战狂(true) → true",
+            ),
+            (
+                "已锁定 && 暴击率 > 3",
+                "已锁定(false) && (暴击率 > 3 → 未计算) → false",
+            ),
+            (
+                "if(战狂, 暴击率, 暴击伤害) < 4",
+                "if(战狂(true), 暴击率(3.9), 暴击伤害) = 3.9 < 4 → true",
+            ),
+        ] {
+            let expr = parser.parse(input).unwrap();
+            let explain = parser.explain(&expr, &expr_var).unwrap();
+            assert_eq!(explain.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_explain_divide_by_zero() {
+        let parser = parser();
+        let expr = parser.parse("暴击率 / 0 > 1").unwrap();
+        assert_eq!(
+            parser.explain(&expr, &expr_var()).unwrap_err().to_string(),
+            "除数不能为零"
+        );
+    }
+
+    #[test]
+    fn test_near_miss() {
+        let parser = parser();
+        let expr_var = expr_var();
+        for (input, expected) in [
+            ("暴击率 * 2 + 暴击伤害 >= 30", false),
+            ("暴击率 * 2 + 暴击伤害 >= 23", true),
+            ("星级 == 5 && 暴击伤害 > 15", true),
+            ("星级 == 4 && 暴击伤害 > 15", false),
+            ("暴击率 > 4 || 暴击伤害 > 15", true),
+            ("暴击率 * 2 + 暴击伤害 >= 21", false),
+        ] {
+            let expr = parser.parse(input).unwrap();
+            let explain = parser.explain(&expr, &expr_var).unwrap();
+            assert_eq!(explain.is_near_miss(0.1), expected, "{}", input);
+        }
+    }
+}
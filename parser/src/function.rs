@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use rust_decimal::prelude::*;
+
+/// 函数参数类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    Number,  // 数字
+    Boolean, // 布尔值
+    String,  // 字符串
+    Generic, // 泛型, 同一次调用中所有泛型参数类型一致, 返回值同为该类型
+}
+
+/// 函数实现
+#[derive(Debug, Clone, Copy)]
+pub enum FunctionImpl {
+    /// 数字函数, 调用前所有参数均已求值
+    Number(fn(&[Decimal]) -> Result<Decimal>),
+    /// 字符串判断函数, 调用前所有参数均已求值
+    String(fn(&[&str]) -> bool),
+    /// 条件函数, 根据第一个参数只求值其中一个分支
+    If,
+}
+
+/// 内置函数
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: &'static str,
+    pub params: Vec<ParamType>,
+    pub variadic: bool, // 最后一个参数是否可重复
+    pub ret: ParamType,
+    pub imp: FunctionImpl,
+}
+
+impl Function {
+    /// 检查参数个数是否匹配
+    ///
+    /// # 参数
+    ///
+    /// * `count` - 参数个数
+    pub fn check_arity(&self, count: usize) -> bool {
+        if self.variadic {
+            count >= self.params.len()
+        } else {
+            count == self.params.len()
+        }
+    }
+
+    /// 获取指定位置的参数类型
+    ///
+    /// # 参数
+    ///
+    /// * `index` - 参数位置
+    pub fn param_type(&self, index: usize) -> ParamType {
+        self.params[index.min(self.params.len() - 1)]
+    }
+}
+
+/// 将数字转换为保留的小数位数
+///
+/// # 参数
+///
+/// * `dp` - 小数位数
+fn to_dp(dp: Decimal) -> Result<u32> {
+    if dp.is_sign_negative() || !dp.fract().is_zero() {
+        bail!("小数位数必须为非负整数: {}", dp);
+    }
+    dp.to_u32()
+        .ok_or_else(|| anyhow::anyhow!("小数位数超出范围: {}", dp))
+}
+
+fn max(args: &[Decimal]) -> Result<Decimal> {
+    Ok(args.iter().copied().fold(args[0], Decimal::max))
+}
+
+fn min(args: &[Decimal]) -> Result<Decimal> {
+    Ok(args.iter().copied().fold(args[0], Decimal::min))
+}
+
+fn abs(args: &[Decimal]) -> Result<Decimal> {
+    Ok(args[0].abs())
+}
+
+fn round(args: &[Decimal]) -> Result<Decimal> {
+    Ok(args[0].round_dp_with_strategy(to_dp(args[1])?, RoundingStrategy::MidpointAwayFromZero))
+}
+
+fn floor(args: &[Decimal]) -> Result<Decimal> {
+    Ok(args[0].floor())
+}
+
+fn ceil(args: &[Decimal]) -> Result<Decimal> {
+    Ok(args[0].ceil())
+}
+
+fn clamp(args: &[Decimal]) -> Result<Decimal> {
+    let (value, low, high) = (args[0], args[1], args[2]);
+    if low > high {
+        bail!("clamp 下限 {} 大于上限 {}", low, high);
+    }
+    Ok(value.max(low).min(high))
+}
+
+fn contains(args: &[&str]) -> bool {
+    args[0].contains(args[1])
+}
+
+fn starts_with(args: &[&str]) -> bool {
+    args[0].starts_with(args[1])
+}
+
+/// 获取所有内置函数
+pub fn builtin_functions() -> HashMap<String, Function> {
+    use ParamType::*;
+
+    let number = |name: &'static str, params: Vec<ParamType>, variadic: bool, f| Function {
+        name,
+        params,
+        variadic,
+        ret: Number,
+        imp: FunctionImpl::Number(f),
+    };
+
+    let string = |name: &'static str, f| Function {
+        name,
+        params: vec![String, String],
+        variadic: false,
+        ret: Boolean,
+        imp: FunctionImpl::String(f),
+    };
+
+    let functions = vec![
+        number("max", vec![Number], true, max),
+        number("min", vec![Number], true, min),
+        number("abs", vec![Number], false, abs),
+        number("round", vec![Number, Number], false, round),
+        number("floor", vec![Number], false, floor),
+        number("ceil", vec![Number], false, ceil),
+        number("clamp", vec![Number, Number, Number], false, clamp),
+        string("contains", contains),
+        string("starts_with", starts_with),
+        Function {
+            name: "if",
+            params: vec![Boolean, Generic, Generic],
+            variadic: false,
+            ret: Generic,
+            imp: FunctionImpl::If,
+        },
+    ];
+
+    functions
+        .into_iter()
+        .map(|function| (function.name.to_string(), function))
+        .collect()
+}
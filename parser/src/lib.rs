@@ -1,5 +1,15 @@
+mod analyze;
+mod check;
+mod compile;
+mod explain;
+mod function;
 mod parse;
 mod parser;
+mod suggest;
 
-pub use parse::{Expr, ExprVar, ExprVarKey};
+pub use analyze::Interval;
+pub use compile::{Program, Record, RecordLayout};
+pub use explain::Explain;
+pub use function::{Function, FunctionImpl, ParamType, builtin_functions};
+pub use parse::{Expr, ExprSpan, ExprVar, ExprVarKey, split_param_args, split_param_var};
 pub use parser::*;
@@ -1,30 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Result;
 use rust_decimal::prelude::*;
 
 /// 表达式变量
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ExprVar {
     pub boolean_vars: HashMap<String, bool>,
     pub number_vars: HashMap<String, f32>,
-}
-
-impl ExprVar {
-    /// 创建默认的表达式变量
-    pub fn default() -> Self {
-        Self {
-            boolean_vars: HashMap::new(),
-            number_vars: HashMap::new(),
-        }
-    }
+    pub string_vars: HashMap<String, String>,
+    /// 值未知的变量, 例如未能识别的圣遗物属性, 求值结果为 `ExprResult::Unknown`
+    pub unknown_vars: HashSet<String>,
 }
 
 /// 表达式变量键
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ExprVarKey {
     pub boolean_keys: Vec<String>,
     pub number_keys: Vec<String>,
+    pub string_keys: Vec<String>,
+    /// 带参数的数字变量名称, 例如 `达标概率(30)` 中的 `达标概率`
+    pub param_number_keys: Vec<String>,
 }
 
 impl ExprVarKey {
@@ -32,15 +28,65 @@ impl ExprVarKey {
         Self {
             boolean_keys,
             number_keys,
+            string_keys: vec![],
+            param_number_keys: vec![],
         }
     }
+}
 
-    pub fn default() -> Self {
-        Self {
-            boolean_keys: vec![],
-            number_keys: vec![],
+/// 拆分带参数的变量名称, 例如 `达标概率(30)` 拆分为 `达标概率` 和 `30`
+///
+/// # 参数
+///
+/// * `name` - 变量名称
+pub fn split_param_var(name: &str) -> Option<(&str, &str)> {
+    name.strip_suffix(')')?.split_once('(')
+}
+
+/// 按顶层逗号拆分变量参数, 括号和字符串中的逗号不拆分
+///
+/// # 参数
+///
+/// * `params` - 变量参数, 例如 `双爆分, 部位`
+pub fn split_param_args(params: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' if !quoted => depth += 1,
+            ')' | ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                result.push(params[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
         }
     }
+    result.push(params[start..].trim());
+    result
+}
+
+/// 构造带参数的变量名称, 参数中的数字去掉多余的 0, 参数之间以 `, ` 分隔
+///
+/// # 参数
+///
+/// * `name` - 变量名称
+/// * `args` - 参数表达式
+fn param_var(name: String, args: Vec<Spanned>) -> String {
+    let args: Vec<String> = args
+        .into_iter()
+        .map(|(arg, _)| {
+            arg.replace(&|e| match e {
+                Expr::Number(n) => Some(Expr::Number(n.normalize())),
+                _ => None,
+            })
+            .to_string()
+        })
+        .collect();
+    format!("{}({})", name, args.join(", "))
 }
 
 /// 表达式类型
@@ -49,10 +95,12 @@ pub enum Expr {
     // 基础
     Number(Decimal), // 数字
     Boolean(bool),   // 布尔值
+    String(String),  // 字符串
 
     // 变量
     NumberVariable(String),  // 数字变量
     BooleanVariable(String), // 布尔变量
+    StringVariable(String),  // 字符串变量
 
     // 运算符
     Plus(Box<Expr>, Box<Expr>),   // 加法
@@ -72,12 +120,58 @@ pub enum Expr {
     GreaterThan(Box<Expr>, Box<Expr>),      // 大于
     LessThanEqual(Box<Expr>, Box<Expr>),    // 小于等于
     GreaterThanEqual(Box<Expr>, Box<Expr>), // 大于等于
+
+    // 函数
+    Call(String, Vec<Expr>), // 函数调用
+
+    // 成员运算符
+    In(Box<Expr>, Vec<String>), // 属于列表
 }
 
 impl Expr {
     pub fn get_var_keys(&self) -> ExprVarKey {
         loop_var_keys(self)
     }
+
+    /// 替换表达式节点
+    ///
+    /// `f` 返回 `Some` 时替换当前节点, 否则继续替换子节点
+    ///
+    /// # 参数
+    ///
+    /// * `f` - 替换函数
+    pub fn replace(&self, f: &impl Fn(&Expr) -> Option<Expr>) -> Expr {
+        if let Some(expr) = f(self) {
+            return expr;
+        }
+        let r = |expr: &Expr| Box::new(expr.replace(f));
+        match self {
+            Expr::Number(_)
+            | Expr::Boolean(_)
+            | Expr::String(_)
+            | Expr::NumberVariable(_)
+            | Expr::BooleanVariable(_)
+            | Expr::StringVariable(_) => self.clone(),
+            Expr::Plus(left, right) => Expr::Plus(r(left), r(right)),
+            Expr::Minus(left, right) => Expr::Minus(r(left), r(right)),
+            Expr::Times(left, right) => Expr::Times(r(left), r(right)),
+            Expr::Divide(left, right) => Expr::Divide(r(left), r(right)),
+            Expr::And(left, right) => Expr::And(r(left), r(right)),
+            Expr::Or(left, right) => Expr::Or(r(left), r(right)),
+            Expr::Not(expr) => Expr::Not(r(expr)),
+            Expr::Equal(left, right) => Expr::Equal(r(left), r(right)),
+            Expr::NotEqual(left, right) => Expr::NotEqual(r(left), r(right)),
+            Expr::LessThan(left, right) => Expr::LessThan(r(left), r(right)),
+            Expr::GreaterThan(left, right) => Expr::GreaterThan(r(left), r(right)),
+            Expr::LessThanEqual(left, right) => Expr::LessThanEqual(r(left), r(right)),
+            Expr::GreaterThanEqual(left, right) => Expr::GreaterThanEqual(r(left), r(right)),
+            Expr::Call(name, args) => Expr::Call(
+                name.clone(),
+                args.iter().map(|arg| arg.replace(f)).collect(),
+            ),
+            Expr::In(expr, members) => Expr::In(r(expr), members.clone()),
+        }
+    }
 }
 
 /// 遍历表达式变量键
@@ -88,94 +182,271 @@ impl Expr {
 pub fn loop_var_keys(expr: &Expr) -> ExprVarKey {
     let mut boolean_keys = vec![];
     let mut number_keys = vec![];
+    let mut string_keys = vec![];
 
-    let mut keys_append = |left: &Expr, right: &Expr| {
-        let left = loop_var_keys(left);
-        let right = loop_var_keys(right);
-        boolean_keys.extend(left.boolean_keys);
-        boolean_keys.extend(right.boolean_keys);
-        number_keys.extend(left.number_keys);
-        number_keys.extend(right.number_keys);
+    let mut keys_append = |exprs: &[&Expr]| {
+        for expr in exprs {
+            let var_key = loop_var_keys(expr);
+            boolean_keys.extend(var_key.boolean_keys);
+            number_keys.extend(var_key.number_keys);
+            string_keys.extend(var_key.string_keys);
+        }
     };
 
     match expr {
         Expr::NumberVariable(key) => number_keys.push(key.clone()),
         Expr::BooleanVariable(key) => boolean_keys.push(key.clone()),
-        Expr::Plus(left, right) => keys_append(left, right),
-        Expr::Minus(left, right) => keys_append(left, right),
-        Expr::Times(left, right) => keys_append(left, right),
-        Expr::Divide(left, right) => keys_append(left, right),
-        Expr::And(left, right) => keys_append(left, right),
-        Expr::Or(left, right) => keys_append(left, right),
-        Expr::Not(expr) => {
-            let var_key = loop_var_keys(expr);
-            boolean_keys.extend(var_key.boolean_keys);
-            number_keys.extend(var_key.number_keys);
-        }
-        Expr::Equal(left, right) => keys_append(left, right),
-        Expr::NotEqual(left, right) => keys_append(left, right),
-        Expr::LessThan(left, right) => keys_append(left, right),
-        Expr::GreaterThan(left, right) => keys_append(left, right),
-        Expr::LessThanEqual(left, right) => keys_append(left, right),
-        Expr::GreaterThanEqual(left, right) => keys_append(left, right),
-        Expr::Boolean(_) | Expr::Number(_) => (),
+        Expr::StringVariable(key) => string_keys.push(key.clone()),
+        Expr::Plus(left, right) => keys_append(&[left, right]),
+        Expr::Minus(left, right) => keys_append(&[left, right]),
+        Expr::Times(left, right) => keys_append(&[left, right]),
+        Expr::Divide(left, right) => keys_append(&[left, right]),
+        Expr::And(left, right) => keys_append(&[left, right]),
+        Expr::Or(left, right) => keys_append(&[left, right]),
+        Expr::Not(expr) => keys_append(&[expr]),
+        Expr::Equal(left, right) => keys_append(&[left, right]),
+        Expr::NotEqual(left, right) => keys_append(&[left, right]),
+        Expr::LessThan(left, right) => keys_append(&[left, right]),
+        Expr::GreaterThan(left, right) => keys_append(&[left, right]),
+        Expr::LessThanEqual(left, right) => keys_append(&[left, right]),
+        Expr::GreaterThanEqual(left, right) => keys_append(&[left, right]),
+        Expr::Call(_, args) => keys_append(&args.iter().collect::<Vec<_>>()),
+        Expr::In(expr, _) => keys_append(&[expr]),
+        Expr::Boolean(_) | Expr::Number(_) | Expr::String(_) => (),
     }
     ExprVarKey {
         boolean_keys,
         number_keys,
+        string_keys,
+        ..Default::default()
+    }
+}
+
+/// 表达式在原始字符串中的位置
+///
+/// `children` 与表达式的子节点一一对应, 顺序与 `Expr` 中子节点的顺序相同,
+/// `Expr::In` 的列表成员位置依次排在被检查表达式之后
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExprSpan {
+    pub start: usize, // 起始字节位置
+    pub end: usize,   // 结束字节位置
+    pub children: Vec<ExprSpan>,
+}
+
+/// 带位置的表达式
+type Spanned = (Expr, ExprSpan);
+
+/// 构造叶子节点
+fn leaf(expr: Expr, start: usize, end: usize) -> Spanned {
+    let children = vec![];
+    (
+        expr,
+        ExprSpan {
+            start,
+            end,
+            children,
+        },
+    )
+}
+
+/// 将节点位置扩展到括号
+fn wrap((expr, span): Spanned, start: usize, end: usize) -> Spanned {
+    let children = span.children;
+    (
+        expr,
+        ExprSpan {
+            start,
+            end,
+            children,
+        },
+    )
+}
+
+/// 构造一元运算节点
+fn unary(op: fn(Box<Expr>) -> Expr, (expr, span): Spanned, start: usize) -> Spanned {
+    let end = span.end;
+    let children = vec![span];
+    (
+        op(Box::new(expr)),
+        ExprSpan {
+            start,
+            end,
+            children,
+        },
+    )
+}
+
+/// 构造二元运算节点
+fn binary(op: fn(Box<Expr>, Box<Expr>) -> Expr, left: Spanned, right: Spanned) -> Spanned {
+    let (start, end) = (left.1.start, right.1.end);
+    let children = vec![left.1, right.1];
+    (
+        op(Box::new(left.0), Box::new(right.0)),
+        ExprSpan {
+            start,
+            end,
+            children,
+        },
+    )
+}
+
+/// 构造成员运算节点, `not in` 会被转换为 `!(x in [...])`
+fn membership(
+    (left, span): Spanned,
+    members: Vec<(String, usize, usize)>,
+    negate: bool,
+    end: usize,
+) -> Spanned {
+    // 成员运算的左侧按字符串变量解析
+    let left = match left {
+        Expr::NumberVariable(name) => Expr::StringVariable(name),
+        left => left,
+    };
+    let start = span.start;
+    let mut children = vec![span];
+    let mut names = vec![];
+    for (name, start, end) in members {
+        names.push(name);
+        children.push(ExprSpan {
+            start,
+            end,
+            children: vec![],
+        });
+    }
+    let node = (
+        Expr::In(Box::new(left), names),
+        ExprSpan {
+            start,
+            end,
+            children,
+        },
+    );
+    if negate {
+        let (expr, span) = node;
+        let children = vec![span];
+        return (
+            Expr::Not(Box::new(expr)),
+            ExprSpan {
+                start,
+                end,
+                children,
+            },
+        );
     }
+    node
+}
+
+/// 构造函数调用节点
+fn call(name: String, args: Vec<Spanned>, start: usize, end: usize) -> Spanned {
+    let (args, children): (Vec<Expr>, Vec<ExprSpan>) = args.into_iter().unzip();
+    (
+        Expr::Call(name, args),
+        ExprSpan {
+            start,
+            end,
+            children,
+        },
+    )
 }
 
 // 布尔表达式解析器
+//
+// 语法只按上下文区分数字变量和布尔变量, 操作数类型是否匹配由类型检查负责
 peg::parser!(grammar bool_parser() for str {
     rule _ = quiet!{[' ' | '\t' | '\n' | '\r']*}
 
     // 基础匹配
-    rule world() = ['a'..='z' | 'A'..='Z' | '\u{4e00}'..='\u{9fa5}' | ':' | '_']+
+    rule word_char() = ['a'..='z' | 'A'..='Z' | '\u{4e00}'..='\u{9fa5}' | ':' | '_']
+    rule keyword() = ("in" / "not") !word_char()
+    rule world() = !keyword() word_char()+
     rule variable() -> String = n:$(world() (_ world())*) { String::from(n) }
     rule number() -> f64 = n:$("-"? ['0'..='9']+ ("." ['0'..='9']+)?) { n.parse().unwrap() }
     rule boolean() -> bool = "true" { true } / "false" { false }
+    rule string() -> String = "\"" s:$([^ '"']*) "\"" { String::from(s) }
+    rule function_name() -> String = n:$(['a'..='z' | 'A'..='Z' | '_']+) { String::from(n) }
+    rule compare() -> fn(Box<Expr>, Box<Expr>) -> Expr =
+        "==" { Expr::Equal } / "!=" { Expr::NotEqual } /
+        "<=" { Expr::LessThanEqual } / ">=" { Expr::GreaterThanEqual } /
+        "<" { Expr::LessThan } / ">" { Expr::GreaterThan }
+
+    // 数字表达式的操作数
+    rule calculate_atom() -> Spanned =
+        s:position!() "(" _ c:calculate() _ ")" e:position!() { wrap(c, s, e) } /
+        s:position!() "(" _ l:logical() _ ")" e:position!() { wrap(l, s, e) } /
+        s:position!() "if" _ "(" _ c:logical() _ "," _ x:calculate() _ "," _ y:calculate() _ ")" e:position!() {
+            call(String::from("if"), vec![c, x, y], s, e)
+        } /
+        s:position!() n:function_name() _ "(" _ args:(calculate() ** (_ "," _)) _ ")" e:position!() {
+            call(n, args, s, e)
+        } /
+        s:position!() b:boolean() e:position!() { leaf(Expr::Boolean(b), s, e) } /
+        s:position!() t:string() e:position!() { leaf(Expr::String(t), s, e) } /
+        s:position!() v:variable() _ "(" _ args:(calculate() ++ (_ "," _)) _ ")" e:position!() {
+            leaf(Expr::NumberVariable(param_var(v, args)), s, e)
+        } /
+        s:position!() v:variable() e:position!() { leaf(Expr::NumberVariable(v), s, e) } /
+        s:position!() n:number() e:position!() { leaf(Expr::Number(Decimal::from_f64(n).unwrap()), s, e) }
 
     // 运算符
-    rule calculate() -> Expr = precedence!{
-        x:(@) _ "+" _ y:@ { Expr::Plus(Box::new(x), Box::new(y)) }
-        x:(@) _ "-" _ y:@ { Expr::Minus(Box::new(x), Box::new(y)) }
+    #[cache]
+    rule calculate() -> Spanned = precedence!{
+        x:(@) _ "+" _ y:@ { binary(Expr::Plus, x, y) }
+        x:(@) _ "-" _ y:@ { binary(Expr::Minus, x, y) }
         --
-        x:(@) _ "*" _ y:@ { Expr::Times(Box::new(x), Box::new(y)) }
-        x:(@) _ "/" _ y:@ { Expr::Divide(Box::new(x), Box::new(y)) }
+        x:(@) _ "*" _ y:@ { binary(Expr::Times, x, y) }
+        x:(@) _ "/" _ y:@ { binary(Expr::Divide, x, y) }
         --
-        "(" _ c:calculate() _ ")" { c }
-        v:variable() { Expr::NumberVariable(v) }
-        n:number() { Expr::Number(Decimal::from_f64(n).unwrap()) }
+        a:calculate_atom() { a }
     }
 
+    // 列表成员
+    rule member() -> (String, usize, usize) =
+        s:position!() v:(variable() / string()) e:position!() { (v, s, e) }
+    rule members() -> Vec<(String, usize, usize)> = "[" _ m:(member() ** (_ "," _)) _ ","? _ "]" { m }
+
+    // 成员运算
+    rule membership() -> Spanned =
+        x:calculate_atom() _ n:("not" _)? "in" _ m:members() end:position!() {
+            membership(x, m, n.is_some(), end)
+        }
+
+    // 逻辑表达式的操作数
+    rule logical_atom() -> Spanned =
+        membership() /
+        x:calculate() _ op:compare() _ y:calculate() { binary(op, x, y) } /
+        s:position!() b:boolean() e:position!() { leaf(Expr::Boolean(b), s, e) } /
+        s:position!() "if" _ "(" _ c:logical() _ "," _ x:logical() _ "," _ y:logical() _ ")" e:position!() {
+            call(String::from("if"), vec![c, x, y], s, e)
+        } /
+        s:position!() v:variable() e:position!() !(_ ['+' | '-' | '*' | '/' | '(']) {
+            leaf(Expr::BooleanVariable(v), s, e)
+        } /
+        s:position!() "(" _ l:logical() _ ")" e:position!() !(_ ['+' | '-' | '*' | '/']) {
+            wrap(l, s, e)
+        } /
+        // 数字表达式, 交由类型检查报告错误
+        calculate()
+
     // 逻辑运算符
-    rule logical() -> Expr = precedence!{
-        x:(@) _ "&&" _ y:@ { Expr::And(Box::new(x), Box::new(y)) }
-        x:(@) _ "||" _ y:@ { Expr::Or(Box::new(x), Box::new(y)) }
-        "!" _ v:@ { Expr::Not(Box::new(v)) }
+    #[cache]
+    rule logical() -> Spanned = precedence!{
+        x:(@) _ "&&" _ y:@ { binary(Expr::And, x, y) }
+        x:(@) _ "||" _ y:@ { binary(Expr::Or, x, y) }
         --
-        x:calculate() _ "==" _ y:calculate() { Expr::Equal(Box::new(x), Box::new(y)) }
-        x:calculate() _ "!=" _ y:calculate() { Expr::NotEqual(Box::new(x), Box::new(y)) }
-        x:calculate() _ "<"  _ y:calculate() { Expr::LessThan(Box::new(x), Box::new(y)) }
-        x:calculate() _ ">"  _ y:calculate() { Expr::GreaterThan(Box::new(x), Box::new(y)) }
-        x:calculate() _ "<=" _ y:calculate() { Expr::LessThanEqual(Box::new(x), Box::new(y)) }
-        x:calculate() _ ">=" _ y:calculate() { Expr::GreaterThanEqual(Box::new(x), Box::new(y)) }
+        s:position!() "!" _ v:@ { unary(Expr::Not, v, s) }
         --
-        b:boolean() { Expr::Boolean(b) }
-        v:variable() { Expr::BooleanVariable(v) }
-        "(" _ e:logical() _ ")" { e }
+        a:logical_atom() { a }
     }
 
-    pub(crate) rule parse() -> Expr = _ e:logical() _ { e }
+    pub(crate) rule parse() -> Spanned = _ e:logical() _ { e }
 });
 
-/// 解析布尔表达式
+/// 解析布尔表达式, 并返回表达式各节点的位置
 ///
 /// # 参数
 ///
 /// * `input` - 输入字符串
-pub(crate) fn parse(input: &str) -> Result<Expr> {
+pub(crate) fn parse(input: &str) -> Result<(Expr, ExprSpan)> {
     Ok(bool_parser::parse(input)?)
 }
 
@@ -186,7 +457,7 @@ mod tests {
     #[test]
     fn test_parse_number() {
         let input = "1.0+2.0==3.0";
-        let expr = parse(input).unwrap();
+        let (expr, _) = parse(input).unwrap();
         assert_eq!(
             expr,
             Expr::Equal(
@@ -202,7 +473,7 @@ mod tests {
     #[test]
     fn test_parse_number_variable() {
         let input = "a+b*c>10";
-        let expr = bool_parser::parse(input).unwrap();
+        let (expr, _) = bool_parser::parse(input).unwrap();
         assert_eq!(
             expr,
             Expr::GreaterThan(
@@ -222,7 +493,7 @@ mod tests {
     fn test_parse_boolean_variable() {
         let input = "a&&b||c&&!d";
 
-        let expr = bool_parser::parse(input).unwrap();
+        let (expr, _) = bool_parser::parse(input).unwrap();
 
         assert_eq!(
             expr,
@@ -240,4 +511,106 @@ mod tests {
             )
         )
     }
+
+    #[test]
+    fn test_parse_call() {
+        let input = "max(a, b * 2) >= if(c, 1, round(d, 1))";
+
+        let (expr, _) = bool_parser::parse(input).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::GreaterThanEqual(
+                Box::new(Expr::Call(
+                    String::from("max"),
+                    vec![
+                        Expr::NumberVariable(String::from("a")),
+                        Expr::Times(
+                            Box::new(Expr::NumberVariable(String::from("b"))),
+                            Box::new(Expr::Number(dec!(2)))
+                        )
+                    ]
+                )),
+                Box::new(Expr::Call(
+                    String::from("if"),
+                    vec![
+                        Expr::BooleanVariable(String::from("c")),
+                        Expr::Number(dec!(1)),
+                        Expr::Call(
+                            String::from("round"),
+                            vec![
+                                Expr::NumberVariable(String::from("d")),
+                                Expr::Number(dec!(1))
+                            ]
+                        )
+                    ]
+                ))
+            )
+        );
+        assert_eq!(
+            expr.get_var_keys().number_keys,
+            vec![String::from("a"), String::from("b"), String::from("d")]
+        );
+        assert_eq!(expr.get_var_keys().boolean_keys, vec![String::from("c")]);
+    }
+
+    #[test]
+    fn test_parse_membership() {
+        let input = "主词条 in [火元素伤害加成, 水元素伤害加成] && 套装 not in [战狂]";
+
+        let (expr, span) = parse(input).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::In(
+                    Box::new(Expr::StringVariable(String::from("主词条"))),
+                    vec![
+                        String::from("火元素伤害加成"),
+                        String::from("水元素伤害加成")
+                    ]
+                )),
+                Box::new(Expr::Not(Box::new(Expr::In(
+                    Box::new(Expr::StringVariable(String::from("套装"))),
+                    vec![String::from("战狂")]
+                ))))
+            )
+        );
+        let members = &span.children[0].children;
+        assert_eq!(&input[members[0].start..members[0].end], "主词条");
+        assert_eq!(&input[members[2].start..members[2].end], "水元素伤害加成");
+        assert_eq!(expr.get_var_keys().string_keys, vec!["主词条", "套装"]);
+    }
+
+    #[test]
+    fn test_parse_span() {
+        let input = "(a > 1) + 1\n  && !b";
+
+        let (expr, span) = parse(input).unwrap();
+
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::Plus(
+                    Box::new(Expr::GreaterThan(
+                        Box::new(Expr::NumberVariable(String::from("a"))),
+                        Box::new(Expr::Number(dec!(1)))
+                    )),
+                    Box::new(Expr::Number(dec!(1)))
+                )),
+                Box::new(Expr::Not(Box::new(Expr::BooleanVariable(String::from(
+                    "b"
+                )))))
+            )
+        );
+        let plus = &span.children[0];
+        assert_eq!(&input[plus.start..plus.end], "(a > 1) + 1");
+        assert_eq!(
+            &input[plus.children[0].start..plus.children[0].end],
+            "(a > 1)"
+        );
+        let not = &span.children[1];
+        assert_eq!(&input[not.start..not.end], "!b");
+        assert_eq!(&input[not.children[0].start..not.children[0].end], "b");
+    }
 }
@@ -1,12 +1,17 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use anyhow::bail;
 use anyhow::{Error, Result, anyhow};
 use rust_decimal::prelude::Decimal;
 use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::prelude::ToPrimitive;
 use tracing::debug;
 
 use crate::{
     ExprVar, ExprVarKey,
-    parse::{Expr, parse},
+    function::{Function, FunctionImpl, builtin_functions},
+    parse::{Expr, ExprSpan, parse, split_param_var},
 };
 
 /// 表达式结果
@@ -14,12 +19,57 @@ use crate::{
 pub enum ExprResult {
     Number(Decimal),
     Boolean(bool),
+    String(String),
+    /// 值未知, 按三值逻辑参与运算
+    Unknown,
+}
+
+impl ExprResult {
+    /// 数字结果转为浮点数, 其他结果返回 `None`
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ExprResult::Number(n) => n.to_f64(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExprResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExprResult::Number(n) => write!(f, "{}", n.normalize()),
+            ExprResult::Boolean(b) => write!(f, "{}", b),
+            ExprResult::String(s) => write!(f, "\"{}\"", s),
+            ExprResult::Unknown => write!(f, "未知"),
+        }
+    }
+}
+
+/// 表达式类型
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExprType {
+    Number,
+    Boolean,
+    String,
+}
+
+impl fmt::Display for ExprType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExprType::Number => write!(f, "数字"),
+            ExprType::Boolean => write!(f, "布尔"),
+            ExprType::String => write!(f, "字符串"),
+        }
+    }
 }
 
 /// 表达式解析器
 pub struct Parser {
     pub precision: u32,
     pub var_key: ExprVarKey,
+    pub functions: HashMap<String, Function>,
+    pub defines: HashMap<String, (ExprType, Expr)>,
+    pub string_values: HashMap<String, Vec<String>>, // 字符串变量的所有取值
 }
 
 impl Parser {
@@ -31,7 +81,186 @@ impl Parser {
     /// * `var_key` - 变量键
     pub fn new(precision: u32, var_key: ExprVarKey) -> Result<Self> {
         debug!("初始化解析器, 精度: {},变量: {:?}.", precision, var_key);
-        Ok(Self { precision, var_key })
+        Ok(Self {
+            precision,
+            var_key,
+            functions: builtin_functions(),
+            defines: HashMap::new(),
+            string_values: HashMap::new(),
+        })
+    }
+
+    /// 设置字符串变量
+    ///
+    /// 成员运算 `in [...]` 的列表成员和执行时的变量值都必须是变量的取值之一
+    ///
+    /// # 参数
+    ///
+    /// * `string_values` - 字符串变量名称和所有取值
+    pub fn set_string_vars(&mut self, string_values: HashMap<String, Vec<String>>) {
+        debug!("设置字符串变量: {:?}.", string_values.keys());
+        self.var_key.string_keys = string_values.keys().cloned().collect();
+        self.string_values = string_values;
+    }
+
+    /// 设置带参数的数字变量, 表达式中以 `名称(参数, ...)` 的形式引用, 参数的含义由使用方解释
+    ///
+    /// # 参数
+    ///
+    /// * `param_number_keys` - 带参数的数字变量名称
+    pub fn set_param_number_vars(&mut self, param_number_keys: Vec<String>) {
+        debug!("设置带参数的数字变量: {:?}.", param_number_keys);
+        self.var_key.param_number_keys = param_number_keys;
+    }
+
+    /// 判断是否为带参数的数字变量
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称, 包含参数
+    fn is_param_number_var(&self, name: &str) -> bool {
+        split_param_var(name)
+            .is_some_and(|(name, _)| self.var_key.param_number_keys.iter().any(|key| key == name))
+    }
+
+    /// 检查字符串变量的值是否有效
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 字符串变量名称
+    /// * `value` - 变量值
+    fn check_string_value(&self, name: &str, value: &str) -> Result<()> {
+        // 定义直接引用字符串变量时, 按被引用变量的取值检查
+        let name = match self.defines.get(name) {
+            Some((ExprType::String, Expr::StringVariable(var_name))) => var_name.as_str(),
+            _ => name,
+        };
+        match self.string_values.get(name) {
+            Some(values) if !values.iter().any(|v| v == value) => {
+                Err(self.invalid_member_error(name, value, values))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 设置命名定义
+    ///
+    /// 定义之间可以相互引用, 但不能循环引用. 定义会被展开, 之后解析的表达式可像变量一样引用定义名称
+    ///
+    /// # 参数
+    ///
+    /// * `defines` - 定义名称和定义表达式列表
+    pub fn set_defines(&mut self, defines: &[(String, String)]) -> Result<()> {
+        debug!("设置命名定义: {:?}.", defines);
+        let mut raw_defines = HashMap::new();
+        for (name, input) in defines {
+            if raw_defines.contains_key(name) {
+                bail!("定义 '{}' 重复", name);
+            }
+            if self.var_key.number_keys.contains(name)
+                || self.var_key.boolean_keys.contains(name)
+                || self.var_key.string_keys.contains(name)
+            {
+                bail!("定义 '{}' 与已有变量重名", name);
+            }
+            let (expr, span) =
+                parse(input).map_err(|e| anyhow!("解析定义 '{}' 失败: {}", name, e))?;
+            raw_defines.insert(name.clone(), (input.as_str(), expr, span));
+        }
+
+        self.defines.clear();
+        for (name, _) in defines {
+            self.resolve_define(name, &raw_defines, &mut vec![])?;
+        }
+        Ok(())
+    }
+
+    /// 按引用顺序检查并展开定义, 同时检查循环引用
+    ///
+    /// 定义的类型由类型检查得出, 因此被引用的定义需要先处理
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 定义名称
+    /// * `raw_defines` - 未展开的定义
+    /// * `stack` - 当前展开路径
+    fn resolve_define(
+        &mut self,
+        name: &str,
+        raw_defines: &HashMap<String, (&str, Expr, ExprSpan)>,
+        stack: &mut Vec<String>,
+    ) -> Result<()> {
+        if self.defines.contains_key(name) {
+            return Ok(());
+        }
+        if let Some(index) = stack.iter().position(|n| n == name) {
+            bail!(
+                "定义存在循环引用: {} -> {}",
+                stack[index..].join(" -> "),
+                name
+            );
+        }
+
+        let (input, expr, span) = &raw_defines[name];
+        stack.push(name.to_string());
+        let var_keys = expr.get_var_keys();
+        for key in var_keys
+            .boolean_keys
+            .iter()
+            .chain(var_keys.number_keys.iter())
+            .chain(var_keys.string_keys.iter())
+        {
+            if raw_defines.contains_key(key) {
+                self.resolve_define(key, raw_defines, stack)?;
+            }
+        }
+        stack.pop();
+
+        let define_err = |e: Error| anyhow!("定义 '{}' 错误: {}", name, e);
+        let expr_type = self.check_types(expr, span, input).map_err(define_err)?;
+        let expr = self.resolve_var_types(expr);
+        self.check_vars(&expr).map_err(define_err)?;
+        let expr = Self::replace_defines(&expr, &self.defines);
+        self.defines.insert(name.to_string(), (expr_type, expr));
+        Ok(())
+    }
+
+    /// 将表达式中引用的定义替换为定义表达式
+    ///
+    /// # 参数
+    ///
+    /// * `expr` - 表达式
+    /// * `defines` - 已展开的定义
+    fn replace_defines(expr: &Expr, defines: &HashMap<String, (ExprType, Expr)>) -> Expr {
+        expr.replace(&|e| match e {
+            Expr::NumberVariable(name)
+            | Expr::BooleanVariable(name)
+            | Expr::StringVariable(name) => defines.get(name).map(|(_, define)| define.clone()),
+            _ => None,
+        })
+    }
+
+    /// 检查变量是否为指定类型的定义
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称
+    /// * `expr_type` - 变量类型
+    pub(crate) fn is_define(&self, name: &str, expr_type: ExprType) -> bool {
+        self.defines
+            .get(name)
+            .is_some_and(|(define_type, _)| *define_type == expr_type)
+    }
+
+    /// 获取函数
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 函数名称
+    pub(crate) fn get_function(&self, name: &str) -> Result<&Function> {
+        self.functions
+            .get(name)
+            .ok_or_else(|| anyhow!("函数 '{}' 不受支持", name))
     }
 
     /// 检查表达式变量是否受支持
@@ -42,15 +271,18 @@ impl Parser {
     pub fn check_vars(&self, expr: &Expr) -> Result<()> {
         debug!("检查表达式变量: {:?}.", expr);
         match expr {
-            Expr::NumberVariable(name) => {
-                if !self.var_key.number_keys.contains(&name.to_string()) {
-                    bail!("数字变量 '{}' 不受支持", name);
-                }
+            Expr::NumberVariable(name)
+                if !self.var_key.number_keys.contains(name)
+                    && !self.is_param_number_var(name)
+                    && !self.is_define(name, ExprType::Number) =>
+            {
+                return Err(self.unknown_var_error(name, ExprType::Number));
             }
-            Expr::BooleanVariable(name) => {
-                if !self.var_key.boolean_keys.contains(&name.to_string()) {
-                    bail!("布尔变量 '{}' 不受支持", name);
-                }
+            Expr::BooleanVariable(name)
+                if !self.var_key.boolean_keys.contains(name)
+                    && !self.is_define(name, ExprType::Boolean) =>
+            {
+                return Err(self.unknown_var_error(name, ExprType::Boolean));
             }
             Expr::Plus(left, right)
             | Expr::Minus(left, right)
@@ -58,8 +290,6 @@ impl Parser {
             | Expr::Divide(left, right)
             | Expr::And(left, right)
             | Expr::Or(left, right)
-            | Expr::Equal(left, right)
-            | Expr::NotEqual(left, right)
             | Expr::LessThan(left, right)
             | Expr::GreaterThan(left, right)
             | Expr::LessThanEqual(left, right)
@@ -67,16 +297,80 @@ impl Parser {
                 self.check_vars(left)?;
                 self.check_vars(right)?;
             }
+            Expr::StringVariable(name)
+                if !self.var_key.string_keys.contains(name)
+                    && !self.is_define(name, ExprType::String) =>
+            {
+                return Err(self.unknown_var_error(name, ExprType::String));
+            }
+            Expr::Equal(left, right) | Expr::NotEqual(left, right) => {
+                self.check_vars(left)?;
+                self.check_vars(right)?;
+                // 字符串变量与字符串常量比较时, 常量必须是变量的取值之一
+                if let (Expr::StringVariable(name), Expr::String(value))
+                | (Expr::String(value), Expr::StringVariable(name)) =
+                    (left.as_ref(), right.as_ref())
+                {
+                    self.check_string_value(name, value)?;
+                }
+            }
+            Expr::Not(expr) => self.check_vars(expr)?,
+            Expr::In(expr, members) => {
+                self.check_vars(expr)?;
+                if let Expr::StringVariable(name) = expr.as_ref() {
+                    for member in members {
+                        self.check_string_value(name, member)?;
+                    }
+                }
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    self.check_vars(arg)?;
+                }
+            }
             _ => {}
         }
         Ok(())
     }
 
+    /// 解析表达式, 并展开其中引用的定义
+    ///
+    /// # 参数
+    ///
+    /// * `input` - 表达式字符串
     pub fn parse(&self, input: &str) -> Result<Expr> {
+        let (expr, expr_type) = self.parse_typed(input, ExprType::Boolean)?;
+        if expr_type != ExprType::Boolean {
+            bail!("表达式结果应为布尔类型, 实际为{}类型", expr_type);
+        }
+        Ok(expr)
+    }
+
+    /// 解析任意类型的表达式, 并展开其中引用的定义
+    ///
+    /// # 参数
+    ///
+    /// * `input` - 表达式字符串
+    /// * `context` - 整个表达式只有一个变量时, 变量同时属于多种类型时按该类型解析
+    ///
+    /// # 返回值
+    ///
+    /// 表达式及其结果类型
+    pub fn parse_typed(&self, input: &str, context: ExprType) -> Result<(Expr, ExprType)> {
         debug!("解析表达式: '{}'.", input);
-        let expr: Expr = parse(input)?;
+        let (mut expr, span) = parse(input)?;
+        // 语法将单独的变量解析为布尔变量, 例如 `暴击率` 既是数字变量也是布尔变量
+        if let Expr::BooleanVariable(name) = &expr {
+            expr = match context {
+                ExprType::Number => Expr::NumberVariable(name.clone()),
+                ExprType::Boolean => Expr::BooleanVariable(name.clone()),
+                ExprType::String => Expr::StringVariable(name.clone()),
+            };
+        }
+        let expr_type = self.check_types(&expr, &span, input)?;
+        let expr = self.resolve_var_types(&expr);
         self.check_vars(&expr)?;
-        Ok(expr)
+        Ok((Self::replace_defines(&expr, &self.defines), expr_type))
     }
 
     /// 执行表达式
@@ -86,36 +380,43 @@ impl Parser {
     /// * `expr` - 表达式
     /// * `expr_var` - 表达式变量
     pub fn exec(&self, expr: &Expr, expr_var: &ExprVar) -> Result<ExprResult> {
-        debug!(
-            "执行表达式: {:#?}, 数字变量: {:?}, 布尔变量: {:?}.",
-            expr, expr_var.number_vars, expr_var.boolean_vars
-        );
+        debug!("执行表达式: {}.", expr);
 
         let err_msg = |m: &str| -> Error { anyhow!("无效的操作数类型: {}", m) };
 
         let arithmetic_op = |op_name: &str,
-                             op: fn(Decimal, Decimal) -> Decimal,
+                             op: fn(Decimal, Decimal) -> Result<Decimal>,
                              left: &Expr,
                              right: &Expr|
          -> Result<ExprResult> {
             match (self.exec(left, expr_var)?, self.exec(right, expr_var)?) {
-                (ExprResult::Number(l), ExprResult::Number(r)) => Ok(ExprResult::Number(op(l, r))),
+                (ExprResult::Number(l), ExprResult::Number(r)) => Ok(ExprResult::Number(op(l, r)?)),
+                (ExprResult::Unknown, _) | (_, ExprResult::Unknown) => Ok(ExprResult::Unknown),
                 _ => Err(err_msg(op_name)),
             }
         };
 
-        let logical_op = |op_name: &str,
-                          op: fn(bool, bool) -> bool,
-                          left: &Expr,
-                          right: &Expr|
-         -> Result<ExprResult> {
-            match (self.exec(left, expr_var)?, self.exec(right, expr_var)?) {
-                (ExprResult::Boolean(l), ExprResult::Boolean(r)) => {
-                    Ok(ExprResult::Boolean(op(l, r)))
+        // 三值逻辑: 左侧已能决定结果时不再计算右侧, 假 && 未知 = 假, 真 || 未知 = 真
+        let logical_op =
+            |op_name: &str, short: bool, left: &Expr, right: &Expr| -> Result<ExprResult> {
+                let left_result = self.exec(left, expr_var)?;
+                if left_result == ExprResult::Boolean(short) {
+                    return Ok(left_result);
                 }
-                _ => Err(err_msg(op_name)),
-            }
-        };
+                match (left_result, self.exec(right, expr_var)?) {
+                    (
+                        ExprResult::Boolean(_),
+                        right_result @ (ExprResult::Boolean(_) | ExprResult::Unknown),
+                    ) => Ok(right_result),
+                    (ExprResult::Unknown, ExprResult::Boolean(r)) if r == short => {
+                        Ok(ExprResult::Boolean(r))
+                    }
+                    (ExprResult::Unknown, ExprResult::Boolean(_) | ExprResult::Unknown) => {
+                        Ok(ExprResult::Unknown)
+                    }
+                    _ => Err(err_msg(op_name)),
+                }
+            };
 
         let comparison_op = |op_name: &str,
                              op: fn(Decimal, Decimal) -> bool,
@@ -127,18 +428,46 @@ impl Parser {
                     l.round_dp(self.precision),
                     r.round_dp(self.precision),
                 ))),
+                (ExprResult::Unknown, _) | (_, ExprResult::Unknown) => Ok(ExprResult::Unknown),
                 _ => Err(err_msg(op_name)),
             }
         };
 
+        let equality_op =
+            |op_name: &str, negate: bool, left: &Expr, right: &Expr| -> Result<ExprResult> {
+                match (self.exec(left, expr_var)?, self.exec(right, expr_var)?) {
+                    (ExprResult::Number(l), ExprResult::Number(r)) => Ok(ExprResult::Boolean(
+                        l.round_dp(self.precision).eq(&r.round_dp(self.precision)) != negate,
+                    )),
+                    (ExprResult::Boolean(l), ExprResult::Boolean(r)) => {
+                        Ok(ExprResult::Boolean((l == r) != negate))
+                    }
+                    (ExprResult::String(l), ExprResult::String(r)) => {
+                        Ok(ExprResult::Boolean((l == r) != negate))
+                    }
+                    (ExprResult::Unknown, _) | (_, ExprResult::Unknown) => Ok(ExprResult::Unknown),
+                    _ => Err(err_msg(op_name)),
+                }
+            };
+
         let expr_result = match expr {
             // 基础
             Expr::Number(n) => Ok(ExprResult::Number(*n)),
             Expr::Boolean(b) => Ok(ExprResult::Boolean(*b)),
+            Expr::String(s) => Ok(ExprResult::String(s.clone())),
             // 变量
+            Expr::NumberVariable(name)
+            | Expr::BooleanVariable(name)
+            | Expr::StringVariable(name)
+                if expr_var.unknown_vars.contains(name) =>
+            {
+                Ok(ExprResult::Unknown)
+            }
             Expr::NumberVariable(name) => {
                 if let Some(n) = expr_var.number_vars.get(name) {
-                    return Ok(ExprResult::Number(Decimal::from_f32(*n).unwrap()));
+                    return Decimal::from_f32(*n)
+                        .map(ExprResult::Number)
+                        .ok_or_else(|| anyhow!("数字变量 '{}' 的值无效: {}", name, n));
                 }
                 Err(anyhow!("数字变量 '{}' 不受支持", name))
             }
@@ -148,16 +477,29 @@ impl Parser {
                 }
                 Err(anyhow!("布尔变量 '{}' 不受支持", name))
             }
+            Expr::StringVariable(name) => {
+                if let Some(value) = expr_var.string_vars.get(name) {
+                    self.check_string_value(name, value)?;
+                    return Ok(ExprResult::String(value.clone()));
+                }
+                Err(anyhow!("字符串变量 '{}' 不受支持", name))
+            }
             // 数字运算
-            Expr::Plus(left, right) => arithmetic_op("+", |l, r| l + r, left, right),
-            Expr::Minus(left, right) => arithmetic_op("-", |l, r| l - r, left, right),
-            Expr::Times(left, right) => arithmetic_op("*", |l, r| l * r, left, right),
-            Expr::Divide(left, right) => arithmetic_op("/", |l, r| l / r, left, right),
+            Expr::Plus(left, right) => arithmetic_op("+", |l, r| Ok(l + r), left, right),
+            Expr::Minus(left, right) => arithmetic_op("-", |l, r| Ok(l - r), left, right),
+            Expr::Times(left, right) => arithmetic_op("*", |l, r| Ok(l * r), left, right),
+            Expr::Divide(left, right) => arithmetic_op(
+                "/",
+                |l, r| l.checked_div(r).ok_or_else(|| anyhow!("除数不能为零")),
+                left,
+                right,
+            ),
             // 逻辑运算
-            Expr::And(left, right) => logical_op("&&", |l, r| l && r, left, right),
-            Expr::Or(left, right) => logical_op("||", |l, r| l || r, left, right),
+            Expr::And(left, right) => logical_op("&&", false, left, right),
+            Expr::Or(left, right) => logical_op("||", true, left, right),
             Expr::Not(expr) => match self.exec(expr, expr_var)? {
                 ExprResult::Boolean(b) => Ok(ExprResult::Boolean(!b)),
+                ExprResult::Unknown => Ok(ExprResult::Unknown),
                 _ => Err(err_msg("!")),
             },
             // 比较运算
@@ -165,41 +507,69 @@ impl Parser {
             Expr::GreaterThan(left, right) => comparison_op(">", |l, r| l > r, left, right),
             Expr::LessThanEqual(left, right) => comparison_op("<=", |l, r| l <= r, left, right),
             Expr::GreaterThanEqual(left, right) => comparison_op(">=", |l, r| l >= r, left, right),
-            Expr::Equal(left, right) => {
-                let left_result = self.exec(left, expr_var)?;
-                let right_result = self.exec(right, expr_var)?;
-                match (left_result, right_result) {
-                    (ExprResult::Number(l), ExprResult::Number(r)) => Ok(ExprResult::Boolean(
-                        l.round_dp(self.precision).eq(&r.round_dp(self.precision)),
-                    )),
-                    (ExprResult::Boolean(l), ExprResult::Boolean(r)) => {
-                        Ok(ExprResult::Boolean(l == r))
+            Expr::Equal(left, right) => equality_op("==", false, left, right),
+            Expr::NotEqual(left, right) => equality_op("!=", true, left, right),
+            // 函数调用
+            Expr::Call(name, args) => self.exec_call(name, args, expr_var),
+            // 成员运算
+            Expr::In(expr, members) => match self.exec(expr, expr_var)? {
+                ExprResult::String(value) => Ok(ExprResult::Boolean(members.contains(&value))),
+                ExprResult::Unknown => Ok(ExprResult::Unknown),
+                _ => Err(err_msg("in")),
+            },
+        };
+        debug!("表达式结果: {:?}.", expr_result);
+        expr_result
+    }
+
+    /// 执行函数调用
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 函数名称
+    /// * `args` - 函数参数
+    /// * `expr_var` - 表达式变量
+    fn exec_call(&self, name: &str, args: &[Expr], expr_var: &ExprVar) -> Result<ExprResult> {
+        let function = self.get_function(name)?;
+        match function.imp {
+            FunctionImpl::If => match self.exec(&args[0], expr_var)? {
+                ExprResult::Boolean(true) => self.exec(&args[1], expr_var),
+                ExprResult::Boolean(false) => self.exec(&args[2], expr_var),
+                ExprResult::Unknown => Ok(ExprResult::Unknown),
+                _ => Err(anyhow!("无效的操作数类型: {}", name)),
+            },
+            // 任一参数未知时结果未知
+            FunctionImpl::String(f) => {
+                let mut values = vec![];
+                for arg in args {
+                    match self.exec(arg, expr_var)? {
+                        ExprResult::String(s) => values.push(s),
+                        ExprResult::Unknown => return Ok(ExprResult::Unknown),
+                        _ => bail!("无效的操作数类型: {}", name),
                     }
-                    _ => Err(err_msg("==")),
                 }
+                let values: Vec<&str> = values.iter().map(String::as_str).collect();
+                Ok(ExprResult::Boolean(f(&values)))
             }
-            Expr::NotEqual(left, right) => {
-                let left_result = self.exec(left, expr_var)?;
-                let right_result = self.exec(right, expr_var)?;
-                match (left_result, right_result) {
-                    (ExprResult::Number(l), ExprResult::Number(r)) => Ok(ExprResult::Boolean(
-                        l.round_dp(self.precision).ne(&r.round_dp(self.precision)),
-                    )),
-                    (ExprResult::Boolean(l), ExprResult::Boolean(r)) => {
-                        Ok(ExprResult::Boolean(l != r))
+            FunctionImpl::Number(f) => {
+                let mut values = vec![];
+                for arg in args {
+                    match self.exec(arg, expr_var)? {
+                        ExprResult::Number(n) => values.push(n),
+                        ExprResult::Unknown => return Ok(ExprResult::Unknown),
+                        _ => bail!("无效的操作数类型: {}", name),
                     }
-                    _ => Err(err_msg("!=")),
                 }
+                Ok(ExprResult::Number(f(&values)?))
             }
-        };
-        debug!("表达式结果: {:?}.", expr_result);
-        expr_result
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::split_param_args;
 
     const PRECISION: u32 = 2;
 
@@ -257,6 +627,21 @@ mod tests {
         assert_eq!(output, ExprResult::Boolean(true))
     }
 
+    #[test]
+    fn test_exec_invalid_number_variable() {
+        let var_key = ExprVarKey::new(vec![], vec![String::from("评分")]);
+        let parser = Parser::new(PRECISION, var_key).unwrap();
+        let expr = parser.parse("评分 > 1").unwrap();
+        for value in [f32::INFINITY, f32::NAN] {
+            let mut expr_var = ExprVar::default();
+            expr_var.number_vars.insert(String::from("评分"), value);
+            assert_eq!(
+                parser.exec(&expr, &expr_var).unwrap_err().to_string(),
+                format!("数字变量 '评分' 的值无效: {}", value)
+            );
+        }
+    }
+
     #[test]
     fn test_exec_boolean_variable() {
         let input = "a&&b||c&&!d";
@@ -285,6 +670,178 @@ mod tests {
         assert_eq!(output, ExprResult::Boolean(true))
     }
 
+    #[test]
+    fn test_exec_membership() {
+        let input = "主词条 in [火元素伤害加成, 水元素伤害加成] && 部位 not in [生之花, 死之羽]";
+
+        let mut parser = Parser::new(PRECISION, ExprVarKey::default()).unwrap();
+        parser.set_string_vars(HashMap::from([
+            (
+                String::from("主词条"),
+                vec![
+                    String::from("火元素伤害加成"),
+                    String::from("水元素伤害加成"),
+                    String::from("暴击率"),
+                ],
+            ),
+            (
+                String::from("部位"),
+                vec![
+                    String::from("生之花"),
+                    String::from("死之羽"),
+                    String::from("空之杯"),
+                ],
+            ),
+        ]));
+        let expr = parser.parse(input).unwrap();
+
+        let mut expr_var = ExprVar::default();
+        expr_var
+            .string_vars
+            .insert(String::from("主词条"), String::from("水元素伤害加成"));
+        expr_var
+            .string_vars
+            .insert(String::from("部位"), String::from("空之杯"));
+        assert_eq!(
+            parser.exec(&expr, &expr_var).unwrap(),
+            ExprResult::Boolean(true)
+        );
+
+        expr_var
+            .string_vars
+            .insert(String::from("主词条"), String::from("暴击率"));
+        assert_eq!(
+            parser.exec(&expr, &expr_var).unwrap(),
+            ExprResult::Boolean(false)
+        );
+
+        expr_var
+            .string_vars
+            .insert(String::from("主词条"), String::from("火伤"));
+        let err = parser.exec(&expr, &expr_var).unwrap_err();
+        assert!(err.to_string().starts_with("'火伤' 不是有效的主词条"));
+
+        let err = parser.parse("主词条 in [火元素伤害, 暴击率]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'火元素伤害' 不是有效的主词条, 是否想输入: '火元素伤害加成', '水元素伤害加成'"
+        );
+
+        let err = parser.parse("主词条 > 3").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 1 列 '主词条': 运算符 '>' 需要数字类型的操作数, 实际为字符串类型, '主词条' 是字符串变量"
+        );
+    }
+
+    #[test]
+    fn test_exec_string() {
+        let mut parser = Parser::new(PRECISION, ExprVarKey::default()).unwrap();
+        parser.set_string_vars(HashMap::from([
+            (
+                String::from("主词条"),
+                vec![String::from("暴击率"), String::from("暴击伤害")],
+            ),
+            (
+                String::from("套装"),
+                vec![String::from("绝缘之旗印"), String::from("追忆之注连")],
+            ),
+        ]));
+
+        let mut expr_var = ExprVar::default();
+        expr_var
+            .string_vars
+            .insert(String::from("主词条"), String::from("暴击伤害"));
+        expr_var
+            .string_vars
+            .insert(String::from("套装"), String::from("追忆之注连"));
+
+        for (input, expected) in [
+            ("主词条 == \"暴击伤害\"", true),
+            ("\"暴击率\" != 主词条", true),
+            ("主词条 == 套装", false),
+            (
+                "contains(套装, \"注连\") && starts_with(主词条, \"暴击\")",
+                true,
+            ),
+            ("starts_with(套装, \"绝缘\")", false),
+            ("套装 in [\"追忆之注连\", 绝缘之旗印]", true),
+        ] {
+            let expr = parser.parse(input).unwrap();
+            assert_eq!(
+                parser.exec(&expr, &expr_var).unwrap(),
+                ExprResult::Boolean(expected),
+                "{}",
+                input
+            );
+        }
+
+        let err = parser.parse("主词条 == \"暴击\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'暴击' 不是有效的主词条, 是否想输入: '暴击率', '暴击伤害'"
+        );
+
+        let err = parser.parse("主词条 == 1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 1 列 '主词条 == 1': 运算符 '==' 两侧类型不一致, 左侧为字符串类型, 右侧为数字类型"
+        );
+    }
+
+    #[test]
+    fn test_exec_unknown() {
+        let var_key = ExprVarKey::new(
+            vec![String::from("战狂")],
+            vec![String::from("暴击率"), String::from("星级")],
+        );
+        let mut parser = Parser::new(PRECISION, var_key).unwrap();
+        parser.set_string_vars(HashMap::from([(
+            String::from("主词条"),
+            vec![String::from("暴击率"), String::from("暴击伤害")],
+        )]));
+
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("星级"), 5.0);
+        expr_var.boolean_vars.insert(String::from("战狂"), true);
+        expr_var.unknown_vars.insert(String::from("暴击率"));
+        expr_var.unknown_vars.insert(String::from("主词条"));
+
+        for (input, expected) in [
+            ("星级 == 4 && 暴击率 > 3", ExprResult::Boolean(false)),
+            ("暴击率 > 3 && 星级 == 4", ExprResult::Boolean(false)),
+            ("战狂 || 暴击率 > 3", ExprResult::Boolean(true)),
+            ("暴击率 > 3 || 战狂", ExprResult::Boolean(true)),
+            ("战狂 && 暴击率 > 3", ExprResult::Unknown),
+            ("!(暴击率 * 2 > 3) || !战狂", ExprResult::Unknown),
+            ("max(星级, 暴击率) >= 5", ExprResult::Unknown),
+            ("if(战狂, 星级, 暴击率) == 5", ExprResult::Boolean(true)),
+            (
+                "主词条 in [暴击率] || starts_with(主词条, \"暴击\")",
+                ExprResult::Unknown,
+            ),
+        ] {
+            let expr = parser.parse(input).unwrap();
+            assert_eq!(
+                parser.exec(&expr, &expr_var).unwrap(),
+                expected,
+                "{}",
+                input
+            );
+        }
+
+        // 未知变量之外的字符串值依然需要校验
+        expr_var.unknown_vars.clear();
+        expr_var
+            .string_vars
+            .insert(String::from("主词条"), String::new());
+        let expr = parser.parse("主词条 == \"暴击率\"").unwrap();
+        assert_eq!(
+            parser.exec(&expr, &expr_var).unwrap_err().to_string(),
+            "'' 不是有效的主词条"
+        );
+    }
+
     #[test]
     // 测试精度
     fn test_precision() {
@@ -306,4 +863,212 @@ mod tests {
 
         assert_eq!(output, ExprResult::Boolean(true));
     }
+
+    #[test]
+    fn test_exec_call() {
+        let var_key = ExprVarKey::new(
+            vec![String::from("c")],
+            vec![String::from("a"), String::from("b")],
+        );
+        let parser = Parser::new(PRECISION, var_key).unwrap();
+
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("a"), 5.0);
+        expr_var.number_vars.insert(String::from("b"), -2.5);
+        expr_var.boolean_vars.insert(String::from("c"), false);
+
+        let cases = [
+            ("max(a, b, 3) == 5", true),
+            ("min(a, b) == -2.5", true),
+            ("abs(b) == 2.5", true),
+            ("round(b, 0) == -3", true),
+            ("floor(b) == -3 && ceil(b) == -2", true),
+            ("clamp(a, 0, 4) == 4", true),
+            ("if(c, a, b) == b", true),
+            ("if(a > b, c, true)", false),
+        ];
+        for (input, expected) in cases {
+            let expr = parser.parse(input).unwrap();
+            let output = parser.exec(&expr, &expr_var).unwrap();
+            assert_eq!(output, ExprResult::Boolean(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_call_check() {
+        let parser = Parser::new(PRECISION, ExprVarKey::default()).unwrap();
+
+        let err = parser.parse("abs(1, 2) > 0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 1 列 'abs(1, 2)': 函数 'abs' 需要 1 个参数, 实际为 2 个"
+        );
+
+        let err = parser.parse("max() > 0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 1 列 'max()': 函数 'max' 需要至少 1 个参数, 实际为 0 个"
+        );
+
+        let err = parser.parse("pow(2, 3) > 0").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "第 1 行第 1 列 'pow(2, 3)': 函数 'pow' 不受支持"
+        );
+    }
+
+    #[test]
+    fn test_parse_param_var() {
+        let mut parser = Parser::new(PRECISION, ExprVarKey::default()).unwrap();
+        parser.set_param_number_vars(vec![String::from("达标概率")]);
+
+        let expr = parser.parse("达标概率(30.0) >= 0.5").unwrap();
+        let var_keys = expr.get_var_keys();
+        assert_eq!(var_keys.number_keys, vec![String::from("达标概率(30)")]);
+        assert_eq!(split_param_var("达标概率(30)"), Some(("达标概率", "30")));
+
+        let expr_var = ExprVar {
+            number_vars: HashMap::from([(String::from("达标概率(30)"), 0.6)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            parser.exec(&expr, &expr_var).unwrap(),
+            ExprResult::Boolean(true)
+        );
+
+        let err = parser.parse("期望(30) > 0").unwrap_err();
+        assert!(err.to_string().contains("数字变量 '期望(30)' 不受支持"));
+    }
+
+    #[test]
+    fn test_parse_param_var_args() {
+        let var_key = ExprVarKey::new(vec![], vec![String::from("a"), String::from("b")]);
+        let mut parser = Parser::new(PRECISION, var_key).unwrap();
+        parser.set_param_number_vars(vec![String::from("排名")]);
+
+        // 参数按统一格式写入变量名称
+        let expr = parser.parse("排名(a*2+b,部位, (a > 1.50)) <= 3").unwrap();
+        let var_keys = expr.get_var_keys();
+        let name = "排名(a * 2 + b, 部位, a > 1.5)";
+        assert_eq!(var_keys.number_keys, vec![String::from(name)]);
+
+        let (key, params) = split_param_var(name).unwrap();
+        assert_eq!(key, "排名");
+        assert_eq!(
+            split_param_args(params),
+            vec!["a * 2 + b", "部位", "a > 1.5"]
+        );
+        assert_eq!(
+            split_param_args("max(a, b), \"x,y\""),
+            vec!["max(a, b)", "\"x,y\""]
+        );
+
+        let (expr, expr_type) = parser.parse_typed("a * 2 + b", ExprType::Boolean).unwrap();
+        assert_eq!(expr_type, ExprType::Number);
+        let expr_var = ExprVar {
+            number_vars: HashMap::from([(String::from("a"), 1.0), (String::from("b"), 2.0)]),
+            ..Default::default()
+        };
+        assert_eq!(parser.exec(&expr, &expr_var).unwrap().as_f64(), Some(4.0));
+    }
+
+    #[test]
+    fn test_parse_defines() {
+        let var_key = ExprVarKey::new(
+            vec![String::from("c")],
+            vec![String::from("a"), String::from("b")],
+        );
+        let mut parser = Parser::new(PRECISION, var_key).unwrap();
+        parser
+            .set_defines(&[
+                (String::from("总分"), String::from("分 + b")),
+                (String::from("分"), String::from("a * 2")),
+                (String::from("有c"), String::from("c && 分 > 0")),
+            ])
+            .unwrap();
+        assert_eq!(parser.defines["总分"].0, ExprType::Number);
+        assert_eq!(parser.defines["有c"].0, ExprType::Boolean);
+
+        let expr = parser.parse("有c && 总分 >= 10").unwrap();
+        let var_keys = expr.get_var_keys();
+        assert_eq!(var_keys.boolean_keys, vec![String::from("c")]);
+        assert_eq!(
+            var_keys.number_keys,
+            vec![String::from("a"), String::from("a"), String::from("b")]
+        );
+
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("a"), 4.0);
+        expr_var.number_vars.insert(String::from("b"), 2.0);
+        expr_var.boolean_vars.insert(String::from("c"), true);
+        let output = parser.exec(&expr, &expr_var).unwrap();
+        assert_eq!(output, ExprResult::Boolean(true));
+
+        let err = parser.parse("总分").unwrap_err();
+        assert_eq!(err.to_string(), "表达式结果应为布尔类型, 实际为数字类型");
+    }
+
+    #[test]
+    fn test_parse_string_defines() {
+        let var_key = ExprVarKey::new(vec![], vec![String::from("星级")]);
+        let mut parser = Parser::new(PRECISION, var_key).unwrap();
+        parser.set_string_vars(HashMap::from([(
+            String::from("套装"),
+            vec![String::from("角斗士的终幕礼"), String::from("绝缘之旗印")],
+        )]));
+        parser
+            .set_defines(&[
+                (
+                    String::from("是角斗士"),
+                    String::from("我的套装 == \"角斗士的终幕礼\""),
+                ),
+                (String::from("我的套装"), String::from("套装")),
+            ])
+            .unwrap();
+        assert_eq!(parser.defines["我的套装"].0, ExprType::String);
+
+        let expr = parser
+            .parse("星级 == 5 && 我的套装 in [角斗士的终幕礼] && 是角斗士")
+            .unwrap();
+        let var_keys = expr.get_var_keys();
+        assert_eq!(
+            var_keys.string_keys,
+            vec![String::from("套装"), String::from("套装")]
+        );
+
+        let mut expr_var = ExprVar::default();
+        expr_var.number_vars.insert(String::from("星级"), 5.0);
+        expr_var
+            .string_vars
+            .insert(String::from("套装"), String::from("角斗士的终幕礼"));
+        let output = parser.exec(&expr, &expr_var).unwrap();
+        assert_eq!(output, ExprResult::Boolean(true));
+
+        // 成员按被引用变量的取值检查
+        let err = parser.parse("我的套装 in [角斗士]").unwrap_err();
+        assert!(
+            err.to_string().starts_with("'角斗士' 不是有效的套装"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_parse_defines_cycle() {
+        let var_key = ExprVarKey::new(vec![], vec![String::from("a")]);
+        let mut parser = Parser::new(PRECISION, var_key).unwrap();
+        let err = parser
+            .set_defines(&[
+                (String::from("x"), String::from("a + y")),
+                (String::from("y"), String::from("z * 2")),
+                (String::from("z"), String::from("x - 1")),
+            ])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "定义存在循环引用: x -> y -> z -> x");
+
+        let err = parser
+            .set_defines(&[(String::from("a"), String::from("1"))])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "定义 'a' 与已有变量重名");
+    }
 }
@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use anyhow::{Error, anyhow};
+
+use crate::{ExprType, Parser};
+
+/// 最多给出的建议个数
+const MAX_SUGGESTIONS: usize = 3;
+
+/// 计算两个字符串按字符计算的编辑距离
+///
+/// # 参数
+///
+/// * `a` - 字符串 a
+/// * `b` - 字符串 b
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.chars().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// 计算两个字符串共有的字符个数(去重)
+///
+/// 中文名称往往只差一两个字, 或字的顺序不同(例如 "伤害暴击"), 共有字符能弥补编辑距离的不足
+///
+/// # 参数
+///
+/// * `a` - 字符串 a
+/// * `b` - 字符串 b
+fn shared_chars(a: &str, b: &str) -> usize {
+    let b: HashSet<char> = b.chars().collect();
+    a.chars()
+        .collect::<HashSet<char>>()
+        .intersection(&b)
+        .count()
+}
+
+/// 从候选名称中找出与名称最相近的几个
+///
+/// 编辑距离不超过名称长度的一半, 或共有字符不少于名称字符数一半的候选才会被采纳,
+/// 按编辑距离减去共有字符数从小到大排序
+///
+/// # 参数
+///
+/// * `name` - 名称
+/// * `candidates` - 候选名称
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let len = name.chars().count();
+    let mut scored: Vec<(usize, usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| {
+            let distance = edit_distance(name, candidate);
+            let shared = shared_chars(name, candidate);
+            (distance, shared, candidate)
+        })
+        .filter(|(distance, shared, _)| *distance <= (len / 2).max(1) || *shared * 2 >= len.max(2))
+        .collect();
+    scored.sort_by_key(|(distance, shared, candidate)| {
+        (*distance as isize - *shared as isize, *distance, *candidate)
+    });
+    scored.dedup_by(|a, b| a.2 == b.2);
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, candidate)| candidate)
+        .collect()
+}
+
+impl Parser {
+    /// 获取指定类型的所有变量和定义名称
+    ///
+    /// # 参数
+    ///
+    /// * `expr_type` - 类型
+    pub(crate) fn var_names(&self, expr_type: ExprType) -> impl Iterator<Item = &str> {
+        let keys = match expr_type {
+            ExprType::Number => &self.var_key.number_keys,
+            ExprType::Boolean => &self.var_key.boolean_keys,
+            ExprType::String => &self.var_key.string_keys,
+        };
+        let defines = self
+            .defines
+            .iter()
+            .filter(move |(_, (define_type, _))| *define_type == expr_type)
+            .map(|(name, _)| name.as_str());
+        keys.iter().map(String::as_str).chain(defines)
+    }
+
+    /// 构造列表成员无效的错误, 并附带修改建议
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 字符串变量名称
+    /// * `member` - 列表成员
+    /// * `values` - 字符串变量的所有取值
+    pub(crate) fn invalid_member_error(
+        &self,
+        name: &str,
+        member: &str,
+        values: &[String],
+    ) -> Error {
+        let mut message = format!("'{}' 不是有效的{}", member, name);
+        let suggestions = suggest(member, values.iter().map(String::as_str));
+        if !suggestions.is_empty() {
+            let suggestions: Vec<String> = suggestions.iter().map(|s| format!("'{}'", s)).collect();
+            message.push_str(&format!(", 是否想输入: {}", suggestions.join(", ")));
+        }
+        anyhow!(message)
+    }
+
+    /// 构造变量不受支持的错误, 并附带修改建议
+    ///
+    /// 名称属于另一种类型时给出提示, 否则列出最相近的变量名称
+    ///
+    /// # 参数
+    ///
+    /// * `name` - 变量名称
+    /// * `expr_type` - 变量所在上下文的类型
+    pub(crate) fn unknown_var_error(&self, name: &str, expr_type: ExprType) -> Error {
+        let mut message = format!("{}变量 '{}' 不受支持", expr_type, name);
+        let other_type = [ExprType::Number, ExprType::Boolean, ExprType::String]
+            .into_iter()
+            .filter(|other_type| *other_type != expr_type)
+            .find(|other_type| self.var_names(*other_type).any(|other| other == name));
+        if let Some(other_type) = other_type {
+            message.push_str(&format!(", '{}' 是{}变量", name, other_type));
+            return anyhow!(message);
+        }
+
+        let suggestions = suggest(name, self.var_names(expr_type));
+        if !suggestions.is_empty() {
+            let suggestions: Vec<String> = suggestions.iter().map(|s| format!("'{}'", s)).collect();
+            message.push_str(&format!(", 是否想输入: {}", suggestions.join(", ")));
+        }
+        anyhow!(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ExprVarKey;
+
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("暴击伤", "暴击伤害"), 1);
+        assert_eq!(edit_distance("暴击率", "暴击伤害"), 2);
+        assert_eq!(edit_distance("", "战狂"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let candidates = ["暴击率", "暴击伤害", "攻击力", "攻击力百分比", "生命值"];
+        assert_eq!(suggest("暴击伤", candidates), vec!["暴击伤害", "暴击率"]);
+        assert_eq!(suggest("伤害暴击", candidates), vec!["暴击伤害", "暴击率"]);
+        assert_eq!(
+            suggest("攻击力百分", candidates),
+            vec!["攻击力百分比", "攻击力"]
+        );
+        assert!(suggest("元素充能效率", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_var_error() {
+        let var_key = ExprVarKey::new(
+            vec![String::from("战狂"), String::from("已锁定")],
+            vec![
+                String::from("暴击率"),
+                String::from("暴击伤害"),
+                String::from("星级"),
+            ],
+        );
+        let parser = Parser::new(2, var_key).unwrap();
+
+        let err = parser.parse("暴击伤 > 10").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "数字变量 '暴击伤' 不受支持, 是否想输入: '暴击伤害', '暴击率'"
+        );
+
+        let err = parser.parse("战狂的 && 星级 == 5").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "布尔变量 '战狂的' 不受支持, 是否想输入: '战狂'"
+        );
+
+        let err = parser.unknown_var_error("星级", ExprType::Boolean);
+        assert_eq!(
+            err.to_string(),
+            "布尔变量 '星级' 不受支持, '星级' 是数字变量"
+        );
+    }
+}
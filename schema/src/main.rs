@@ -4,7 +4,7 @@ use std::{
 };
 
 use anyhow::Result;
-use metadata::{ArtifactInfo, Coordinate, Rule};
+use metadata::{ArtifactInfo, CharacterFile, Coordinate, KeyTable, RuleFileFormat, StatTable};
 use schemars::schema_for;
 
 const VSCODE_DIR: &str = "../../../.vscode";
@@ -17,10 +17,19 @@ fn main() -> Result<()> {
         artifact_info_schema,
     )?;
 
+    let stat_table_schema = serde_json::to_string_pretty(&schema_for!(StatTable))?;
+    fs::write(vscode_dir.join("stat_table.schema.json"), stat_table_schema)?;
+
     let coordinate_schema = serde_json::to_string_pretty(&schema_for!(Coordinate))?;
     fs::write(vscode_dir.join("coordinate.schema.json"), coordinate_schema)?;
 
-    let rules_schema = serde_json::to_string_pretty(&schema_for!(Vec<Rule>))?;
+    let rules_schema = serde_json::to_string_pretty(&schema_for!(RuleFileFormat))?;
     fs::write(vscode_dir.join("rules.schema.json"), rules_schema)?;
+
+    let characters_schema = serde_json::to_string_pretty(&schema_for!(CharacterFile))?;
+    fs::write(vscode_dir.join("characters.schema.json"), characters_schema)?;
+
+    let key_table_schema = serde_json::to_string_pretty(&schema_for!(KeyTable))?;
+    fs::write(vscode_dir.join("key_table.schema.json"), key_table_schema)?;
     Ok(())
 }